@@ -0,0 +1,105 @@
+//! 订单账户的可读输出
+
+use chrono::DateTime;
+use sol_escrow_client::Escrow;
+use solana_sdk::pubkey::Pubkey;
+
+
+// 按精度格式化代币数量，去掉小数部分末尾的0
+pub fn format_amount(amount: u64, decimals: u8) -> String {
+    if decimals == 0 {
+        return amount.to_string();
+    }
+    let scale = 10u128.pow(decimals as u32);
+    let whole = amount as u128 / scale;
+    let fraction = amount as u128 % scale;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{fraction:0width$}", width = decimals as usize);
+    format!("{whole}.{}", fraction.trim_end_matches('0'))
+}
+
+fn format_duration(seconds: i64) -> String {
+    let (days, hours, minutes) = (seconds / 86_400, seconds % 86_400 / 3_600, seconds % 3_600 / 60);
+    match (days, hours) {
+        (0, 0) => format!("{minutes}m"),
+        (0, _) => format!("{hours}h {minutes}m"),
+        _ => format!("{days}d {hours}h"),
+    }
+}
+
+// 超时时间及其与当前时间的间隔
+pub fn format_expiration(expiration: i64, now: i64) -> String {
+    let time = DateTime::from_timestamp(expiration, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| expiration.to_string());
+    if expiration > now {
+        format!("{time} (in {})", format_duration(expiration - now))
+    } else {
+        format!("{time} (expired {} ago)", format_duration(now - expiration))
+    }
+}
+
+fn format_optional(address: Option<Pubkey>, missing: &str) -> String {
+    address.map_or_else(|| missing.to_string(), |address| address.to_string())
+}
+
+
+// 订单详情
+pub fn print_escrow(address: &Pubkey, escrow: &Escrow, decimals: u8, now: i64) {
+    let asset = if escrow.is_native { "SOL".to_string() } else { escrow.token_mint.to_string() };
+    let kind = if escrow.is_listing { "listing" } else { "order" };
+
+    println!("{:<14}{address} ({kind} #{})", "Escrow", escrow.order_id);
+    println!("{:<14}{:?}", "Status", escrow.status);
+    println!("{:<14}{}", "Buyer", escrow.buyer);
+    println!("{:<14}{}", "Seller", format_optional(escrow.seller, if escrow.open_offer { "(open offer)" } else { "-" }));
+    println!("{:<14}{asset}", "Asset");
+    println!("{:<14}{}", "Amount", format_amount(escrow.amount, decimals));
+    println!("{:<14}{}", "Released", format_amount(escrow.released_amount, decimals));
+    println!("{:<14}{}", "Expiration", format_expiration(escrow.expiration, now));
+    if let Some(release_after) = escrow.release_after {
+        println!("{:<14}{}", "Release after", format_expiration(release_after, now));
+    }
+    println!("{:<14}{}", "Arbitrator", format_optional(escrow.arbitrator, "-"));
+    if let Some(nft_mint) = escrow.nft_mint {
+        println!("{:<14}{nft_mint}", "NFT");
+    }
+    for (index, milestone) in escrow.milestones.iter().enumerate() {
+        let state = if milestone.released { "released" } else { "pending" };
+        println!("{:<14}#{index} {} ({state})", "Milestone", format_amount(milestone.amount, decimals));
+    }
+}
+
+// 订单列表中的一行
+pub fn print_escrow_row(address: &Pubkey, escrow: &Escrow, decimals: u8, now: i64) {
+    println!(
+        "{address}  {:<10} {:>20}  {}",
+        format!("{:?}", escrow.status),
+        format_amount(escrow.amount, decimals),
+        format_expiration(escrow.expiration, now)
+    );
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts_use_mint_decimals() {
+        assert_eq!(format_amount(1_500_000, 6), "1.5");
+        assert_eq!(format_amount(1_000_000_000, 9), "1");
+        assert_eq!(format_amount(1, 6), "0.000001");
+        assert_eq!(format_amount(42, 0), "42");
+        assert_eq!(format_amount(u64::MAX, 9), "18446744073.709551615");
+    }
+
+    #[test]
+    fn expiration_shows_remaining_or_elapsed_time() {
+        assert_eq!(format_expiration(90_000, 0), "1970-01-02 01:00:00 UTC (in 1d 1h)");
+        assert_eq!(format_expiration(3_600, 3_900), "1970-01-01 01:00:00 UTC (expired 5m ago)");
+        assert_eq!(format_expiration(7_500, 0), "1970-01-01 02:05:00 UTC (in 2h 5m)");
+    }
+}
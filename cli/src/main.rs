@@ -0,0 +1,503 @@
+//! sol-escrow 命令行工具：创建、付款、取消、确认、放款、超时处理订单，以及查看订单账户
+
+mod display;
+mod rpc;
+
+use std::{collections::HashMap, future, path::PathBuf};
+
+use anchor_lang::Discriminator;
+use anchor_spl::{
+    associated_token::get_associated_token_address_with_program_id,
+    token_2022::spl_token_2022::{self, offchain::add_extra_account_metas},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::{Args, Parser, Subcommand};
+use futures_executor::block_on;
+use serde_json::json;
+use sol_escrow_client::{
+    instructions::{self as ix, NftTransfer, SellerNft},
+    pda, state, Asset, CreateOrderArgs, Escrow,
+};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair},
+    signer::Signer,
+    sysvar,
+    transaction::Transaction,
+};
+
+use crate::rpc::RpcClient;
+
+// SOL的精度
+const NATIVE_DECIMALS: u8 = 9;
+
+// 代币mint账户中精度字段的偏移，SPL Token 与 Token-2022 相同
+const MINT_DECIMALS_OFFSET: usize = 44;
+
+// Clock 账户中 unix_timestamp 字段的偏移
+const CLOCK_TIMESTAMP_OFFSET: usize = 32;
+
+
+#[derive(Parser)]
+#[command(name = "sol-escrow", version, about = "Operate sol-escrow orders from the command line")]
+struct Cli {
+    /// RPC endpoint, e.g. a local test validator
+    #[arg(short, long, global = true, env = "SOL_ESCROW_RPC_URL", default_value = "http://127.0.0.1:8899")]
+    url: String,
+
+    /// Keypair file that signs and pays for transactions [default: ~/.config/solana/id.json]
+    #[arg(short, long, global = true, env = "SOL_ESCROW_KEYPAIR")]
+    keypair: Option<PathBuf>,
+
+    /// Print the signed transaction as base64 instead of sending it
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// 订单定位：买家与订单编号
+#[derive(Args)]
+struct OrderRef {
+    /// Order id chosen by the buyer
+    #[arg(long)]
+    order_id: u64,
+
+    /// Buyer of the order [default: the keypair]
+    #[arg(long)]
+    buyer: Option<Pubkey>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create an order as the buyer
+    Create {
+        /// Order id, unique per buyer
+        #[arg(long)]
+        order_id: u64,
+
+        /// Payment token mint; omit to pay in SOL
+        #[arg(long)]
+        mint: Option<Pubkey>,
+
+        /// Amount in base units
+        #[arg(long)]
+        amount: u64,
+
+        /// Seconds until the order expires
+        #[arg(long, default_value_t = 86_400)]
+        expires_in: i64,
+
+        /// Seller allowed to confirm; omit to accept any seller
+        #[arg(long)]
+        seller: Option<Pubkey>,
+
+        /// Arbitrator who can resolve disputes
+        #[arg(long)]
+        arbitrator: Option<Pubkey>,
+
+        /// Seconds after seller confirmation before the seller may release the funds
+        #[arg(long)]
+        release_delay: Option<i64>,
+
+        /// Deposit the funds in the same transaction
+        #[arg(long)]
+        fund: bool,
+    },
+    /// Deposit the funds of an order as the buyer
+    Fund {
+        #[arg(long)]
+        order_id: u64,
+    },
+    /// Cancel an unconfirmed order and refund the buyer
+    Cancel {
+        #[arg(long)]
+        order_id: u64,
+    },
+    /// Confirm an order as the seller
+    Confirm {
+        #[command(flatten)]
+        order: OrderRef,
+
+        /// NFT delivered to a collection order, any verified member of the collection
+        #[arg(long)]
+        nft_mint: Option<Pubkey>,
+    },
+    /// Release the escrowed funds to the seller
+    Release(OrderRef),
+    /// Expire an order past its expiration: refund an unconfirmed order, or hand a confirmed one without a release delay to the arbitrator
+    Timeout(OrderRef),
+    /// Show a single escrow account
+    Show {
+        #[command(flatten)]
+        order: Option<OrderRef>,
+
+        /// Escrow account address
+        #[arg(long, conflicts_with_all = ["order_id", "buyer"])]
+        address: Option<Pubkey>,
+    },
+    /// List escrow accounts
+    List {
+        #[arg(long)]
+        buyer: Option<Pubkey>,
+
+        #[arg(long)]
+        seller: Option<Pubkey>,
+
+        /// Only show orders in this status, e.g. funded
+        #[arg(long)]
+        status: Option<String>,
+    },
+}
+
+
+struct Client {
+    rpc: RpcClient,
+    keypair: PathBuf,
+    dry_run: bool,
+}
+
+impl Client {
+    fn payer(&self) -> Result<Keypair> {
+        read_keypair_file(&self.keypair).map_err(|error| anyhow!("failed to read keypair {}: {error}", self.keypair.display()))
+    }
+
+    // 未指定买家时使用签名账户
+    fn buyer(&self, order: &OrderRef) -> Result<Pubkey> {
+        match order.buyer {
+            Some(buyer) => Ok(buyer),
+            None => Ok(self.payer()?.pubkey()),
+        }
+    }
+
+    // 链上时间，与程序的超时判断保持一致
+    fn now(&self) -> Result<i64> {
+        let clock = self.rpc.get_account(&sysvar::clock::ID)?.context("clock sysvar not found")?;
+        let timestamp = clock
+            .data
+            .get(CLOCK_TIMESTAMP_OFFSET..CLOCK_TIMESTAMP_OFFSET + 8)
+            .context("invalid clock sysvar")?;
+        Ok(i64::from_le_bytes(timestamp.try_into()?))
+    }
+
+    fn escrow(&self, address: &Pubkey) -> Result<Escrow> {
+        let account = self.rpc.get_account(address)?.with_context(|| format!("escrow {address} not found"))?;
+        state::escrow(&account.data).map_err(|error| anyhow!("{address} is not an escrow account: {error}"))
+    }
+
+    // mint账户的所有者即代币程序
+    fn token_program(&self, mint: &Pubkey) -> Result<Pubkey> {
+        let account = self.rpc.get_account(mint)?.with_context(|| format!("mint {mint} not found"))?;
+        Ok(account.owner)
+    }
+
+    fn asset(&self, mint: Option<Pubkey>) -> Result<Asset> {
+        match mint {
+            Some(mint) => Ok(Asset::Token { mint, program: self.token_program(&mint)? }),
+            None => Ok(Asset::Native),
+        }
+    }
+
+    fn escrow_asset(&self, escrow: &Escrow) -> Result<Asset> {
+        self.asset((!escrow.is_native).then_some(escrow.token_mint))
+    }
+
+    fn decimals(&self, escrow: &Escrow) -> Result<u8> {
+        if escrow.is_native {
+            return Ok(NATIVE_DECIMALS);
+        }
+        let mint = self.rpc.get_account(&escrow.token_mint)?.with_context(|| format!("mint {} not found", escrow.token_mint))?;
+        mint.data.get(MINT_DECIMALS_OFFSET).copied().context("invalid mint account")
+    }
+
+    // Token-2022 转账钩子所需的额外账户：按每个收款账户解析后去重，程序将其传给每笔转账
+    fn hook_accounts(
+        &self,
+        asset: &Asset,
+        source: &Pubkey,
+        authority: &Pubkey,
+        destinations: &[Pubkey],
+        amount: u64,
+    ) -> Result<Vec<AccountMeta>> {
+        let mint = match asset.mint() {
+            Some(mint) if asset.program() == spl_token_2022::ID => mint,
+            _ => return Ok(Vec::new()),
+        };
+        let fetch = |address: Pubkey| {
+            future::ready(self.rpc.get_account(&address).map(|account| account.map(|account| account.data)).map_err(Into::into))
+        };
+
+        let mut accounts: Vec<AccountMeta> = Vec::new();
+        for destination in destinations {
+            let mut transfer = Instruction::new_with_bytes(
+                asset.program(),
+                &[],
+                vec![
+                    AccountMeta::new(*source, false),
+                    AccountMeta::new_readonly(mint, false),
+                    AccountMeta::new(*destination, false),
+                    AccountMeta::new_readonly(*authority, false),
+                ],
+            );
+            block_on(add_extra_account_metas(&mut transfer, source, &mint, destination, authority, amount, fetch))
+                .map_err(|error| anyhow!("failed to resolve transfer hook accounts of {mint}: {error}"))?;
+            for account in transfer.accounts.split_off(4) {
+                if !accounts.iter().any(|existing| existing.pubkey == account.pubkey) {
+                    accounts.push(account);
+                }
+            }
+        }
+        Ok(accounts)
+    }
+
+    // 托管资金转出时的转账钩子账户，由订单账户签名
+    fn payout_hook_accounts(&self, escrow: &Pubkey, asset: &Asset, owners: &[Pubkey], amount: u64) -> Result<Vec<AccountMeta>> {
+        let Some(vault) = asset.ata(escrow) else {
+            return Ok(Vec::new());
+        };
+        let destinations: Vec<Pubkey> = owners.iter().filter_map(|owner| asset.ata(owner)).collect();
+        self.hook_accounts(asset, &vault, escrow, &destinations, amount)
+    }
+
+    // 买家存入资金时的转账钩子账户
+    fn deposit_hook_accounts(&self, buyer: &Pubkey, order_id: u64, asset: &Asset, amount: u64) -> Result<Vec<AccountMeta>> {
+        let (Some(source), Some(vault)) = (asset.ata(buyer), asset.ata(&pda::order(buyer, order_id))) else {
+            return Ok(Vec::new());
+        };
+        self.hook_accounts(asset, &source, buyer, &[vault], amount)
+    }
+
+    fn submit(&self, payer: &Keypair, instructions: &[Instruction]) -> Result<()> {
+        let blockhash = self.rpc.latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), &[payer], blockhash);
+
+        if self.dry_run {
+            println!("{}", BASE64.encode(bincode::serialize(&transaction)?));
+            return Ok(());
+        }
+        let signature = self.rpc.send_and_confirm(&transaction)?;
+        println!("Signature: {signature}");
+        Ok(())
+    }
+}
+
+
+struct CreateOptions {
+    seller: Option<Pubkey>,
+    arbitrator: Option<Pubkey>,
+    release_delay: Option<i64>,
+    fund: bool,
+}
+
+fn create(client: &Client, order_id: u64, mint: Option<Pubkey>, amount: u64, expires_in: i64, options: CreateOptions) -> Result<()> {
+    let payer = client.payer()?;
+    let asset = client.asset(mint)?;
+    let args = CreateOrderArgs {
+        amount,
+        expiration: client.now()? + expires_in,
+        nft_mint: None,
+        collection_mint: None,
+        buyer_nft_account: None,
+        is_nft: false,
+        arbitrator: options.arbitrator,
+        open_offer: options.seller.is_none(),
+        seller: options.seller,
+        milestones: Vec::new(),
+        release_delay: options.release_delay,
+    };
+
+    let instruction = if options.fund {
+        let mut instruction = ix::create_and_fund_order(&payer.pubkey(), order_id, &asset, args);
+        instruction.accounts.extend(client.deposit_hook_accounts(&payer.pubkey(), order_id, &asset, amount)?);
+        instruction
+    } else {
+        ix::create_order(&payer.pubkey(), order_id, &asset, args)
+    };
+    println!("Order: {}", pda::order(&payer.pubkey(), order_id));
+    client.submit(&payer, &[instruction])
+}
+
+fn fund(client: &Client, order_id: u64) -> Result<()> {
+    let payer = client.payer()?;
+    let escrow = client.escrow(&pda::order(&payer.pubkey(), order_id))?;
+    let asset = client.escrow_asset(&escrow)?;
+    let mut instruction = ix::buyer_payment(&payer.pubkey(), order_id, &asset);
+    instruction.accounts.extend(client.deposit_hook_accounts(&payer.pubkey(), order_id, &asset, escrow.amount)?);
+    client.submit(&payer, &[instruction])
+}
+
+fn cancel(client: &Client, order_id: u64) -> Result<()> {
+    let payer = client.payer()?;
+    let escrow = client.escrow(&pda::order(&payer.pubkey(), order_id))?;
+    let asset = client.escrow_asset(&escrow)?;
+    let mut instruction = ix::order_cancellation(&payer.pubkey(), order_id, &asset);
+    let escrow_address = pda::order(&payer.pubkey(), order_id);
+    instruction.accounts.extend(client.payout_hook_accounts(&escrow_address, &asset, &[payer.pubkey()], escrow.amount)?);
+    client.submit(&payer, &[instruction])
+}
+
+// NFT订单由卖家将NFT转入托管；集合订单由卖家指定交付的NFT，买家以关联代币账户接收
+fn confirm(client: &Client, order: &OrderRef, nft_mint: Option<Pubkey>) -> Result<()> {
+    let payer = client.payer()?;
+    let seller = payer.pubkey();
+    let buyer = client.buyer(order)?;
+    let escrow = client.escrow(&pda::order(&buyer, order.order_id))?;
+
+    let nft_mint = match (escrow.is_nft, escrow.collection_mint) {
+        (false, _) => None,
+        (true, Some(_)) => Some(nft_mint.context("collection orders require --nft-mint")?),
+        (true, None) => escrow.nft_mint,
+    };
+    let instruction = match nft_mint {
+        Some(mint) => {
+            let token_program = client.token_program(&mint)?;
+            let buyer_nft_account = match escrow.buyer_nft_account {
+                Some(account) => account,
+                None => get_associated_token_address_with_program_id(&buyer, &mint, &token_program),
+            };
+            let nft = SellerNft {
+                mint,
+                token_program,
+                seller_nft_account: get_associated_token_address_with_program_id(&seller, &mint, &token_program),
+                buyer_nft_account,
+            };
+            ix::seller_confirmation_nft(&seller, &buyer, order.order_id, escrow.amount, escrow.expiration, &nft)
+        }
+        None => ix::seller_confirmation(&seller, &buyer, order.order_id, escrow.amount, escrow.expiration),
+    };
+    client.submit(&payer, &[instruction])
+}
+
+// 版税创作者的收款账户，按订单中记录的顺序追加
+fn creator_accounts(escrow: &Escrow, asset: &Asset) -> Vec<AccountMeta> {
+    if escrow.royalty_bps == 0 {
+        return Vec::new();
+    }
+    escrow
+        .royalty_creators
+        .iter()
+        .map(|creator| AccountMeta::new(asset.ata(&creator.address).unwrap_or(creator.address), false))
+        .collect()
+}
+
+fn release(client: &Client, order: &OrderRef) -> Result<()> {
+    let payer = client.payer()?;
+    let buyer = client.buyer(order)?;
+    let escrow = client.escrow(&pda::order(&buyer, order.order_id))?;
+    let asset = client.escrow_asset(&escrow)?;
+    let seller = escrow.seller.context("order has no seller yet")?;
+
+    let config_account = client.rpc.get_account(&pda::config())?.context("protocol config not initialized")?;
+    let treasury = state::config(&config_account.data).map_err(|error| anyhow!("invalid protocol config: {error}"))?.treasury;
+
+    let mut instruction = match escrow.nft_mint.filter(|_| escrow.is_nft) {
+        Some(mint) => {
+            let nft = NftTransfer {
+                mint,
+                token_program: client.token_program(&mint)?,
+                destination: escrow.buyer_nft_account.context("NFT order has no buyer NFT account")?,
+            };
+            ix::escrow_release_nft(&payer.pubkey(), &buyer, &seller, order.order_id, &asset, &treasury, &nft)
+        }
+        None => ix::escrow_release(&payer.pubkey(), &buyer, &seller, order.order_id, &asset, &treasury),
+    };
+    instruction.accounts.extend(creator_accounts(&escrow, &asset));
+    // 转账钩子账户位于版税创作者账户之后
+    let mut payees = vec![seller, treasury];
+    payees.extend(escrow.royalty_creators.iter().filter(|_| escrow.royalty_bps > 0).map(|creator| creator.address));
+    let escrow_address = pda::order(&buyer, order.order_id);
+    instruction.accounts.extend(client.payout_hook_accounts(&escrow_address, &asset, &payees, escrow.amount)?);
+    client.submit(&payer, &[instruction])
+}
+
+// 只有卖家确认前的订单会超时退款，已确认的订单转入争议，都不涉及托管的NFT
+fn timeout(client: &Client, order: &OrderRef) -> Result<()> {
+    let payer = client.payer()?;
+    let buyer = client.buyer(order)?;
+    let escrow = client.escrow(&pda::order(&buyer, order.order_id))?;
+    let asset = client.escrow_asset(&escrow)?;
+
+    let mut instruction = ix::timeout_check(&buyer, order.order_id, &asset);
+    let escrow_address = pda::order(&buyer, order.order_id);
+    instruction.accounts.extend(client.payout_hook_accounts(&escrow_address, &asset, &[buyer], escrow.amount)?);
+    client.submit(&payer, &[instruction])
+}
+
+fn show(client: &Client, order: Option<&OrderRef>, address: Option<Pubkey>) -> Result<()> {
+    let address = match (address, order) {
+        (Some(address), _) => address,
+        (None, Some(order)) => pda::order(&client.buyer(order)?, order.order_id),
+        (None, None) => bail!("either --address or --order-id is required"),
+    };
+    let escrow = client.escrow(&address)?;
+    display::print_escrow(&address, &escrow, client.decimals(&escrow)?, client.now()?);
+    Ok(())
+}
+
+fn list(client: &Client, buyer: Option<Pubkey>, seller: Option<Pubkey>, status: Option<&str>) -> Result<()> {
+    let mut filters = vec![json!({ "memcmp": { "offset": 0, "bytes": BASE64.encode(Escrow::DISCRIMINATOR), "encoding": "base64" } })];
+    // 买家紧随鉴别符之后；卖家为可选字段，偏移不固定，在本地过滤
+    if let Some(buyer) = buyer {
+        filters.push(json!({ "memcmp": { "offset": 8, "bytes": buyer.to_string() } }));
+    }
+
+    let mut escrows: Vec<(Pubkey, Escrow)> = client
+        .rpc
+        .get_program_accounts(&sol_escrow_client::ID, filters)?
+        .into_iter()
+        .filter_map(|(address, account)| state::escrow(&account.data).ok().map(|escrow| (address, escrow)))
+        .filter(|(_, escrow)| seller.is_none() || escrow.seller == seller)
+        .filter(|(_, escrow)| status.is_none_or(|status| format!("{:?}", escrow.status).eq_ignore_ascii_case(status)))
+        .collect();
+    escrows.sort_by_key(|(_, escrow)| (escrow.buyer, escrow.order_id));
+
+    let now = client.now()?;
+    let mut decimals = HashMap::new();
+    for (address, escrow) in &escrows {
+        let escrow_decimals = match decimals.get(&(escrow.is_native, escrow.token_mint)) {
+            Some(&value) => value,
+            None => {
+                let value = client.decimals(escrow)?;
+                decimals.insert((escrow.is_native, escrow.token_mint), value);
+                value
+            }
+        };
+        display::print_escrow_row(address, escrow, escrow_decimals, now);
+    }
+    if escrows.is_empty() {
+        println!("No escrow accounts found");
+    }
+    Ok(())
+}
+
+
+fn default_keypair() -> PathBuf {
+    let home = std::env::var_os("HOME").unwrap_or_default();
+    PathBuf::from(home).join(".config/solana/id.json")
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let client = Client {
+        rpc: RpcClient::new(&cli.url),
+        keypair: cli.keypair.unwrap_or_else(default_keypair),
+        dry_run: cli.dry_run,
+    };
+
+    match cli.command {
+        Command::Create { order_id, mint, amount, expires_in, seller, arbitrator, release_delay, fund } => {
+            let options = CreateOptions { seller, arbitrator, release_delay, fund };
+            create(&client, order_id, mint, amount, expires_in, options)
+        }
+        Command::Fund { order_id } => fund(&client, order_id),
+        Command::Cancel { order_id } => cancel(&client, order_id),
+        Command::Confirm { order, nft_mint } => confirm(&client, &order, nft_mint),
+        Command::Release(order) => release(&client, &order),
+        Command::Timeout(order) => timeout(&client, &order),
+        Command::Show { order, address } => show(&client, order.as_ref(), address),
+        Command::List { buyer, seller, status } => list(&client, buyer, seller, status.as_deref()),
+    }
+}
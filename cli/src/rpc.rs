@@ -0,0 +1,114 @@
+//! 精简的 JSON-RPC 客户端，只实现命令行用到的方法
+
+use std::{str::FromStr, thread, time::Duration};
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde_json::{json, Value};
+use solana_sdk::{hash::Hash, pubkey::Pubkey, signature::Signature, transaction::Transaction};
+
+// 等待交易确认的轮询次数与间隔
+const CONFIRM_ATTEMPTS: usize = 60;
+const CONFIRM_INTERVAL: Duration = Duration::from_millis(500);
+
+
+/// 链上账户的所有者与数据
+pub struct RpcAccount {
+    pub owner: Pubkey,
+    pub data: Vec<u8>,
+}
+
+pub struct RpcClient {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl RpcClient {
+    pub fn new(url: &str) -> Self {
+        Self { url: url.to_string(), agent: ureq::agent() }
+    }
+
+    fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let mut response: Value = self
+            .agent
+            .post(&self.url)
+            .send_json(body)
+            .with_context(|| format!("{method} request to {} failed", self.url))?
+            .into_json()?;
+
+        if let Some(error) = response.get("error") {
+            bail!("{method} failed: {}", error["message"].as_str().unwrap_or("unknown error"));
+        }
+        Ok(response["result"].take())
+    }
+
+    pub fn get_account(&self, address: &Pubkey) -> Result<Option<RpcAccount>> {
+        let result = self.request(
+            "getAccountInfo",
+            json!([address.to_string(), { "encoding": "base64", "commitment": "confirmed" }]),
+        )?;
+        match &result["value"] {
+            Value::Null => Ok(None),
+            account => parse_account(account).map(Some),
+        }
+    }
+
+    // 按过滤条件获取程序账户
+    pub fn get_program_accounts(&self, program_id: &Pubkey, filters: Vec<Value>) -> Result<Vec<(Pubkey, RpcAccount)>> {
+        let result = self.request(
+            "getProgramAccounts",
+            json!([program_id.to_string(), { "encoding": "base64", "commitment": "confirmed", "filters": filters }]),
+        )?;
+        result
+            .as_array()
+            .ok_or_else(|| anyhow!("getProgramAccounts returned no accounts"))?
+            .iter()
+            .map(|entry| Ok((parse_pubkey(&entry["pubkey"])?, parse_account(&entry["account"])?)))
+            .collect()
+    }
+
+    pub fn latest_blockhash(&self) -> Result<Hash> {
+        let result = self.request("getLatestBlockhash", json!([{ "commitment": "confirmed" }]))?;
+        let blockhash = result["value"]["blockhash"].as_str().ok_or_else(|| anyhow!("missing blockhash"))?;
+        Hash::from_str(blockhash).map_err(|error| anyhow!("invalid blockhash: {error}"))
+    }
+
+    // 发送交易并等待确认，交易失败时返回错误
+    pub fn send_and_confirm(&self, transaction: &Transaction) -> Result<Signature> {
+        let encoded = BASE64.encode(bincode::serialize(transaction)?);
+        let result = self.request("sendTransaction", json!([encoded, { "encoding": "base64", "preflightCommitment": "confirmed" }]))?;
+        let signature = result.as_str().ok_or_else(|| anyhow!("sendTransaction returned no signature"))?;
+        let signature = Signature::from_str(signature)?;
+
+        for _ in 0..CONFIRM_ATTEMPTS {
+            let statuses = self.request("getSignatureStatuses", json!([[signature.to_string()]]))?;
+            let status = &statuses["value"][0];
+            if !status.is_null() {
+                if !status["err"].is_null() {
+                    bail!("transaction {signature} failed: {}", status["err"]);
+                }
+                if matches!(status["confirmationStatus"].as_str(), Some("confirmed" | "finalized")) {
+                    return Ok(signature);
+                }
+            }
+            thread::sleep(CONFIRM_INTERVAL);
+        }
+        bail!("transaction {signature} was not confirmed in time")
+    }
+}
+
+
+fn parse_pubkey(value: &Value) -> Result<Pubkey> {
+    let address = value.as_str().ok_or_else(|| anyhow!("missing address"))?;
+    Pubkey::from_str(address).map_err(|error| anyhow!("invalid address {address}: {error}"))
+}
+
+// 账户数据按 ["<base64>", "base64"] 返回
+fn parse_account(account: &Value) -> Result<RpcAccount> {
+    let data = account["data"][0].as_str().ok_or_else(|| anyhow!("missing account data"))?;
+    Ok(RpcAccount {
+        owner: parse_pubkey(&account["owner"])?,
+        data: BASE64.decode(data)?,
+    })
+}
@@ -0,0 +1,650 @@
+//! 指令构造，账户顺序与 `sol_escrow::accounts` 中的定义一致
+//!
+//! 可选账户传 `None` 时按 Anchor 约定填入程序ID。NFT版税创作者账户与转账钩子的额外账户
+//! 通过 remaining accounts 传入，需要时追加到返回指令的 `accounts` 末尾。
+
+use anchor_lang::{
+    prelude::Pubkey,
+    Result,
+    solana_program::instruction::Instruction,
+    system_program,
+    InstructionData,
+    ToAccountMetas,
+};
+use anchor_spl::associated_token::spl_associated_token_account;
+use sol_escrow::{accounts, instruction as args};
+
+use crate::{pda, Asset, CreateListingArgs, CreateOrderArgs, ErrorCode};
+
+
+/// 卖家确认NFT订单所需的账户
+#[derive(Clone, Copy, Debug)]
+pub struct SellerNft {
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub seller_nft_account: Pubkey,
+    pub buyer_nft_account: Pubkey,
+}
+
+/// 从NFT托管账户转出NFT：放款时交付买家，超时时退还卖家，仲裁时交付胜诉方
+#[derive(Clone, Copy, Debug)]
+pub struct NftTransfer {
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub destination: Pubkey,
+}
+
+
+fn instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: sol_escrow::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+
+pub fn initialize_config(admin: &Pubkey, treasury: &Pubkey, fee_bps: u16) -> Instruction {
+    instruction(
+        accounts::InitializeConfig {
+            admin: *admin,
+            config: pda::config(),
+            program: sol_escrow::ID,
+            program_data: pda::program_data(),
+            system_program: system_program::ID,
+        },
+        args::InitializeConfig { treasury: *treasury, fee_bps },
+    )
+}
+
+pub fn update_config(admin: &Pubkey, new_admin: Option<Pubkey>, treasury: &Pubkey, fee_bps: u16, keeper_bounty: u64) -> Instruction {
+    instruction(
+        accounts::UpdateConfig { admin: *admin, config: pda::config() },
+        args::UpdateConfig { new_admin, treasury: *treasury, fee_bps, keeper_bounty },
+    )
+}
+
+pub fn set_mint_fee(admin: &Pubkey, mint: &Pubkey, fee_bps: u16) -> Instruction {
+    instruction(
+        accounts::SetMintFee {
+            admin: *admin,
+            config: pda::config(),
+            mint: *mint,
+            mint_fee: pda::mint_fee(mint),
+            system_program: system_program::ID,
+        },
+        args::SetMintFee { fee_bps },
+    )
+}
+
+
+pub fn create_order(buyer: &Pubkey, order_id: u64, asset: &Asset, order: CreateOrderArgs) -> Instruction {
+    let escrow = pda::order(buyer, order_id);
+    instruction(
+        accounts::CreateOrder {
+            signer: *buyer,
+            mint: asset.mint(),
+            escrow,
+            escrow_vault: asset.ata(&escrow),
+            system_program: system_program::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: asset.program(),
+        },
+        args::CreateOrder { order_id, args: order },
+    )
+}
+
+pub fn create_and_fund_order(buyer: &Pubkey, order_id: u64, asset: &Asset, order: CreateOrderArgs) -> Instruction {
+    let escrow = pda::order(buyer, order_id);
+    instruction(
+        accounts::CreateAndFundOrder {
+            signer: *buyer,
+            mint: asset.mint(),
+            escrow,
+            escrow_vault: asset.ata(&escrow),
+            buyer_token_account: asset.ata(buyer),
+            system_program: system_program::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: asset.program(),
+        },
+        args::CreateAndFundOrder { order_id, args: order },
+    )
+}
+
+fn buyer_payment_accounts(buyer: &Pubkey, order_id: u64, asset: &Asset) -> accounts::BuyerPayment {
+    let escrow = pda::order(buyer, order_id);
+    accounts::BuyerPayment {
+        buyer: *buyer,
+        mint: asset.mint(),
+        escrow,
+        escrow_vault: asset.ata(&escrow),
+        buyer_token_account: asset.ata(buyer),
+        system_program: system_program::ID,
+        associated_token_program: spl_associated_token_account::ID,
+        token_program: asset.program(),
+    }
+}
+
+pub fn buyer_payment(buyer: &Pubkey, order_id: u64, asset: &Asset) -> Instruction {
+    instruction(
+        buyer_payment_accounts(buyer, order_id, asset),
+        args::BuyerPayment { _order_id: order_id },
+    )
+}
+
+pub fn amend_order(buyer: &Pubkey, order_id: u64, asset: &Asset, amount: u64, expiration: i64) -> Instruction {
+    instruction(
+        buyer_payment_accounts(buyer, order_id, asset),
+        args::AmendOrder { _order_id: order_id, amount, expiration },
+    )
+}
+
+pub fn propose_counter(seller: &Pubkey, buyer: &Pubkey, order_id: u64, amount: u64, expiration: i64) -> Instruction {
+    let escrow = pda::order(buyer, order_id);
+    instruction(
+        accounts::ProposeCounter {
+            seller: *seller,
+            buyer: *buyer,
+            escrow,
+            counter_offer: pda::counter_offer(&escrow, seller),
+            system_program: system_program::ID,
+        },
+        args::ProposeCounter { _order_id: order_id, amount, expiration },
+    )
+}
+
+// 接受还价：expected_amount 与 expected_expiration 为买家确认的还价条款，卖家在此之后修改则交易失败
+pub fn accept_counter(
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    order_id: u64,
+    asset: &Asset,
+    expected_amount: u64,
+    expected_expiration: i64,
+) -> Instruction {
+    let escrow = pda::order(buyer, order_id);
+    instruction(
+        accounts::AcceptCounter {
+            payment: buyer_payment_accounts(buyer, order_id, asset),
+            seller: *seller,
+            counter_offer: pda::counter_offer(&escrow, seller),
+        },
+        args::AcceptCounter { _order_id: order_id, expected_amount, expected_expiration },
+    )
+}
+
+// 关闭还价：signer 为撤回还价的卖家或拒绝还价的买家
+pub fn close_counter(signer: &Pubkey, buyer: &Pubkey, seller: &Pubkey, order_id: u64) -> Instruction {
+    let escrow = pda::order(buyer, order_id);
+    instruction(
+        accounts::CloseCounter {
+            signer: *signer,
+            buyer: *buyer,
+            seller: *seller,
+            escrow,
+            counter_offer: pda::counter_offer(&escrow, seller),
+        },
+        args::CloseCounter { _order_id: order_id },
+    )
+}
+
+pub fn order_cancellation(buyer: &Pubkey, order_id: u64, asset: &Asset) -> Instruction {
+    let escrow = pda::order(buyer, order_id);
+    instruction(
+        accounts::OrderCancellation {
+            buyer: *buyer,
+            mint: asset.mint(),
+            escrow,
+            escrow_vault: asset.ata(&escrow),
+            buyer_token_account: asset.ata(buyer),
+            system_program: system_program::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: asset.program(),
+        },
+        args::OrderCancellation { _order_id: order_id },
+    )
+}
+
+
+// 非NFT订单的卖家确认
+pub fn seller_confirmation(
+    seller: &Pubkey,
+    buyer: &Pubkey,
+    order_id: u64,
+    expected_amount: u64,
+    expected_expiration: i64,
+) -> Instruction {
+    seller_confirmation_with(seller, buyer, order_id, expected_amount, expected_expiration, None)
+}
+
+// NFT订单的卖家确认：NFT转入订单的NFT托管账户
+pub fn seller_confirmation_nft(
+    seller: &Pubkey,
+    buyer: &Pubkey,
+    order_id: u64,
+    expected_amount: u64,
+    expected_expiration: i64,
+    nft: &SellerNft,
+) -> Instruction {
+    seller_confirmation_with(seller, buyer, order_id, expected_amount, expected_expiration, Some(nft))
+}
+
+fn seller_confirmation_with(
+    seller: &Pubkey,
+    buyer: &Pubkey,
+    order_id: u64,
+    expected_amount: u64,
+    expected_expiration: i64,
+    nft: Option<&SellerNft>,
+) -> Instruction {
+    let escrow = pda::order(buyer, order_id);
+    instruction(
+        accounts::SellerConfirmation {
+            seller: *seller,
+            buyer: *buyer,
+            nft_mint: nft.map(|nft| nft.mint),
+            escrow,
+            seller_nft_account: nft.map(|nft| nft.seller_nft_account),
+            buyer_nft_account: nft.map(|nft| nft.buyer_nft_account),
+            nft_metadata: nft.map(|nft| pda::nft_metadata(&nft.mint)),
+            nft_vault: nft.map(|nft| pda::nft_vault(&escrow, &nft.mint, &nft.token_program)),
+            system_program: system_program::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: nft.map_or(anchor_spl::token::ID, |nft| nft.token_program),
+        },
+        args::SellerConfirmation { _order_id: order_id, expected_amount, expected_expiration },
+    )
+}
+
+fn escrow_release_accounts(
+    authority: &Pubkey,
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    order_id: u64,
+    asset: &Asset,
+    treasury: &Pubkey,
+    nft: Option<&NftTransfer>,
+) -> accounts::EscrowRelease {
+    let escrow = pda::order(buyer, order_id);
+    accounts::EscrowRelease {
+        authority: *authority,
+        buyer: *buyer,
+        seller: *seller,
+        mint: asset.mint(),
+        escrow,
+        escrow_vault: asset.ata(&escrow),
+        seller_token_account: asset.ata(seller),
+        config: pda::config(),
+        mint_fee: pda::mint_fee(&asset.fee_mint()),
+        treasury_token_account: asset.ata(treasury),
+        treasury: asset.mint().is_none().then_some(*treasury),
+        nft_mint: nft.map(|nft| nft.mint),
+        nft_vault: nft.map(|nft| pda::nft_vault(&escrow, &nft.mint, &nft.token_program)),
+        buyer_nft_account: nft.map(|nft| nft.destination),
+        nft_token_program: nft.map(|nft| nft.token_program),
+        system_program: system_program::ID,
+        associated_token_program: spl_associated_token_account::ID,
+        token_program: asset.program(),
+    }
+}
+
+// 放款：授权人为买家、仲裁者，或自动放款时间已到的卖家
+pub fn escrow_release(authority: &Pubkey, buyer: &Pubkey, seller: &Pubkey, order_id: u64, asset: &Asset, treasury: &Pubkey) -> Instruction {
+    instruction(
+        escrow_release_accounts(authority, buyer, seller, order_id, asset, treasury, None),
+        args::EscrowRelease { _order_id: order_id },
+    )
+}
+
+// NFT订单放款：托管的NFT交付买家
+pub fn escrow_release_nft(
+    authority: &Pubkey,
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    order_id: u64,
+    asset: &Asset,
+    treasury: &Pubkey,
+    nft: &NftTransfer,
+) -> Instruction {
+    instruction(
+        escrow_release_accounts(authority, buyer, seller, order_id, asset, treasury, Some(nft)),
+        args::EscrowRelease { _order_id: order_id },
+    )
+}
+
+pub fn release_milestone(
+    authority: &Pubkey,
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    order_id: u64,
+    asset: &Asset,
+    treasury: &Pubkey,
+    index: u8,
+) -> Instruction {
+    instruction(
+        escrow_release_accounts(authority, buyer, seller, order_id, asset, treasury, None),
+        args::ReleaseMilestone { _order_id: order_id, index },
+    )
+}
+
+fn timeout_accounts(buyer: &Pubkey, order_id: u64, asset: &Asset, nft: Option<(&Pubkey, &NftTransfer)>) -> accounts::TimeoutCheck {
+    let escrow = pda::order(buyer, order_id);
+    accounts::TimeoutCheck {
+        buyer: *buyer,
+        mint: asset.mint(),
+        escrow,
+        escrow_vault: asset.ata(&escrow),
+        buyer_token_account: asset.ata(buyer),
+        seller: nft.map(|(seller, _)| *seller),
+        nft_mint: nft.map(|(_, nft)| nft.mint),
+        nft_vault: nft.map(|(_, nft)| pda::nft_vault(&escrow, &nft.mint, &nft.token_program)),
+        seller_nft_account: nft.map(|(_, nft)| nft.destination),
+        nft_token_program: nft.map(|(_, nft)| nft.token_program),
+        system_program: system_program::ID,
+        associated_token_program: spl_associated_token_account::ID,
+        token_program: asset.program(),
+    }
+}
+
+pub fn timeout_check(buyer: &Pubkey, order_id: u64, asset: &Asset) -> Instruction {
+    instruction(
+        timeout_accounts(buyer, order_id, asset, None),
+        args::TimeoutCheck { _order_id: order_id },
+    )
+}
+
+// NFT订单超时：卖家已托管的NFT退还卖家
+pub fn timeout_check_nft(buyer: &Pubkey, order_id: u64, asset: &Asset, seller: &Pubkey, nft: &NftTransfer) -> Instruction {
+    instruction(
+        timeout_accounts(buyer, order_id, asset, Some((seller, nft))),
+        args::TimeoutCheck { _order_id: order_id },
+    )
+}
+
+pub fn crank_expired(keeper: &Pubkey, buyer: &Pubkey, order_id: u64, asset: &Asset) -> Instruction {
+    instruction(
+        accounts::CrankExpired {
+            keeper: *keeper,
+            timeout: timeout_accounts(buyer, order_id, asset, None),
+            config: pda::config(),
+        },
+        args::CrankExpired { _order_id: order_id },
+    )
+}
+
+pub fn crank_expired_nft(keeper: &Pubkey, buyer: &Pubkey, order_id: u64, asset: &Asset, seller: &Pubkey, nft: &NftTransfer) -> Instruction {
+    instruction(
+        accounts::CrankExpired {
+            keeper: *keeper,
+            timeout: timeout_accounts(buyer, order_id, asset, Some((seller, nft))),
+            config: pda::config(),
+        },
+        args::CrankExpired { _order_id: order_id },
+    )
+}
+
+pub fn close_order(buyer: &Pubkey, order_id: u64, asset: &Asset) -> Instruction {
+    let escrow = pda::order(buyer, order_id);
+    instruction(
+        accounts::CloseOrder {
+            buyer: *buyer,
+            mint: asset.mint(),
+            escrow,
+            escrow_vault: asset.ata(&escrow),
+            system_program: system_program::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: asset.program(),
+        },
+        args::CloseOrder { _order_id: order_id },
+    )
+}
+
+
+pub fn open_dispute(signer: &Pubkey, buyer: &Pubkey, order_id: u64) -> Instruction {
+    instruction(
+        accounts::OpenDispute {
+            signer: *signer,
+            buyer: *buyer,
+            escrow: pda::order(buyer, order_id),
+        },
+        args::OpenDispute { _order_id: order_id },
+    )
+}
+
+fn resolve_dispute_accounts(
+    arbitrator: &Pubkey,
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    order_id: u64,
+    asset: &Asset,
+    treasury: &Pubkey,
+    nft: Option<&NftTransfer>,
+) -> accounts::ResolveDispute {
+    let escrow = pda::order(buyer, order_id);
+    accounts::ResolveDispute {
+        arbitrator: *arbitrator,
+        buyer: *buyer,
+        seller: *seller,
+        mint: asset.mint(),
+        escrow,
+        escrow_vault: asset.ata(&escrow),
+        buyer_token_account: asset.ata(buyer),
+        seller_token_account: asset.ata(seller),
+        config: pda::config(),
+        mint_fee: pda::mint_fee(&asset.fee_mint()),
+        treasury_token_account: asset.ata(treasury),
+        treasury: asset.mint().is_none().then_some(*treasury),
+        nft_mint: nft.map(|nft| nft.mint),
+        nft_vault: nft.map(|nft| pda::nft_vault(&escrow, &nft.mint, &nft.token_program)),
+        nft_destination: nft.map(|nft| nft.destination),
+        nft_token_program: nft.map(|nft| nft.token_program),
+        system_program: system_program::ID,
+        associated_token_program: spl_associated_token_account::ID,
+        token_program: asset.program(),
+    }
+}
+
+// 仲裁：seller_bps 为卖家获得的未放款资金比例，单位：基点，卖家所得扣除协议手续费
+pub fn resolve_dispute(
+    arbitrator: &Pubkey,
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    order_id: u64,
+    asset: &Asset,
+    treasury: &Pubkey,
+    seller_bps: u16,
+) -> Instruction {
+    instruction(
+        resolve_dispute_accounts(arbitrator, buyer, seller, order_id, asset, treasury, None),
+        args::ResolveDispute { _order_id: order_id, seller_bps },
+    )
+}
+
+// NFT订单仲裁：卖家获得款项时NFT交付买家的NFT账户，否则退还卖家
+#[allow(clippy::too_many_arguments)]
+pub fn resolve_dispute_nft(
+    arbitrator: &Pubkey,
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    order_id: u64,
+    asset: &Asset,
+    treasury: &Pubkey,
+    seller_bps: u16,
+    nft: &NftTransfer,
+) -> Instruction {
+    instruction(
+        resolve_dispute_accounts(arbitrator, buyer, seller, order_id, asset, treasury, Some(nft)),
+        args::ResolveDispute { _order_id: order_id, seller_bps },
+    )
+}
+
+
+// 挂单的付款代币与出售资产都必须是代币，原生SOL没有mint与关联代币账户
+fn listing_mint(asset: &Asset) -> Result<Pubkey> {
+    asset.mint().ok_or_else(|| ErrorCode::MissingTokenAccount.into())
+}
+
+fn listing_ata(asset: &Asset, owner: &Pubkey) -> Result<Pubkey> {
+    asset.ata(owner).ok_or_else(|| ErrorCode::MissingTokenAccount.into())
+}
+
+fn create_listing_accounts(
+    seller: &Pubkey,
+    listing_id: u64,
+    payment: &Asset,
+    listed: &Asset,
+    nft: bool,
+) -> Result<accounts::CreateListing> {
+    let escrow = pda::listing(seller, listing_id);
+    let asset_mint = listing_mint(listed)?;
+    Ok(accounts::CreateListing {
+        seller: *seller,
+        mint: listing_mint(payment)?,
+        asset_mint,
+        escrow,
+        escrow_vault: listing_ata(listed, &escrow)?,
+        seller_asset_account: listing_ata(listed, seller)?,
+        nft_metadata: nft.then(|| pda::nft_metadata(&asset_mint)),
+        system_program: system_program::ID,
+        associated_token_program: spl_associated_token_account::ID,
+        token_program: payment.program(),
+        asset_token_program: listed.program(),
+    })
+}
+
+// 挂单：payment 为买家付款所用代币，listed 为出售的资产，均不支持原生SOL
+pub fn create_listing(
+    seller: &Pubkey,
+    listing_id: u64,
+    payment: &Asset,
+    listed: &Asset,
+    listing: CreateListingArgs,
+) -> Result<Instruction> {
+    Ok(instruction(
+        create_listing_accounts(seller, listing_id, payment, listed, false)?,
+        args::CreateListing { listing_id, args: listing },
+    ))
+}
+
+// NFT挂单：参数中 is_nft 为 true，附带元数据账户，成交时按其中记录的版税向创作者付款
+pub fn create_listing_nft(
+    seller: &Pubkey,
+    listing_id: u64,
+    payment: &Asset,
+    listed: &Asset,
+    listing: CreateListingArgs,
+) -> Result<Instruction> {
+    Ok(instruction(
+        create_listing_accounts(seller, listing_id, payment, listed, true)?,
+        args::CreateListing { listing_id, args: listing },
+    ))
+}
+
+pub fn fill_listing(
+    buyer: &Pubkey,
+    seller: &Pubkey,
+    listing_id: u64,
+    payment: &Asset,
+    listed: &Asset,
+    treasury: &Pubkey,
+) -> Result<Instruction> {
+    let escrow = pda::listing(seller, listing_id);
+    let mint = listing_mint(payment)?;
+    Ok(instruction(
+        accounts::FillListing {
+            buyer: *buyer,
+            seller: *seller,
+            mint,
+            asset_mint: listing_mint(listed)?,
+            escrow,
+            escrow_vault: listing_ata(listed, &escrow)?,
+            buyer_token_account: listing_ata(payment, buyer)?,
+            seller_token_account: listing_ata(payment, seller)?,
+            buyer_asset_account: listing_ata(listed, buyer)?,
+            config: pda::config(),
+            mint_fee: pda::mint_fee(&mint),
+            treasury_token_account: listing_ata(payment, treasury)?,
+            system_program: system_program::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: payment.program(),
+            asset_token_program: listed.program(),
+        },
+        args::FillListing { _listing_id: listing_id },
+    ))
+}
+
+pub fn cancel_listing(seller: &Pubkey, listing_id: u64, listed: &Asset) -> Result<Instruction> {
+    let escrow = pda::listing(seller, listing_id);
+    Ok(instruction(
+        accounts::CancelListing {
+            seller: *seller,
+            asset_mint: listing_mint(listed)?,
+            escrow,
+            escrow_vault: listing_ata(listed, &escrow)?,
+            seller_asset_account: listing_ata(listed, seller)?,
+            system_program: system_program::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            asset_token_program: listed.program(),
+        },
+        args::CancelListing { _listing_id: listing_id },
+    ))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use anchor_spl::token_2022;
+
+    use super::*;
+
+    fn token_asset() -> Asset {
+        Asset::Token { mint: Pubkey::new_unique(), program: anchor_spl::token::ID }
+    }
+
+    #[test]
+    fn omitted_optional_accounts_use_program_id() {
+        let buyer = Pubkey::new_unique();
+        let instruction = timeout_check(&buyer, 1, &token_asset());
+        // seller、nft_mint、nft_vault、seller_nft_account
+        let optional = &instruction.accounts[5..9];
+        assert!(optional.iter().all(|meta| meta.pubkey == sol_escrow::ID && !meta.is_writable));
+    }
+
+    #[test]
+    fn nft_accounts_follow_nft_token_program() {
+        let (buyer, seller) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let nft = NftTransfer {
+            mint: Pubkey::new_unique(),
+            token_program: token_2022::ID,
+            destination: Pubkey::new_unique(),
+        };
+        let escrow = pda::order(&buyer, 1);
+        let vault = pda::nft_vault(&escrow, &nft.mint, &token_2022::ID);
+
+        // 计价代币与NFT属于不同的代币程序
+        let instruction = escrow_release_nft(&buyer, &buyer, &seller, 1, &token_asset(), &Pubkey::new_unique(), &nft);
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert!(keys.contains(&nft.mint));
+        assert!(keys.contains(&vault));
+        assert!(keys.contains(&nft.destination));
+        // nft_token_program、system_program、associated_token_program、token_program
+        assert_eq!(keys[keys.len() - 4], token_2022::ID);
+        assert_eq!(keys.last(), Some(&anchor_spl::token::ID));
+    }
+
+    #[test]
+    fn listings_reject_native_assets() {
+        let (buyer, seller) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let args = CreateListingArgs { price: 1_000, asset_amount: 1, expiration: 0, is_nft: false };
+        assert!(create_listing(&seller, 1, &Asset::Native, &token_asset(), args).is_err());
+        assert!(fill_listing(&buyer, &seller, 1, &token_asset(), &Asset::Native, &Pubkey::new_unique()).is_err());
+        assert!(cancel_listing(&seller, 1, &Asset::Native).is_err());
+
+        // 关联代币账户按资产所属的代币程序推导
+        let (payment, listed) = (token_asset(), Asset::Token { mint: Pubkey::new_unique(), program: token_2022::ID });
+        let instruction = fill_listing(&buyer, &seller, 1, &payment, &listed, &Pubkey::new_unique()).unwrap();
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert!(keys.contains(&listed.ata(&buyer).unwrap()));
+        assert!(keys.contains(&payment.ata(&seller).unwrap()));
+    }
+}
@@ -0,0 +1,55 @@
+//! sol-escrow 的 Rust 客户端
+//!
+//! 提供 PDA 推导、覆盖全部指令的构造函数，以及订单等账户的解析。
+
+pub mod instructions;
+pub mod pda;
+pub mod state;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::{associated_token::get_associated_token_address_with_program_id, token::spl_token};
+
+pub use sol_escrow::{
+    error::ErrorCode,
+    instructions::{CreateListingArgs, CreateOrderArgs, MilestoneArgs},
+    state::{Config, CounterOffer, Escrow, MintFeeConfig, TransactionStatus},
+    ID,
+};
+
+
+/// 订单计价资产或挂单资产：SPL / Token-2022 代币，或原生SOL
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Asset {
+    Token { mint: Pubkey, program: Pubkey },
+    Native,
+}
+
+impl Asset {
+    pub fn mint(&self) -> Option<Pubkey> {
+        match self {
+            Asset::Token { mint, .. } => Some(*mint),
+            Asset::Native => None,
+        }
+    }
+
+    // SOL订单仍需传入代币程序账户
+    pub fn program(&self) -> Pubkey {
+        match self {
+            Asset::Token { program, .. } => *program,
+            Asset::Native => spl_token::ID,
+        }
+    }
+
+    // 手续费配置按原生SOL的mint派生
+    pub fn fee_mint(&self) -> Pubkey {
+        match self {
+            Asset::Token { mint, .. } => *mint,
+            Asset::Native => spl_token::native_mint::ID,
+        }
+    }
+
+    // 关联代币账户，SOL订单没有代币账户
+    pub fn ata(&self, owner: &Pubkey) -> Option<Pubkey> {
+        self.mint().map(|mint| get_associated_token_address_with_program_id(owner, &mint, &self.program()))
+    }
+}
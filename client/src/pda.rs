@@ -0,0 +1,54 @@
+//! 程序账户地址推导，种子与程序中的 `seeds` 约束一致
+
+use anchor_lang::prelude::{pubkey, Pubkey};
+use anchor_spl::{associated_token::get_associated_token_address_with_program_id, metadata::mpl_token_metadata};
+
+
+// 可升级BPF加载器，anchor_lang 中的模块已弃用
+const BPF_LOADER_UPGRADEABLE_ID: Pubkey = pubkey!("BPFLoaderUpgradeab1e11111111111111111111111");
+
+
+// 订单账户
+pub fn order(buyer: &Pubkey, order_id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"order", buyer.as_ref(), order_id.to_le_bytes().as_ref()], &sol_escrow::ID).0
+}
+
+// 卖家挂单账户
+pub fn listing(seller: &Pubkey, listing_id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"listing", seller.as_ref(), listing_id.to_le_bytes().as_ref()], &sol_escrow::ID).0
+}
+
+// 全局协议配置
+pub fn config() -> Pubkey {
+    Pubkey::find_program_address(&[b"config"], &sol_escrow::ID).0
+}
+
+// 单个代币的手续费配置，SOL订单使用原生SOL的mint
+pub fn mint_fee(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"mint_fee", mint.as_ref()], &sol_escrow::ID).0
+}
+
+// 卖家还价
+pub fn counter_offer(escrow: &Pubkey, seller: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"counter_offer", escrow.as_ref(), seller.as_ref()], &sol_escrow::ID).0
+}
+
+// 程序数据账户，初始化配置时校验升级权限
+pub fn program_data() -> Pubkey {
+    Pubkey::find_program_address(&[sol_escrow::ID.as_ref()], &BPF_LOADER_UPGRADEABLE_ID).0
+}
+
+// 资金托管账户：订单或挂单持有的关联代币账户
+pub fn escrow_vault(escrow: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(escrow, mint, token_program)
+}
+
+// NFT托管账户：卖家确认后订单持有NFT的关联代币账户
+pub fn nft_vault(escrow: &Pubkey, nft_mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(escrow, nft_mint, token_program)
+}
+
+// Metaplex 元数据账户
+pub fn nft_metadata(nft_mint: &Pubkey) -> Pubkey {
+    mpl_token_metadata::accounts::Metadata::find_pda(nft_mint).0
+}
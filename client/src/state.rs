@@ -0,0 +1,92 @@
+//! 账户数据解析，校验 Anchor 鉴别符后反序列化
+
+use anchor_lang::{AccountDeserialize, Result};
+
+use crate::{Config, CounterOffer, Escrow, MintFeeConfig, TransactionStatus};
+
+
+// 订单或挂单账户，状态为类型化的 `TransactionStatus`
+pub fn escrow(data: &[u8]) -> Result<Escrow> {
+    Escrow::try_deserialize(&mut &data[..])
+}
+
+// 只读取订单状态
+pub fn escrow_status(data: &[u8]) -> Result<TransactionStatus> {
+    escrow(data).map(|escrow| escrow.status)
+}
+
+pub fn config(data: &[u8]) -> Result<Config> {
+    Config::try_deserialize(&mut &data[..])
+}
+
+pub fn mint_fee(data: &[u8]) -> Result<MintFeeConfig> {
+    MintFeeConfig::try_deserialize(&mut &data[..])
+}
+
+pub fn counter_offer(data: &[u8]) -> Result<CounterOffer> {
+    CounterOffer::try_deserialize(&mut &data[..])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::{prelude::Pubkey, AccountSerialize};
+
+    use super::*;
+
+    fn escrow_data(status: TransactionStatus) -> Vec<u8> {
+        let escrow = Escrow {
+            buyer: Pubkey::new_unique(),
+            order_id: 7,
+            seller: None,
+            token_mint: Pubkey::new_unique(),
+            buyer_nft_account: None,
+            nft_mint: None,
+            collection_mint: None,
+            nft_vault: None,
+            amount: 1_000,
+            escrow_vault: Pubkey::new_unique(),
+            is_nft: false,
+            expiration: 0,
+            status,
+            arbitrator: None,
+            open_offer: true,
+            is_listing: false,
+            asset_amount: 0,
+            milestones: Vec::new(),
+            released_amount: 0,
+            royalty_bps: 0,
+            royalty_creators: Vec::new(),
+            is_native: false,
+            release_delay: None,
+            release_after: None,
+        };
+        let mut data = Vec::new();
+        escrow.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn escrow_status_is_typed() {
+        let data = escrow_data(TransactionStatus::InTransit);
+        assert_eq!(escrow_status(&data).unwrap(), TransactionStatus::InTransit);
+        assert_eq!(escrow(&data).unwrap().order_id, 7);
+    }
+
+    #[test]
+    fn rejects_other_account_types() {
+        let data = escrow_data(TransactionStatus::Created);
+        let Err(error) = config(&data) else { panic!("订单数据不应解析为协议配置") };
+        assert_eq!(error, anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch.into());
+    }
+
+    #[test]
+    fn rejects_unknown_status() {
+        let mut data = escrow_data(TransactionStatus::Created);
+        // 鉴别符、买家、订单编号、卖家、代币、四个空的可选账户、金额、托管账户、是否NFT、超时时间之后为状态
+        let status_offset = 8 + 32 + 8 + 1 + 32 + 4 + 8 + 32 + 1 + 8;
+        assert_eq!(data[status_offset], TransactionStatus::Created as u8);
+        data[status_offset] = 7;
+        assert!(escrow_status(&data).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! 程序订单事件，所有事件都带有订单地址、计价代币与执行后的订单状态
+
+use std::fmt;
+
+use anchor_lang::{prelude::Pubkey, AnchorDeserialize, AnchorSerialize, Discriminator};
+use sol_escrow::{instructions::*, state::TransactionStatus};
+
+
+// 为订单事件生成统一的枚举，按鉴别符解码
+macro_rules! escrow_events {
+    ($($event:ident),* $(,)?) => {
+        /// 程序发出的订单事件
+        pub enum EscrowEvent {
+            $($event($event),)*
+        }
+
+        impl EscrowEvent {
+            // 按 Anchor 事件格式解码：8字节鉴别符后为 borsh 序列化的事件数据，非订单事件返回 None
+            pub fn decode(data: &[u8]) -> Option<Self> {
+                $(
+                    if let Some(mut payload) = data.strip_prefix($event::DISCRIMINATOR) {
+                        return $event::deserialize(&mut payload).ok().map(Self::$event);
+                    }
+                )*
+                None
+            }
+
+            // 重新编码为事件日志中的格式
+            pub fn encode(&self) -> Vec<u8> {
+                match self {
+                    $(Self::$event(event) => {
+                        let mut data = $event::DISCRIMINATOR.to_vec();
+                        event.serialize(&mut data).expect("serializing into a Vec cannot fail");
+                        data
+                    })*
+                }
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$event(_) => stringify!($event),)*
+                }
+            }
+
+            pub fn escrow(&self) -> Pubkey {
+                match self {
+                    $(Self::$event(event) => event.escrow,)*
+                }
+            }
+
+            pub fn mint(&self) -> Pubkey {
+                match self {
+                    $(Self::$event(event) => event.mint,)*
+                }
+            }
+
+            pub fn status(&self) -> TransactionStatus {
+                match self {
+                    $(Self::$event(event) => event.status,)*
+                }
+            }
+
+            pub fn timestamp(&self) -> i64 {
+                match self {
+                    $(Self::$event(event) => event.timestamp,)*
+                }
+            }
+        }
+    };
+}
+
+escrow_events!(
+    OrderMade,
+    OrderFunded,
+    BuyerTransfers,
+    OrderAmended,
+    OrderCancelled,
+    FundsRefunded,
+    CounterProposed,
+    CounterAccepted,
+    CounterClosed,
+    SellerConfirmed,
+    FundsReleased,
+    MilestoneReleased,
+    OrderTimedOut,
+    OrderCranked,
+    OrderClosed,
+    DisputeOpened,
+    DisputeResolved,
+    ListingCreated,
+    ListingFilled,
+    ListingCancelled,
+);
+
+impl EscrowEvent {
+    // 事件涉及的主要金额，无金额的事件返回 None
+    pub fn amount(&self) -> Option<u64> {
+        match self {
+            Self::OrderMade(event) => Some(event.amount),
+            Self::OrderFunded(event) => Some(event.amount),
+            Self::BuyerTransfers(event) => Some(event.amount),
+            Self::OrderAmended(event) => Some(event.new_amount),
+            Self::FundsRefunded(event) => Some(event.amount),
+            Self::CounterProposed(event) => Some(event.amount),
+            Self::CounterAccepted(event) => Some(event.new_amount),
+            Self::FundsReleased(event) => Some(event.amount),
+            Self::MilestoneReleased(event) => Some(event.amount),
+            Self::OrderTimedOut(event) => Some(event.refunded_amount),
+            Self::OrderCranked(event) => Some(event.refunded_amount),
+            Self::DisputeResolved(event) => Some(event.seller_amount + event.buyer_amount),
+            Self::ListingCreated(event) => Some(event.price),
+            Self::ListingFilled(event) => Some(event.price),
+            Self::OrderCancelled(_)
+            | Self::CounterClosed(_)
+            | Self::SellerConfirmed(_)
+            | Self::OrderClosed(_)
+            | Self::DisputeOpened(_)
+            | Self::ListingCancelled(_) => None,
+        }
+    }
+}
+
+// 程序事件未实现 Debug，只输出统一字段
+impl fmt::Debug for EscrowEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct(self.name())
+            .field("escrow", &self.escrow())
+            .field("mint", &self.mint())
+            .field("status", &self.status())
+            .field("amount", &self.amount())
+            .field("timestamp", &self.timestamp())
+            .finish()
+    }
+}
@@ -0,0 +1,65 @@
+//! sol-escrow 事件索引
+//!
+//! 从交易日志中解析程序发出的订单事件，按订单整理为时间线并持久化到 SQLite。
+
+pub mod events;
+pub mod logs;
+pub mod store;
+
+use std::collections::BTreeMap;
+
+use anchor_lang::prelude::Pubkey;
+use solana_transaction_error::TransactionError;
+
+pub use events::EscrowEvent;
+pub use logs::parse_logs;
+pub use store::{Store, StoreError};
+
+
+/// 时间线中的一条事件，按 (slot, tx_index, index) 排序
+#[derive(Debug)]
+pub struct TimelineEntry {
+    pub signature: String,
+    pub slot: u64,
+    pub tx_index: u32,  // 交易在slot内的顺序
+    pub index: u32,  // 事件在交易内的顺序
+    pub event: EscrowEvent,
+}
+
+// 解析单笔交易的日志，失败的交易状态已回滚，不产生任何事件
+//   tx_index 为交易在区块中的位置，err 为交易元数据中的执行结果
+pub fn index_transaction(
+    signature: &str,
+    slot: u64,
+    tx_index: u32,
+    err: Option<&TransactionError>,
+    logs: &[impl AsRef<str>],
+) -> Vec<TimelineEntry> {
+    if err.is_some() {
+        return Vec::new();
+    }
+
+    parse_logs(logs)
+        .into_iter()
+        .enumerate()
+        .map(|(index, event)| TimelineEntry {
+            signature: signature.to_string(),
+            slot,
+            tx_index,
+            index: index as u32,
+            event,
+        })
+        .collect()
+}
+
+// 按订单分组，并按链上执行顺序排序
+pub fn build_timelines(entries: impl IntoIterator<Item = TimelineEntry>) -> BTreeMap<Pubkey, Vec<TimelineEntry>> {
+    let mut timelines: BTreeMap<Pubkey, Vec<TimelineEntry>> = BTreeMap::new();
+    for entry in entries {
+        timelines.entry(entry.event.escrow()).or_default().push(entry);
+    }
+    for timeline in timelines.values_mut() {
+        timeline.sort_by_key(|entry| (entry.slot, entry.tx_index, entry.index));
+    }
+    timelines
+}
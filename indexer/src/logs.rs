@@ -0,0 +1,170 @@
+//! 交易日志解析
+//!
+//! Anchor 的 `emit!` 以 `Program data: <base64>` 写入日志，按调用栈只保留本程序发出的数据。
+//! 事件按调用帧缓存，调用成功后才并入上层，失败的调用帧及其内部调用发出的事件一并丢弃。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+use crate::EscrowEvent;
+
+const PROGRAM_DATA: &str = "Program data: ";
+
+
+// 调用栈中的一帧及其尚未确认的事件
+struct Frame<'a> {
+    program: &'a str,
+    events: Vec<EscrowEvent>,
+}
+
+
+// 按出现顺序解析本程序的订单事件，跳过其他程序、失败的调用与无法识别的数据
+// 日志只记录执行过程，交易整体是否成功需要由调用方根据交易结果判断
+pub fn parse_logs(logs: &[impl AsRef<str>]) -> Vec<EscrowEvent> {
+    let program_id = sol_escrow::ID.to_string();
+    let mut invocations: Vec<Frame> = Vec::new();
+    let mut events = Vec::new();
+
+    for log in logs {
+        let log = log.as_ref();
+        if let Some(data) = log.strip_prefix(PROGRAM_DATA) {
+            let frame = match invocations.last_mut() {
+                Some(frame) if frame.program == program_id => frame,
+                _ => continue,
+            };
+            if let Some(event) = BASE64.decode(data).ok().and_then(|data| EscrowEvent::decode(&data)) {
+                frame.events.push(event);
+            }
+        } else if let Some(program) = invoked_program(log) {
+            invocations.push(Frame { program, events: Vec::new() });
+        } else if let Some(succeeded) = program_exit(log) {
+            let Some(frame) = invocations.pop() else { continue };
+            if !succeeded {
+                continue;
+            }
+            match invocations.last_mut() {
+                Some(parent) => parent.events.extend(frame.events),
+                None => events.extend(frame.events),
+            }
+        }
+    }
+
+    // 日志被截断时调用帧没有结束记录，由调用方保证交易已成功
+    for frame in invocations {
+        events.extend(frame.events);
+    }
+    events
+}
+
+// "Program <id> invoke [<depth>]"
+fn invoked_program(log: &str) -> Option<&str> {
+    let (program, depth) = log.strip_prefix("Program ")?.split_once(" invoke [")?;
+    depth.ends_with(']').then_some(program)
+}
+
+// "Program <id> success" 返回 true，"Program <id> failed: <reason>" 返回 false
+fn program_exit(log: &str) -> Option<bool> {
+    let (program, result) = log.strip_prefix("Program ")?.split_once(' ')?;
+    if program.ends_with(':') {
+        return None;
+    }
+    if result == "success" {
+        Some(true)
+    } else {
+        result.starts_with("failed").then_some(false)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+    use sol_escrow::{instructions::OrderFunded, state::TransactionStatus};
+
+    use super::*;
+
+    fn funded(escrow: Pubkey) -> String {
+        let event = EscrowEvent::OrderFunded(OrderFunded {
+            escrow,
+            mint: Pubkey::new_unique(),
+            status: TransactionStatus::Funded,
+            buyer: Pubkey::new_unique(),
+            amount: 1_000,
+            timestamp: 1_700_000_000,
+        });
+        format!("{PROGRAM_DATA}{}", BASE64.encode(event.encode()))
+    }
+
+    #[test]
+    fn keeps_only_events_emitted_by_the_program() {
+        let program = sol_escrow::ID.to_string();
+        let other = Pubkey::new_unique().to_string();
+        let escrow = Pubkey::new_unique();
+        let logs = vec![
+            format!("Program {program} invoke [1]"),
+            "Program log: Instruction: BuyerPayment".to_string(),
+            format!("Program {other} invoke [2]"),
+            // 其他程序写入的同格式数据
+            funded(Pubkey::new_unique()),
+            "Program log: success".to_string(),
+            format!("Program {other} success"),
+            funded(escrow),
+            "Program data: bm90IGFuIGV2ZW50".to_string(),
+            format!("Program {program} consumed 12000 of 200000 compute units"),
+            format!("Program {program} success"),
+        ];
+
+        let events = parse_logs(&logs);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name(), "OrderFunded");
+        assert_eq!(events[0].escrow(), escrow);
+        assert_eq!(events[0].status(), TransactionStatus::Funded);
+        assert_eq!(events[0].amount(), Some(1_000));
+    }
+
+    #[test]
+    fn drops_events_from_failed_frames() {
+        let program = sol_escrow::ID.to_string();
+        let other = Pubkey::new_unique().to_string();
+        let (kept, reverted) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let logs = vec![
+            format!("Program {program} invoke [1]"),
+            funded(kept),
+            format!("Program {program} success"),
+            // 同一交易中的第二条指令失败，其事件随状态一起回滚
+            format!("Program {program} invoke [1]"),
+            funded(reverted),
+            format!("Program {other} invoke [2]"),
+            format!("Program {other} failed: custom program error: 0x1"),
+            format!("Program {program} failed: custom program error: 0x1"),
+        ];
+
+        let events = parse_logs(&logs);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].escrow(), kept);
+    }
+
+    #[test]
+    fn events_from_nested_invocation_wait_for_the_caller() {
+        let program = sol_escrow::ID.to_string();
+        let caller = Pubkey::new_unique().to_string();
+        let escrow = Pubkey::new_unique();
+        let logs = vec![
+            format!("Program {caller} invoke [1]"),
+            format!("Program {program} invoke [2]"),
+            funded(escrow),
+            format!("Program {program} success"),
+            // 本程序的调用已成功，但外层调用失败
+            format!("Program {caller} failed: insufficient funds"),
+        ];
+        assert!(parse_logs(&logs).is_empty());
+
+        let logs = vec![
+            format!("Program {caller} invoke [1]"),
+            format!("Program {program} invoke [2]"),
+            funded(escrow),
+            format!("Program {program} success"),
+            format!("Program {caller} success"),
+        ];
+        assert_eq!(parse_logs(&logs).len(), 1);
+    }
+}
@@ -0,0 +1,275 @@
+//! 事件持久化，每条事件一行，按订单查询时间线
+
+use std::{path::Path, str::FromStr};
+
+use anchor_lang::prelude::Pubkey;
+use rusqlite::{params, Connection};
+use sol_escrow::state::TransactionStatus;
+use solana_transaction_error::TransactionError;
+
+use crate::{index_transaction, EscrowEvent, TimelineEntry};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS escrow_events (
+        id INTEGER PRIMARY KEY,
+        signature TEXT NOT NULL,
+        slot INTEGER NOT NULL,
+        tx_index INTEGER NOT NULL,
+        event_index INTEGER NOT NULL,
+        escrow TEXT NOT NULL,
+        mint TEXT NOT NULL,
+        status TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        data BLOB NOT NULL,
+        UNIQUE (signature, event_index)
+    );
+    CREATE INDEX IF NOT EXISTS escrow_events_by_escrow ON escrow_events (escrow, slot, tx_index, event_index);
+";
+
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("stored event {index} of {signature} cannot be decoded")]
+    InvalidEvent { signature: String, index: u32 },
+}
+
+pub type Result<T> = std::result::Result<T, StoreError>;
+
+
+pub struct Store {
+    connection: Connection,
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(connection: Connection) -> Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self { connection })
+    }
+
+    // 写入事件，已索引的交易事件会被忽略，返回新写入的条数
+    pub fn insert(&mut self, entries: &[TimelineEntry]) -> Result<usize> {
+        let transaction = self.connection.transaction()?;
+        let mut inserted = 0;
+        {
+            let mut statement = transaction.prepare(
+                "INSERT OR IGNORE INTO escrow_events
+                    (signature, slot, tx_index, event_index, escrow, mint, status, kind, timestamp, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            for entry in entries {
+                let event = &entry.event;
+                inserted += statement.execute(params![
+                    entry.signature,
+                    entry.slot,
+                    entry.tx_index,
+                    entry.index,
+                    event.escrow().to_string(),
+                    event.mint().to_string(),
+                    format!("{:?}", event.status()),
+                    event.name(),
+                    event.timestamp(),
+                    event.encode(),
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(inserted)
+    }
+
+    // 解析并写入单笔交易，交易可以按任意顺序写入，查询时按 slot 与 tx_index 排序
+    pub fn index_transaction(
+        &mut self,
+        signature: &str,
+        slot: u64,
+        tx_index: u32,
+        err: Option<&TransactionError>,
+        logs: &[impl AsRef<str>],
+    ) -> Result<usize> {
+        self.insert(&index_transaction(signature, slot, tx_index, err, logs))
+    }
+
+    // 订单的完整时间线
+    pub fn timeline(&self, escrow: &Pubkey) -> Result<Vec<TimelineEntry>> {
+        let mut statement = self.connection.prepare(
+            "SELECT signature, slot, tx_index, event_index, data FROM escrow_events
+             WHERE escrow = ?1 ORDER BY slot, tx_index, event_index",
+        )?;
+        let rows = statement.query_map([escrow.to_string()], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, u64>(1)?,
+                row.get::<_, u32>(2)?,
+                row.get::<_, u32>(3)?,
+                row.get::<_, Vec<u8>>(4)?,
+            ))
+        })?;
+
+        rows.map(|row| {
+            let (signature, slot, tx_index, index, data) = row?;
+            match EscrowEvent::decode(&data) {
+                Some(event) => Ok(TimelineEntry { signature, slot, tx_index, index, event }),
+                None => Err(StoreError::InvalidEvent { signature, index }),
+            }
+        })
+        .collect()
+    }
+
+    // 最近一条事件记录的订单状态
+    pub fn latest_status(&self, escrow: &Pubkey) -> Result<Option<TransactionStatus>> {
+        Ok(self.timeline(escrow)?.last().map(|entry| entry.event.status()))
+    }
+
+    // 已索引的订单地址
+    pub fn escrows(&self) -> Result<Vec<Pubkey>> {
+        let mut statement = self.connection.prepare("SELECT DISTINCT escrow FROM escrow_events ORDER BY escrow")?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+        let mut escrows = Vec::new();
+        for row in rows {
+            let address = row?;
+            if let Ok(escrow) = Pubkey::from_str(&address) {
+                escrows.push(escrow);
+            }
+        }
+        Ok(escrows)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+    use anchor_lang::solana_program::instruction::InstructionError;
+    use sol_escrow::instructions::{OrderFunded, OrderMade, SellerConfirmed};
+
+    use super::*;
+
+    struct Order {
+        escrow: Pubkey,
+        mint: Pubkey,
+        buyer: Pubkey,
+        seller: Pubkey,
+    }
+
+    fn transaction_logs(events: &[EscrowEvent]) -> Vec<String> {
+        let program = sol_escrow::ID;
+        let mut logs = vec![format!("Program {program} invoke [1]")];
+        logs.extend(events.iter().map(|event| format!("Program data: {}", BASE64.encode(event.encode()))));
+        logs.push(format!("Program {program} success"));
+        logs
+    }
+
+    fn create_and_fund(order: &Order) -> Vec<String> {
+        transaction_logs(&[
+            EscrowEvent::OrderMade(OrderMade {
+                escrow: order.escrow,
+                mint: order.mint,
+                status: TransactionStatus::Created,
+                buyer: order.buyer,
+                seller: Some(order.seller),
+                amount: 1_000,
+                expiration: 1_700_003_600,
+                timestamp: 1_700_000_000,
+            }),
+            EscrowEvent::OrderFunded(OrderFunded {
+                escrow: order.escrow,
+                mint: order.mint,
+                status: TransactionStatus::Funded,
+                buyer: order.buyer,
+                amount: 1_000,
+                timestamp: 1_700_000_000,
+            }),
+        ])
+    }
+
+    fn confirm(order: &Order) -> Vec<String> {
+        transaction_logs(&[EscrowEvent::SellerConfirmed(SellerConfirmed {
+            escrow: order.escrow,
+            mint: order.mint,
+            status: TransactionStatus::InTransit,
+            seller: order.seller,
+            buyer: order.buyer,
+            timestamp: 1_700_000_100,
+        })])
+    }
+
+    fn order() -> Order {
+        Order {
+            escrow: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            buyer: Pubkey::new_unique(),
+            seller: Pubkey::new_unique(),
+        }
+    }
+
+    #[test]
+    fn timeline_follows_slot_order_per_escrow() {
+        let mut store = Store::open_in_memory().unwrap();
+        let (first, second) = (order(), order());
+
+        assert_eq!(store.index_transaction("create-first", 10, 0, None, &create_and_fund(&first)).unwrap(), 2);
+        assert_eq!(store.index_transaction("create-second", 11, 0, None, &create_and_fund(&second)).unwrap(), 2);
+        assert_eq!(store.index_transaction("confirm-first", 12, 0, None, &confirm(&first)).unwrap(), 1);
+
+        let timeline = store.timeline(&first.escrow).unwrap();
+        let kinds: Vec<&str> = timeline.iter().map(|entry| entry.event.name()).collect();
+        assert_eq!(kinds, ["OrderMade", "OrderFunded", "SellerConfirmed"]);
+        assert!(timeline.iter().all(|entry| entry.event.mint() == first.mint));
+        assert_eq!(timeline[2].signature, "confirm-first");
+
+        assert_eq!(store.latest_status(&first.escrow).unwrap(), Some(TransactionStatus::InTransit));
+        assert_eq!(store.latest_status(&second.escrow).unwrap(), Some(TransactionStatus::Funded));
+        assert_eq!(store.latest_status(&Pubkey::new_unique()).unwrap(), None);
+        assert_eq!(store.escrows().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn reindexing_a_transaction_is_ignored() {
+        let mut store = Store::open_in_memory().unwrap();
+        let order = order();
+
+        assert_eq!(store.index_transaction("create", 10, 0, None, &create_and_fund(&order)).unwrap(), 2);
+        assert_eq!(store.index_transaction("create", 10, 0, None, &create_and_fund(&order)).unwrap(), 0);
+        assert_eq!(store.timeline(&order.escrow).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn timeline_orders_transactions_within_a_slot() {
+        let mut store = Store::open_in_memory().unwrap();
+        let order = order();
+
+        // 同一slot内的两笔交易乱序写入
+        assert_eq!(store.index_transaction("confirm", 10, 7, None, &confirm(&order)).unwrap(), 1);
+        assert_eq!(store.index_transaction("create", 10, 3, None, &create_and_fund(&order)).unwrap(), 2);
+
+        let timeline = store.timeline(&order.escrow).unwrap();
+        let kinds: Vec<&str> = timeline.iter().map(|entry| entry.event.name()).collect();
+        assert_eq!(kinds, ["OrderMade", "OrderFunded", "SellerConfirmed"]);
+        assert_eq!(timeline[2].tx_index, 7);
+        assert_eq!(store.latest_status(&order.escrow).unwrap(), Some(TransactionStatus::InTransit));
+    }
+
+    #[test]
+    fn failed_transactions_are_not_indexed() {
+        let mut store = Store::open_in_memory().unwrap();
+        let order = order();
+
+        assert_eq!(store.index_transaction("create", 10, 0, None, &create_and_fund(&order)).unwrap(), 2);
+        // 日志中有事件，但交易执行失败
+        let failed = TransactionError::InstructionError(0, InstructionError::Custom(6000));
+        assert_eq!(store.index_transaction("confirm", 11, 0, Some(&failed), &confirm(&order)).unwrap(), 0);
+
+        assert_eq!(store.timeline(&order.escrow).unwrap().len(), 2);
+        assert_eq!(store.latest_status(&order.escrow).unwrap(), Some(TransactionStatus::Funded));
+    }
+}
@@ -1,4 +1,22 @@
 pub const ANCHOR_DISCRIMINATOR: usize = 8;
 
 // 订单超时时间最小值，单位：秒（必须至少比当前时间晚 60 秒）
-pub const MIN_EXPIRATION_TIME: i64 = 60;
\ No newline at end of file
+pub const MIN_EXPIRATION_TIME: i64 = 60;
+
+// 订单超时时间最大值，单位：秒（最多比当前时间晚 30 天）
+pub const MAX_EXPIRATION_TIME: i64 = 30 * 24 * 60 * 60;
+
+// 基点分母，10000 基点即 100%
+pub const BASIS_POINTS_DENOMINATOR: u16 = 10_000;
+
+// 单个订单的最大里程碑数量
+pub const MAX_MILESTONES: usize = 5;
+
+// 协议手续费上限，单位：基点（10%）
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+// NFT元数据中创作者数量上限
+pub const MAX_CREATORS: usize = 5;
+
+// 清理超时订单的执行者奖励上限，单位：lamports
+pub const MAX_KEEPER_BOUNTY: u64 = 1_000_000;
@@ -6,8 +6,8 @@ pub enum ErrorCode {
     #[msg("The expiration time is too soon. It must be at least 60 seconds in the future.")]
     ExpirationTooSoon,
 
-    /// 交易超时错误
-    #[msg("The expiration time is too far in the future.")]
+    /// 超时时间设置过晚
+    #[msg("The expiration time is too far in the future. It must be at most 30 days from now.")]
     ExpirationTooFar,
 
     /// 金额为0错误
@@ -65,4 +65,180 @@ pub enum ErrorCode {
     /// 未提供 NFT 集合 mint 账户
     #[msg("Missing collection mint: The collection mint account is required but was not provided.")]
     MissingCollectionMint,
+
+    /// 订单尚未结束，不允许关闭
+    #[msg("Order is not settled: Only successful, cancelled or expired orders can be closed.")]
+    OrderNotSettled,
+
+    /// 托管账户仍有余额
+    #[msg("Escrow vault is not empty: All tokens must be withdrawn before closing the order.")]
+    VaultNotEmpty,
+
+    /// 签名者无权执行该操作
+    #[msg("Unauthorized: The signer is not allowed to perform this action on the order.")]
+    Unauthorized,
+
+    /// 卖家账户与订单不匹配
+    #[msg("Invalid seller: The provided seller does not match the seller recorded in the order.")]
+    InvalidSeller,
+
+    /// 托管账户与订单不匹配
+    #[msg("Invalid escrow vault: The provided vault does not match the vault recorded in the order.")]
+    InvalidEscrowVault,
+
+    /// 代币 mint 与订单不匹配
+    #[msg("Invalid mint: The provided mint does not match the token mint recorded in the order.")]
+    InvalidMint,
+
+    /// 当前订单状态不允许发起争议
+    #[msg("Dispute not allowed: Only funded or in-transit orders with a seller and an arbitrator can be disputed.")]
+    DisputeNotAllowed,
+
+    /// 订单未处于争议状态
+    #[msg("Order is not disputed: Only disputed orders can be resolved by the arbitrator.")]
+    OrderNotDisputed,
+
+    /// 订单处于争议中
+    #[msg("Order is disputed: The order must be resolved by the arbitrator.")]
+    OrderInDispute,
+
+    /// 基点数值无效
+    #[msg("Invalid basis points: The value must not exceed 10000.")]
+    InvalidBasisPoints,
+
+    /// 卖家设置无效
+    #[msg("Invalid seller selection: Either designate a seller or mark the order as an open offer, but not both.")]
+    InvalidSellerSelection,
+
+    /// 订单已超时
+    #[msg("Order expired: The order deadline has already passed.")]
+    OrderExpired,
+
+    /// NFT 不属于订单指定的集合
+    #[msg("Collection mismatch: The NFT does not belong to the collection specified in the order.")]
+    CollectionMismatch,
+
+    /// NFT 的集合未经验证
+    #[msg("Unverified collection: The NFT's collection membership has not been verified.")]
+    UnverifiedCollection,
+
+    /// 账户不是卖家挂单
+    #[msg("Not a listing: The escrow account was not created by create_listing.")]
+    NotAListing,
+
+    /// 挂单不可购买或取消
+    #[msg("Listing not available: The listing has already been filled or cancelled.")]
+    ListingNotAvailable,
+
+    /// 未提供 NFT 托管账户
+    #[msg("Missing NFT vault: The escrow's NFT vault account is required but was not provided.")]
+    MissingNftVault,
+
+    /// NFT 托管账户与订单不匹配
+    #[msg("Invalid NFT vault: The provided NFT vault does not match the escrow's NFT vault.")]
+    InvalidNftVault,
+
+    /// 里程碑数量超出上限
+    #[msg("Too many milestones: An order can have at most 5 milestones.")]
+    TooManyMilestones,
+
+    /// 里程碑设置无效
+    #[msg("Invalid milestones: Amounts must sum to the order amount and deadlines must fall before the order expiration.")]
+    InvalidMilestones,
+
+    /// 里程碑索引无效
+    #[msg("Invalid milestone index: The order has no milestone at the given index.")]
+    InvalidMilestoneIndex,
+
+    /// 里程碑已放款
+    #[msg("Milestone already released: The milestone has already been paid to the seller.")]
+    MilestoneAlreadyReleased,
+
+    /// 手续费超出上限
+    #[msg("Fee too high: The protocol fee must not exceed 1000 basis points.")]
+    FeeTooHigh,
+
+    /// 手续费收款账户与配置不匹配
+    #[msg("Invalid treasury: The treasury token account is not owned by the configured treasury.")]
+    InvalidTreasury,
+
+    /// 缺少创作者版税收款账户
+    #[msg("Missing creator account: A token account must be provided for every verified creator.")]
+    MissingCreatorAccount,
+
+    /// 创作者版税收款账户无效
+    #[msg("Invalid creator account: The token account is not owned by the creator or has the wrong mint.")]
+    InvalidCreatorAccount,
+
+    /// 版税与手续费之和超过放款金额
+    #[msg("Royalty too high: Royalties and protocol fee exceed the released amount.")]
+    RoyaltyTooHigh,
+
+    /// 代币订单缺少代币账户
+    #[msg("Missing token account: Token orders require the mint, escrow vault and token accounts.")]
+    MissingTokenAccount,
+
+    /// 托管账户余额不足，转出后将低于免租金额
+    #[msg("Insufficient escrow balance: The transfer would leave the escrow below rent exemption.")]
+    InsufficientEscrowBalance,
+
+    /// 不支持不可转让的代币
+    #[msg("Non-transferable mint: Tokens with the NonTransferable extension cannot be escrowed.")]
+    NonTransferableMint,
+
+    /// 不支持带永久委托人的代币
+    #[msg("Permanent delegate mint: Tokens with the PermanentDelegate extension cannot be escrowed safely.")]
+    PermanentDelegateMint,
+
+    /// 卖家确认后不能修改订单
+    #[msg("Amend not allowed: The order can only be amended before seller confirmation.")]
+    AmendNotAllowed,
+
+    /// 当前订单状态不允许还价
+    #[msg("Counter not allowed: Counter offers can only be made before seller confirmation.")]
+    CounterNotAllowed,
+
+    /// 订单尚未超时
+    #[msg("Order not expired: The order can only be timed out after its expiration.")]
+    OrderNotExpired,
+
+    /// 执行者奖励超过上限
+    #[msg("Keeper bounty too high: The keeper bounty exceeds the maximum allowed.")]
+    KeeperBountyTooHigh,
+
+    /// 自动放款等待时间无效
+    #[msg("Invalid release delay: The release delay must be positive and within the maximum expiration time.")]
+    InvalidReleaseDelay,
+
+    /// 尚未到达卖家自行领取款项的时间
+    #[msg("Release not ready: The seller can only claim the funds after the release time or the milestone deadline.")]
+    ReleaseNotReady,
+
+    /// 订单已结束，不能再做超时处理
+    #[msg("Order already settled: Successful, cancelled or expired orders cannot be timed out.")]
+    OrderAlreadySettled,
+
+    /// 卖家已可自行领取款项，不能超时退款
+    #[msg("Seller release available: The release time has passed, so the seller can claim the funds instead of a refund.")]
+    SellerReleaseAvailable,
+
+    /// 非法的订单状态流转
+    #[msg("Invalid status transition: The order cannot move from its current status to the requested one.")]
+    InvalidStatusTransition,
+
+    /// NFT代币程序缺失或与NFT的mint不匹配
+    #[msg("Invalid NFT token program: The token program must be provided and own the NFT mint.")]
+    InvalidNftTokenProgram,
+
+    /// 还价条款与买家确认的不一致
+    #[msg("Counter offer changed: The counter offer no longer matches the amount and expiration the buyer accepted.")]
+    CounterOfferChanged,
+
+    /// 卖家已确认的订单不能超时退款
+    #[msg("Order in transit: The seller has confirmed the order, so it can only be released or resolved by the arbitrator.")]
+    OrderInTransit,
+
+    /// 订单条款与卖家确认的不一致
+    #[msg("Order terms changed: The order no longer matches the amount and expiration the seller confirmed.")]
+    OrderTermsChanged,
 }
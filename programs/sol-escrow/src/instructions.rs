@@ -1,7 +1,15 @@
 pub mod buyer_logic;
 pub mod seller_logic;
 pub mod order_settlement;
+pub mod dispute_logic;
+pub mod listing_logic;
+pub mod config_logic;
+pub mod counter_logic;
 
 pub use buyer_logic::*;
 pub use seller_logic::*;
 pub use order_settlement::*;
+pub use dispute_logic::*;
+pub use listing_logic::*;
+pub use config_logic::*;
+pub use counter_logic::*;
@@ -1,36 +1,62 @@
-use anchor_lang::prelude::*;
-use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}};
+use anchor_lang::{prelude::*, system_program::{transfer, Transfer}};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token::spl_token::native_mint,
+    token_interface::{
+        spl_token_2022::{
+            extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+            state::Mint as MintState
+        },
+        Mint, Token2022, TokenAccount, TokenInterface, TransferChecked
+    }
+};
 
 use crate::{
-    constants::{ANCHOR_DISCRIMINATOR, MIN_EXPIRATION_TIME}, 
+    constants::{ANCHOR_DISCRIMINATOR, MAX_EXPIRATION_TIME, MAX_MILESTONES, MIN_EXPIRATION_TIME}, 
     error::ErrorCode, 
-    state::{Escrow, TransactionStatus}
+    instructions::{transfer_checked_with_hook, EscrowFunds},
+    state::{Escrow, Milestone, TransactionStatus}
 };
 
 
+// 订单事件统一以 escrow、mint、status 开头，status 为指令执行后的订单状态
 #[event]
 pub struct OrderMade {
-    pub maker: Pubkey,
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub buyer: Pubkey,
+    pub seller: Option<Pubkey>,
     pub amount: u64,
-    pub expiration: i64
+    pub expiration: i64,
+    pub timestamp: i64,
 }
 
 #[event]
 pub struct BuyerTransfers {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
     pub from: Pubkey,
     pub to: Pubkey,
-    pub amount: u64
+    pub amount: u64,
+    pub timestamp: i64,
 }
 
 #[event]
 pub struct OrderCancelled {
-    pub buyer: Pubkey,
     pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub buyer: Pubkey,
     pub timestamp: i64,
 }
 
 #[event]
 pub struct FundsRefunded {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
     pub buyer: Pubkey,
     pub amount: u64,
     pub timestamp: i64,
@@ -38,26 +64,102 @@ pub struct FundsRefunded {
 
 #[event]
 pub struct OrderFunded {
-    pub buyer: Pubkey,
     pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub buyer: Pubkey,
     pub amount: u64,
     pub timestamp: i64,
 }
 
 
+#[event]
+pub struct OrderAmended {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub buyer: Pubkey,
+    pub old_amount: u64,
+    pub new_amount: u64,
+    pub old_expiration: i64,
+    pub new_expiration: i64,
+    pub timestamp: i64,
+}
+
+
+/// 创建订单参数
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CreateOrderArgs {
+    pub amount: u64,  // 交易金额
+    pub expiration: i64,  // 交易超时时间
+    pub nft_mint: Option<Pubkey>,  // 购买的NFT的mint地址
+    pub collection_mint: Option<Pubkey>,  // 购买集合内任意NFT时的集合mint地址
+    pub buyer_nft_account: Option<Pubkey>,  // 买家nft账户
+    pub is_nft: bool,  // 是否是nft交易
+    pub arbitrator: Option<Pubkey>,  // 仲裁者
+    pub seller: Option<Pubkey>,  // 指定卖家
+    pub open_offer: bool,  // 是否允许任意卖家确认
+    pub milestones: Vec<MilestoneArgs>,  // 分期放款里程碑，为空时一次性放款
+    pub release_delay: Option<i64>  // 卖家确认后买家未放款时，卖家可自行领取款项的等待时间
+}
+
+/// 里程碑参数
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MilestoneArgs {
+    pub amount: u64,  // 里程碑金额
+    pub deadline: Option<i64>  // 里程碑截止时间，过后买家仍未放款时卖家可自行领取
+}
+
+
 #[derive(Accounts)]
+#[instruction(order_id: u64)]
 pub struct CreateOrder<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
 
+    // 不提供mint时订单以原生SOL计价
+    #[account(mint::token_program = token_program)]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = ANCHOR_DISCRIMINATOR + Escrow::INIT_SPACE,
+        seeds = [b"order", signer.key().as_ref(), order_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        init,
+        payer = signer,
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>
+}
+
+
+#[derive(Accounts)]
+#[instruction(order_id: u64)]
+pub struct CreateAndFundOrder<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    // 不提供mint时订单以原生SOL计价
     #[account(mint::token_program = token_program)]
-    pub mint: InterfaceAccount<'info, Mint>,
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
 
     #[account(
         init,
         payer = signer,
         space = ANCHOR_DISCRIMINATOR + Escrow::INIT_SPACE,
-        seeds = [b"order", signer.key().as_ref()],
+        seeds = [b"order", signer.key().as_ref(), order_id.to_le_bytes().as_ref()],
         bump
     )]
     pub escrow: Account<'info, Escrow>,
@@ -69,7 +171,15 @@ pub struct CreateOrder<'info> {
         associated_token::authority = escrow,
         associated_token::token_program = token_program
     )]
-    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program
+    )]
+    pub buyer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
@@ -78,14 +188,18 @@ pub struct CreateOrder<'info> {
 
 
 #[derive(Accounts)]
+#[instruction(order_id: u64)]
 pub struct BuyerPayment<'info> {
-    pub buyer: SystemAccount<'info>,
+    #[account(mut)]
+    pub buyer: Signer<'info>,
 
-    pub mint: InterfaceAccount<'info, Mint>,
+    // 代币订单需要提供，SOL订单传空
+    #[account(constraint = mint.key() == escrow.token_mint @ ErrorCode::InvalidMint)]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
 
     #[account(
         mut,
-        seeds = [b"order", buyer.key().as_ref()],
+        seeds = [b"order", buyer.key().as_ref(), order_id.to_le_bytes().as_ref()],
         bump
     )]
     pub escrow: Account<'info, Escrow>,
@@ -94,9 +208,10 @@ pub struct BuyerPayment<'info> {
         mut,
         associated_token::mint = mint,
         associated_token::authority = escrow,
-        associated_token::token_program = token_program
+        associated_token::token_program = token_program,
+        constraint = escrow_vault.key() == escrow.escrow_vault @ ErrorCode::InvalidEscrowVault
     )]
-    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         mut,
@@ -104,7 +219,7 @@ pub struct BuyerPayment<'info> {
         associated_token::authority = buyer,
         associated_token::token_program = token_program
     )]
-    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub buyer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
@@ -113,14 +228,19 @@ pub struct BuyerPayment<'info> {
 
 
 #[derive(Accounts)]
+#[instruction(order_id: u64)]
 pub struct OrderCancellation<'info> {
-    pub buyer: SystemAccount<'info>,
+    // SOL订单退款直接转入买家账户
+    #[account(mut)]
+    pub buyer: Signer<'info>,
 
-    pub mint: InterfaceAccount<'info, Mint>,
+    // 代币订单需要提供，SOL订单传空
+    #[account(constraint = mint.key() == escrow.token_mint @ ErrorCode::InvalidMint)]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
 
     #[account(
         mut,
-        seeds = [b"order", buyer.key().as_ref()],
+        seeds = [b"order", buyer.key().as_ref(), order_id.to_le_bytes().as_ref()],
         bump
     )]
     pub escrow: Account<'info, Escrow>,
@@ -129,9 +249,10 @@ pub struct OrderCancellation<'info> {
         mut,
         associated_token::mint = mint,
         associated_token::authority = escrow,
-        associated_token::token_program = token_program
+        associated_token::token_program = token_program,
+        constraint = escrow_vault.key() == escrow.escrow_vault @ ErrorCode::InvalidEscrowVault
     )]
-    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         mut,
@@ -139,7 +260,7 @@ pub struct OrderCancellation<'info> {
         associated_token::authority = buyer,
         associated_token::token_program = token_program
     )]
-    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub buyer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
@@ -147,141 +268,511 @@ pub struct OrderCancellation<'info> {
 }
 
 
-pub fn process_order(
-    ctx: Context<CreateOrder>, 
-    amount: u64, 
-    expiration: i64,
-    nft_mint: Option<Pubkey>,
-    buyer_nft_account: Option<Pubkey>,
-    is_nft: bool
+pub fn process_order(ctx: Context<CreateOrder>, order_id: u64, args: CreateOrderArgs) -> Result<()> {
+    initialize_order(
+        &mut ctx.accounts.escrow,
+        ctx.accounts.signer.key(),
+        ctx.accounts.mint.as_ref(),
+        ctx.accounts.escrow_vault.as_ref(),
+        order_id,
+        args
+    )
+}
+
+
+// 创建订单并同时付款，订单直接进入已付款状态
+pub fn process_create_and_fund_order<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CreateAndFundOrder<'info>>,
+    order_id: u64,
+    args: CreateOrderArgs
 ) -> Result<()> {
+    initialize_order(
+        &mut ctx.accounts.escrow,
+        ctx.accounts.signer.key(),
+        ctx.accounts.mint.as_ref(),
+        ctx.accounts.escrow_vault.as_ref(),
+        order_id,
+        args
+    )?;
+
+    // 将交易金额存入托管账户，按实际到账金额记账
+    let escrow_account = &ctx.accounts.escrow;
+    let received_amount = BuyerDeposit {
+        escrow: escrow_account,
+        buyer: ctx.accounts.signer.to_account_info(),
+        mint: ctx.accounts.mint.as_ref(),
+        escrow_vault: ctx.accounts.escrow_vault.as_ref(),
+        buyer_token_account: ctx.accounts.buyer_token_account.as_ref(),
+        system_program: ctx.accounts.system_program.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        hook_accounts: ctx.remaining_accounts
+    }.deposit(escrow_account.amount)?;
+
     let escrow_account = &mut ctx.accounts.escrow;
+    escrow_account.amount = received_amount;
+    escrow_account.status = escrow_account.status.transition(TransactionStatus::Funded)?;
+
+    let timestamp = Clock::get()?.unix_timestamp;
+    emit!(BuyerTransfers {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        from: ctx.accounts.signer.key(),
+        to: escrow_account.escrow_vault,
+        amount: received_amount,
+        timestamp,
+    });
+
+    emit!(OrderFunded {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        buyer: ctx.accounts.signer.key(),
+        amount: received_amount,
+        timestamp,
+    });
+
+    Ok(())
+}
+
+
+// 校验订单参数并写入托管账户
+fn initialize_order(
+    escrow_account: &mut Account<Escrow>,
+    buyer: Pubkey,
+    mint: Option<&InterfaceAccount<Mint>>,
+    escrow_vault: Option<&InterfaceAccount<TokenAccount>>,
+    order_id: u64,
+    args: CreateOrderArgs
+) -> Result<()> {
+    let CreateOrderArgs {
+        amount,
+        expiration,
+        nft_mint,
+        collection_mint,
+        buyer_nft_account,
+        is_nft,
+        arbitrator,
+        seller,
+        open_offer,
+        milestones,
+        release_delay
+    } = args;
 
     let clock = Clock::get()?;
     let min_allowed_expiration = clock.unix_timestamp + MIN_EXPIRATION_TIME;
+    let max_allowed_expiration = clock.unix_timestamp + MAX_EXPIRATION_TIME;
 
     require!(expiration >= min_allowed_expiration, ErrorCode::ExpirationTooSoon);
+    require!(expiration <= max_allowed_expiration, ErrorCode::ExpirationTooFar);
     require!(amount > 0, ErrorCode::AmountZero);
+    // 指定卖家与公开报价二选一
+    require!(seller.is_some() != open_offer, ErrorCode::InvalidSellerSelection);
+    if let Some(release_delay) = release_delay {
+        require!(release_delay > 0 && release_delay <= MAX_EXPIRATION_TIME, ErrorCode::InvalidReleaseDelay);
+    }
+
+    // 里程碑金额之和必须等于订单金额，截止时间不得晚于订单超时时间
+    if !milestones.is_empty() {
+        require!(milestones.len() <= MAX_MILESTONES, ErrorCode::TooManyMilestones);
+        require!(!is_nft, ErrorCode::InvalidMilestones);
+
+        let mut total: u64 = 0;
+        for milestone in milestones.iter() {
+            require!(milestone.amount > 0, ErrorCode::AmountZero);
+            if let Some(deadline) = milestone.deadline {
+                require!(deadline > clock.unix_timestamp && deadline <= expiration, ErrorCode::InvalidMilestones);
+            }
+            total = total.checked_add(milestone.amount).ok_or(ErrorCode::InvalidMilestones)?;
+        }
+        require!(total == amount, ErrorCode::InvalidMilestones);
+
+        escrow_account.milestones = milestones
+            .into_iter()
+            .map(|milestone| Milestone {
+                amount: milestone.amount,
+                deadline: milestone.deadline,
+                released: false
+            })
+            .collect();
+    }
 
     if is_nft {
-        require!(nft_mint.is_some(), ErrorCode::InvalidNftSelection);
-        require!(buyer_nft_account.is_some(), ErrorCode::InvalidNftSelection);
+        // 指定NFT与指定集合二选一
+        require!(nft_mint.is_some() != collection_mint.is_some(), ErrorCode::InvalidNftSelection);
+        // 集合订单的买家NFT账户在卖家确认时才能确定
+        if nft_mint.is_some() {
+            require!(buyer_nft_account.is_some(), ErrorCode::InvalidNftSelection);
+        }
         
         escrow_account.nft_mint = nft_mint;
+        escrow_account.collection_mint = collection_mint;
         escrow_account.buyer_nft_account = buyer_nft_account;
     };
 
-    escrow_account.buyer = ctx.accounts.signer.key();
-    escrow_account.token_mint = ctx.accounts.mint.key();
+    // 代币订单资金存放在托管代币账户，SOL订单资金直接存放在托管PDA中
+    match (mint, escrow_vault) {
+        (Some(mint), Some(escrow_vault)) => {
+            let has_transfer_fee = check_mint_extensions(&mint.to_account_info())?;
+            // 带转账手续费的代币实际到账金额不确定，无法与里程碑金额对应
+            if has_transfer_fee {
+                require!(escrow_account.milestones.is_empty(), ErrorCode::InvalidMilestones);
+            }
+
+            escrow_account.token_mint = mint.key();
+            escrow_account.escrow_vault = escrow_vault.key();
+        }
+        (None, None) => {
+            escrow_account.token_mint = native_mint::ID;
+            escrow_account.escrow_vault = escrow_account.key();
+            escrow_account.is_native = true;
+        }
+        _ => return Err(ErrorCode::MissingTokenAccount.into()),
+    }
+
+    escrow_account.buyer = buyer;
+    escrow_account.order_id = order_id;
     escrow_account.amount = amount;
-    escrow_account.escrow_vault = ctx.accounts.escrow_vault.key();
     escrow_account.expiration = expiration;
     escrow_account.is_nft = is_nft;
-    escrow_account.status = TransactionStatus::Created as u8;
+    escrow_account.arbitrator = arbitrator;
+    escrow_account.seller = seller;
+    escrow_account.open_offer = open_offer;
+    escrow_account.release_delay = release_delay;
+    escrow_account.status = TransactionStatus::Created;
 
     emit!(OrderMade {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        buyer,
+        seller,
         amount,
         expiration,
-        maker: ctx.accounts.signer.key()
+        timestamp: clock.unix_timestamp,
     });
 
     Ok(())
 }
 
 
-pub fn process_buyer_payment(ctx: Context<BuyerPayment>) -> Result<()> {
-    let escrow_account = &mut ctx.accounts.escrow;
+pub fn process_buyer_payment<'info>(ctx: Context<'_, '_, 'info, 'info, BuyerPayment<'info>>) -> Result<()> {
+    let escrow_account = &ctx.accounts.escrow;
     let clock = Clock::get()?;
 
     // 状态判断
-    require!(escrow_account.status == TransactionStatus::Created as u8, ErrorCode::CancellationNotAllowed);
+    require!(escrow_account.status == TransactionStatus::Created, ErrorCode::CancellationNotAllowed);
     // 超时判断
-    require!(escrow_account.expiration > clock.unix_timestamp, ErrorCode::ExpirationTooSoon);
-
-    // 将交易金额存入托管账户
-    let cpi_accounts = TransferChecked {
-        from: ctx.accounts.buyer_token_account.to_account_info(),
-        to: ctx.accounts.escrow_vault.to_account_info(),
-        mint: ctx.accounts.mint.to_account_info(),
-        authority: ctx.accounts.buyer.to_account_info()
-    };
+    require!(escrow_account.expiration > clock.unix_timestamp, ErrorCode::OrderExpired);
 
-    let cpi_ctx = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(), 
-        cpi_accounts
-    );
+    // 将交易金额存入托管账户，按实际到账金额记账
+    let amount = escrow_account.amount;
+    let received_amount = ctx.accounts.deposit(ctx.remaining_accounts, amount)?;
 
-    transfer_checked(cpi_ctx, escrow_account.amount, ctx.accounts.mint.decimals)?;
+    let escrow_account = &mut ctx.accounts.escrow;
+    escrow_account.amount = received_amount;
+    escrow_account.status = escrow_account.status.transition(TransactionStatus::Funded)?;
 
     emit!(BuyerTransfers {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
         from: ctx.accounts.buyer.key(),
-        to: ctx.accounts.escrow_vault.key(),
-        amount: escrow_account.amount
+        to: escrow_account.escrow_vault,
+        amount: escrow_account.amount,
+        timestamp: clock.unix_timestamp,
     });
 
     emit!(OrderFunded {
-        buyer: ctx.accounts.buyer.key(),
         escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        buyer: ctx.accounts.buyer.key(),
         amount: escrow_account.amount,
         timestamp: clock.unix_timestamp,
     });
 
-    escrow_account.status = TransactionStatus::Funded as u8;
+    Ok(())
+}
+
+
+pub fn process_amend_order<'info>(
+    ctx: Context<'_, '_, 'info, 'info, BuyerPayment<'info>>,
+    amount: u64,
+    expiration: i64
+) -> Result<()> {
+    let escrow_account = &ctx.accounts.escrow;
+    let clock = Clock::get()?;
+
+    // 卖家确认前买家可以调整报价
+    require!(
+        escrow_account.status == TransactionStatus::Created
+            || escrow_account.status == TransactionStatus::Funded,
+        ErrorCode::AmendNotAllowed
+    );
+    require!(escrow_account.expiration > clock.unix_timestamp, ErrorCode::OrderExpired);
+    validate_order_terms(escrow_account, amount, expiration, clock.unix_timestamp)?;
+
+    let old_amount = escrow_account.amount;
+    let old_expiration = escrow_account.expiration;
+
+    // 已付款的订单补足差额或退还多余资金
+    let new_amount = ctx.accounts.rebalance(ctx.remaining_accounts, ctx.bumps.escrow, amount)?;
+
+    let escrow_account = &mut ctx.accounts.escrow;
+    escrow_account.amount = new_amount;
+    escrow_account.expiration = expiration;
+
+    emit!(OrderAmended {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        buyer: ctx.accounts.buyer.key(),
+        old_amount,
+        new_amount,
+        old_expiration,
+        new_expiration: expiration,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+
+// 校验修改后的订单条款：金额与超时时间范围，里程碑订单的金额不可变且截止时间不得晚于新的超时时间
+pub fn validate_order_terms(escrow: &Escrow, amount: u64, expiration: i64, now: i64) -> Result<()> {
+    require!(expiration >= now + MIN_EXPIRATION_TIME, ErrorCode::ExpirationTooSoon);
+    require!(expiration <= now + MAX_EXPIRATION_TIME, ErrorCode::ExpirationTooFar);
+    require!(amount > 0, ErrorCode::AmountZero);
+
+    require!(
+        escrow.milestones.is_empty() || amount == escrow.amount,
+        ErrorCode::InvalidMilestones
+    );
+    require!(
+        escrow.milestones.iter().filter_map(|milestone| milestone.deadline).all(|deadline| deadline <= expiration),
+        ErrorCode::InvalidMilestones
+    );
 
     Ok(())
 }
 
 
-pub fn process_order_cancellation(ctx: Context<OrderCancellation>) -> Result<()> {
+pub fn process_order_cancellation<'info>(ctx: Context<'_, '_, 'info, 'info, OrderCancellation<'info>>) -> Result<()> {
     let escrow_account = &ctx.accounts.escrow;
     let clock = Clock::get()?;
 
     // 判断是否是买家确认前的状态
-    require!(escrow_account.status <= TransactionStatus::Funded as u8, ErrorCode::CancellationNotAllowed);
+    require!(
+        escrow_account.status == TransactionStatus::Created
+            || escrow_account.status == TransactionStatus::Funded,
+        ErrorCode::CancellationNotAllowed
+    );
     // 判断订单是否超时
-    require!(escrow_account.expiration > clock.unix_timestamp, ErrorCode::ExpirationTooFar);
+    require!(escrow_account.expiration > clock.unix_timestamp, ErrorCode::OrderExpired);
 
     // 退款逻辑
-    if escrow_account.status == TransactionStatus::Funded as u8 {
+    let mut refunded_amount = None;
+    if escrow_account.status == TransactionStatus::Funded {
+        let order_id_bytes = escrow_account.order_id.to_le_bytes();
         let signer_seeds: &[&[&[u8]]] = &[&[
             b"order",
             ctx.accounts.buyer.to_account_info().key.as_ref(),
+            order_id_bytes.as_ref(),
             &[ctx.bumps.escrow]
         ]];
 
-        let cpi_accounts = TransferChecked {
-            from: ctx.accounts.escrow_vault.to_account_info(),
-            to: ctx.accounts.buyer_token_account.to_account_info(),
-            mint: ctx.accounts.mint.to_account_info(),
-            authority: ctx.accounts.escrow.to_account_info()
+        let funds = EscrowFunds {
+            escrow: escrow_account,
+            escrow_vault: ctx.accounts.escrow_vault.as_ref(),
+            mint: ctx.accounts.mint.as_ref(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            hook_accounts: ctx.remaining_accounts,
+            signer_seeds
         };
 
-        let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(), 
-            cpi_accounts, 
-            signer_seeds
-        );
-        
-        transfer_checked(cpi_ctx, escrow_account.amount, ctx.accounts.mint.decimals)?;
-        
-        emit!(FundsRefunded {
-            buyer: ctx.accounts.buyer.key(),
-            amount: escrow_account.amount,
-            timestamp: clock.unix_timestamp,
-        });
-        
+        let refund_destination = if escrow_account.is_native {
+            ctx.accounts.buyer.to_account_info()
+        } else {
+            match &ctx.accounts.buyer_token_account {
+                Some(buyer_token_account) => buyer_token_account.to_account_info(),
+                None => return Err(ErrorCode::MissingTokenAccount.into()),
+            }
+        };
+
+        funds.pay(refund_destination, escrow_account.unreleased_amount())?;
+        refunded_amount = Some(escrow_account.unreleased_amount());
+
         msg!("用户取消订单，退款");
     };
 
     let escrow_account = &mut ctx.accounts.escrow;
 
-    escrow_account.status = TransactionStatus::Cancelled as u8;
+    escrow_account.status = escrow_account.status.transition(TransactionStatus::Cancelled)?;
+
+    if let Some(amount) = refunded_amount {
+        emit!(FundsRefunded {
+            escrow: escrow_account.key(),
+            mint: escrow_account.token_mint,
+            status: escrow_account.status,
+            buyer: ctx.accounts.buyer.key(),
+            amount,
+            timestamp: clock.unix_timestamp,
+        });
+    }
 
     emit!(OrderCancelled {
-        buyer: ctx.accounts.buyer.key(),
         escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        buyer: ctx.accounts.buyer.key(),
         timestamp: clock.unix_timestamp,
     });
 
     Ok(())
 }
+
+
+impl<'info> BuyerPayment<'info> {
+    // 买家向托管账户存入资金，返回托管账户实际收到的金额
+    fn deposit(&self, hook_accounts: &[AccountInfo<'info>], amount: u64) -> Result<u64> {
+        BuyerDeposit {
+            escrow: &self.escrow,
+            buyer: self.buyer.to_account_info(),
+            mint: self.mint.as_ref(),
+            escrow_vault: self.escrow_vault.as_ref(),
+            buyer_token_account: self.buyer_token_account.as_ref(),
+            system_program: self.system_program.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            hook_accounts
+        }.deposit(amount)
+    }
+
+    // 调整订单金额：已付款的订单补足差额或退还多余资金，返回按实际到账记账的金额
+    pub fn rebalance(&self, hook_accounts: &[AccountInfo<'info>], escrow_bump: u8, amount: u64) -> Result<u64> {
+        let old_amount = self.escrow.amount;
+        if self.escrow.status != TransactionStatus::Funded || amount == old_amount {
+            return Ok(amount);
+        }
+
+        if amount > old_amount {
+            let received_amount = self.deposit(hook_accounts, amount - old_amount)?;
+            return Ok(old_amount + received_amount);
+        }
+
+        let order_id_bytes = self.escrow.order_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"order",
+            self.buyer.to_account_info().key.as_ref(),
+            order_id_bytes.as_ref(),
+            &[escrow_bump]
+        ]];
+
+        let funds = EscrowFunds {
+            escrow: &self.escrow,
+            escrow_vault: self.escrow_vault.as_ref(),
+            mint: self.mint.as_ref(),
+            token_program: self.token_program.to_account_info(),
+            hook_accounts,
+            signer_seeds
+        };
+
+        let refund_destination = if self.escrow.is_native {
+            self.buyer.to_account_info()
+        } else {
+            match &self.buyer_token_account {
+                Some(buyer_token_account) => buyer_token_account.to_account_info(),
+                None => return Err(ErrorCode::MissingTokenAccount.into()),
+            }
+        };
+
+        funds.pay(refund_destination, old_amount - amount)?;
+        Ok(amount)
+    }
+}
+
+
+/// 买家存入托管资金：SOL订单转入托管PDA，代币订单转入托管代币账户
+pub struct BuyerDeposit<'a, 'info> {
+    pub escrow: &'a Account<'info, Escrow>,
+    pub buyer: AccountInfo<'info>,
+    pub mint: Option<&'a InterfaceAccount<'info, Mint>>,
+    pub escrow_vault: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    pub buyer_token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    pub system_program: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    // 转账钩子所需的额外账户
+    pub hook_accounts: &'a [AccountInfo<'info>]
+}
+
+impl BuyerDeposit<'_, '_> {
+    // 存入资金，返回托管账户实际收到的金额
+    pub fn deposit(&self, amount: u64) -> Result<u64> {
+        if self.escrow.is_native {
+            let cpi_accounts = Transfer {
+                from: self.buyer.clone(),
+                to: self.escrow.to_account_info()
+            };
+
+            let cpi_ctx = CpiContext::new(
+                self.system_program.clone(),
+                cpi_accounts
+            );
+
+            transfer(cpi_ctx, amount)?;
+            return Ok(amount);
+        }
+
+        let (mint, escrow_vault, buyer_token_account) = match (
+            self.mint,
+            self.escrow_vault,
+            self.buyer_token_account
+        ) {
+            (Some(mint), Some(escrow_vault), Some(buyer_token_account)) => (mint, escrow_vault, buyer_token_account),
+            _ => return Err(ErrorCode::MissingTokenAccount.into()),
+        };
+
+        let escrow_vault_info = escrow_vault.to_account_info();
+        let vault_balance_before = TokenAccount::try_deserialize(&mut &escrow_vault_info.data.borrow()[..])?.amount;
+
+        let cpi_accounts = TransferChecked {
+            from: buyer_token_account.to_account_info(),
+            to: escrow_vault_info.clone(),
+            mint: mint.to_account_info(),
+            authority: self.buyer.clone()
+        };
+
+        // 转账钩子的额外账户通过remaining_accounts传入
+        let cpi_ctx = CpiContext::new(
+            self.token_program.clone(), 
+            cpi_accounts
+        ).with_remaining_accounts(self.hook_accounts.to_vec());
+
+        transfer_checked_with_hook(cpi_ctx, amount, mint.decimals)?;
+
+        // 带转账手续费的代币实际到账金额少于转出金额，按托管账户实际收到的金额记账
+        let vault_balance_after = TokenAccount::try_deserialize(&mut &escrow_vault_info.data.borrow()[..])?.amount;
+        let received_amount = vault_balance_after - vault_balance_before;
+        require!(received_amount > 0, ErrorCode::AmountZero);
+        Ok(received_amount)
+    }
+}
+
+
+// 检查Token-2022代币扩展：拒绝不可转让与永久委托的代币，返回是否带转账手续费
+pub fn check_mint_extensions(mint: &AccountInfo) -> Result<bool> {
+    if *mint.owner != Token2022::id() {
+        return Ok(false);
+    }
+
+    let mint_data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_data)?;
+    let extensions = mint_state.get_extension_types()?;
+
+    // 不可转让的代币无法转出托管账户
+    require!(!extensions.contains(&ExtensionType::NonTransferable), ErrorCode::NonTransferableMint);
+    // 永久委托人可以随时转走托管账户中的代币
+    require!(!extensions.contains(&ExtensionType::PermanentDelegate), ErrorCode::PermanentDelegateMint);
+
+    Ok(extensions.contains(&ExtensionType::TransferFeeConfig))
+}
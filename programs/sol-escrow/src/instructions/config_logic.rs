@@ -0,0 +1,178 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{
+    constants::{ANCHOR_DISCRIMINATOR, BASIS_POINTS_DENOMINATOR, MAX_FEE_BPS, MAX_KEEPER_BOUNTY},
+    error::ErrorCode,
+    program::SolEscrow,
+    state::{Config, MintFeeConfig}
+};
+
+
+#[event]
+pub struct ConfigUpdated {
+    pub admin: Pubkey,
+    pub treasury: Pubkey,
+    pub fee_bps: u16,
+    pub keeper_bounty: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MintFeeUpdated {
+    pub mint: Pubkey,
+    pub fee_bps: u16,
+    pub timestamp: i64,
+}
+
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    // 只有程序升级权限持有者可以初始化配置
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = ANCHOR_DISCRIMINATOR + Config::INIT_SPACE,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ ErrorCode::Unauthorized)]
+    pub program: Program<'info, SolEscrow>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>
+}
+
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+
+#[derive(Accounts)]
+pub struct SetMintFee<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = ANCHOR_DISCRIMINATOR + MintFeeConfig::INIT_SPACE,
+        seeds = [b"mint_fee", mint.key().as_ref()],
+        bump
+    )]
+    pub mint_fee: Account<'info, MintFeeConfig>,
+
+    pub system_program: Program<'info, System>
+}
+
+
+pub fn process_initialize_config(ctx: Context<InitializeConfig>, treasury: Pubkey, fee_bps: u16) -> Result<()> {
+    require!(fee_bps <= MAX_FEE_BPS, ErrorCode::FeeTooHigh);
+
+    let config = &mut ctx.accounts.config;
+    config.admin = ctx.accounts.admin.key();
+    config.treasury = treasury;
+    config.fee_bps = fee_bps;
+    config.bump = ctx.bumps.config;
+
+    emit!(ConfigUpdated {
+        admin: config.admin,
+        treasury,
+        fee_bps,
+        keeper_bounty: config.keeper_bounty,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+
+pub fn process_update_config(
+    ctx: Context<UpdateConfig>,
+    new_admin: Option<Pubkey>,
+    treasury: Pubkey,
+    fee_bps: u16,
+    keeper_bounty: u64
+) -> Result<()> {
+    require!(fee_bps <= MAX_FEE_BPS, ErrorCode::FeeTooHigh);
+    require!(keeper_bounty <= MAX_KEEPER_BOUNTY, ErrorCode::KeeperBountyTooHigh);
+
+    let config = &mut ctx.accounts.config;
+    if let Some(new_admin) = new_admin {
+        config.admin = new_admin;
+    }
+    config.treasury = treasury;
+    config.fee_bps = fee_bps;
+    config.keeper_bounty = keeper_bounty;
+
+    emit!(ConfigUpdated {
+        admin: config.admin,
+        treasury,
+        fee_bps,
+        keeper_bounty,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+
+pub fn process_set_mint_fee(ctx: Context<SetMintFee>, fee_bps: u16) -> Result<()> {
+    require!(fee_bps <= MAX_FEE_BPS, ErrorCode::FeeTooHigh);
+
+    let mint_fee = &mut ctx.accounts.mint_fee;
+    mint_fee.mint = ctx.accounts.mint.key();
+    mint_fee.fee_bps = fee_bps;
+    mint_fee.bump = ctx.bumps.mint_fee;
+
+    emit!(MintFeeUpdated {
+        mint: mint_fee.mint,
+        fee_bps,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+
+// 读取代币手续费：存在单独配置时覆盖全局默认值
+pub fn resolve_fee_bps(config: &Config, mint_fee: &AccountInfo) -> Result<u16> {
+    if mint_fee.owner != &crate::ID || mint_fee.data_is_empty() {
+        return Ok(config.fee_bps);
+    }
+
+    let mint_fee = MintFeeConfig::try_deserialize(&mut &mint_fee.data.borrow()[..])?;
+    Ok(mint_fee.fee_bps)
+}
+
+
+// 按基点计算手续费
+pub fn calculate_fee(amount: u64, fee_bps: u16) -> u64 {
+    (amount as u128 * fee_bps as u128 / BASIS_POINTS_DENOMINATOR as u128) as u64
+}
@@ -0,0 +1,236 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::ANCHOR_DISCRIMINATOR,
+    error::ErrorCode,
+    // 组合账户需要引入BuyerPayment派生出的客户端模块
+    instructions::buyer_logic::*,
+    state::{CounterOffer, Escrow, TransactionStatus}
+};
+
+
+#[event]
+pub struct CounterProposed {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub seller: Pubkey,
+    pub amount: u64,
+    pub expiration: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CounterAccepted {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub old_amount: u64,
+    pub new_amount: u64,
+    pub expiration: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CounterClosed {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub seller: Pubkey,
+    pub closed_by: Pubkey,
+    pub timestamp: i64,
+}
+
+
+#[derive(Accounts)]
+#[instruction(order_id: u64)]
+pub struct ProposeCounter<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    pub buyer: SystemAccount<'info>,
+
+    #[account(
+        seeds = [b"order", buyer.key().as_ref(), order_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    // 同一卖家重复还价时覆盖之前的报价
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = ANCHOR_DISCRIMINATOR + CounterOffer::INIT_SPACE,
+        seeds = [b"counter_offer", escrow.key().as_ref(), seller.key().as_ref()],
+        bump
+    )]
+    pub counter_offer: Account<'info, CounterOffer>,
+
+    pub system_program: Program<'info, System>
+}
+
+
+// 不声明 #[instruction]：外层反序列化参数后会把剩余的指令数据传给内层账户，BuyerPayment 将无法读取 order_id
+#[derive(Accounts)]
+pub struct AcceptCounter<'info> {
+    // 买家付款相关账户，用于补足或退还差额
+    pub payment: BuyerPayment<'info>,
+
+    // 还价账户关闭后租金退还卖家
+    #[account(mut)]
+    pub seller: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        close = seller,
+        seeds = [b"counter_offer", payment.escrow.key().as_ref(), seller.key().as_ref()],
+        bump = counter_offer.bump
+    )]
+    pub counter_offer: Account<'info, CounterOffer>
+}
+
+
+#[derive(Accounts)]
+#[instruction(order_id: u64)]
+pub struct CloseCounter<'info> {
+    /// 关闭人：卖家撤回还价，或买家拒绝还价
+    #[account(
+        constraint = signer.key() == seller.key()
+            || signer.key() == buyer.key() @ ErrorCode::Unauthorized
+    )]
+    pub signer: Signer<'info>,
+    pub buyer: SystemAccount<'info>,
+
+    // 还价账户关闭后租金退还卖家
+    #[account(mut)]
+    pub seller: SystemAccount<'info>,
+
+    /// CHECK: 只用于派生还价账户地址，订单关闭后仍可清理遗留的还价
+    #[account(
+        seeds = [b"order", buyer.key().as_ref(), order_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = seller,
+        seeds = [b"counter_offer", escrow.key().as_ref(), seller.key().as_ref()],
+        bump = counter_offer.bump
+    )]
+    pub counter_offer: Account<'info, CounterOffer>
+}
+
+
+pub fn process_propose_counter(ctx: Context<ProposeCounter>, amount: u64, expiration: i64) -> Result<()> {
+    let escrow_account = &ctx.accounts.escrow;
+    let clock = Clock::get()?;
+
+    require!(
+        escrow_account.status == TransactionStatus::Created
+            || escrow_account.status == TransactionStatus::Funded,
+        ErrorCode::CounterNotAllowed
+    );
+    require!(escrow_account.expiration > clock.unix_timestamp, ErrorCode::OrderExpired);
+    // 指定卖家的订单只能由该卖家还价
+    if !escrow_account.open_offer {
+        require!(escrow_account.seller == Some(ctx.accounts.seller.key()), ErrorCode::InvalidSeller);
+    }
+    validate_order_terms(escrow_account, amount, expiration, clock.unix_timestamp)?;
+
+    let counter_offer = &mut ctx.accounts.counter_offer;
+    counter_offer.escrow = escrow_account.key();
+    counter_offer.seller = ctx.accounts.seller.key();
+    counter_offer.amount = amount;
+    counter_offer.expiration = expiration;
+    counter_offer.bump = ctx.bumps.counter_offer;
+
+    emit!(CounterProposed {
+        escrow: counter_offer.escrow,
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        seller: counter_offer.seller,
+        amount,
+        expiration,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+
+pub fn process_accept_counter<'info>(
+    ctx: Context<'_, '_, 'info, 'info, AcceptCounter<'info>>,
+    expected_amount: u64,
+    expected_expiration: i64
+) -> Result<()> {
+    let escrow_account = &ctx.accounts.payment.escrow;
+    let counter_offer = &ctx.accounts.counter_offer;
+    let clock = Clock::get()?;
+
+    require!(
+        escrow_account.status == TransactionStatus::Created
+            || escrow_account.status == TransactionStatus::Funded,
+        ErrorCode::CounterNotAllowed
+    );
+    require!(escrow_account.expiration > clock.unix_timestamp, ErrorCode::OrderExpired);
+    // 卖家可以随时覆盖还价，只接受买家签名时看到的条款
+    require!(
+        counter_offer.amount == expected_amount && counter_offer.expiration == expected_expiration,
+        ErrorCode::CounterOfferChanged
+    );
+    // 还价后订单条款可能已变化，接受时重新校验
+    validate_order_terms(escrow_account, counter_offer.amount, counter_offer.expiration, clock.unix_timestamp)?;
+
+    let old_amount = escrow_account.amount;
+    let amount = counter_offer.amount;
+    let expiration = counter_offer.expiration;
+
+    // 已付款的订单补足差额或退还多余资金
+    let new_amount = ctx.accounts.payment.rebalance(ctx.remaining_accounts, ctx.bumps.payment.escrow, amount)?;
+
+    // 接受还价后订单绑定该卖家
+    let escrow_account = &mut ctx.accounts.payment.escrow;
+    escrow_account.amount = new_amount;
+    escrow_account.expiration = expiration;
+    escrow_account.seller = Some(ctx.accounts.seller.key());
+    escrow_account.open_offer = false;
+
+    emit!(CounterAccepted {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        buyer: ctx.accounts.payment.buyer.key(),
+        seller: ctx.accounts.seller.key(),
+        old_amount,
+        new_amount,
+        expiration,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+
+pub fn process_close_counter(ctx: Context<CloseCounter>) -> Result<()> {
+    msg!("还价已关闭，租金退还卖家");
+
+    // 订单已关闭时没有可关联的订单状态，不再发出事件
+    let escrow_info = ctx.accounts.escrow.to_account_info();
+    if escrow_info.owner != ctx.program_id || escrow_info.data_is_empty() {
+        return Ok(());
+    }
+    let escrow_account = Escrow::try_deserialize(&mut &escrow_info.data.borrow()[..])?;
+
+    emit!(CounterClosed {
+        escrow: escrow_info.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        seller: ctx.accounts.seller.key(),
+        closed_by: ctx.accounts.signer.key(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,317 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface}};
+
+use crate::{
+    constants::BASIS_POINTS_DENOMINATOR,
+    error::ErrorCode,
+    instructions::{calculate_fee, pay_royalties, resolve_fee_bps, split_creator_accounts, withdraw_nft_vault, EscrowFunds},
+    state::{Config, Escrow, TransactionStatus}
+};
+
+
+#[event]
+pub struct DisputeOpened {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub opened_by: Pubkey,
+    pub arbitrator: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DisputeResolved {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub arbitrator: Pubkey,
+    pub seller_amount: u64,
+    pub buyer_amount: u64,
+    pub fee: u64,
+    pub royalty: u64,
+    pub timestamp: i64,
+}
+
+
+#[derive(Accounts)]
+#[instruction(order_id: u64)]
+pub struct OpenDispute<'info> {
+    /// 发起人：买家或已确认的卖家
+    #[account(
+        constraint = signer.key() == escrow.buyer
+            || escrow.seller == Some(signer.key()) @ ErrorCode::Unauthorized
+    )]
+    pub signer: Signer<'info>,
+    pub buyer: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"order", buyer.key().as_ref(), order_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+}
+
+
+#[derive(Accounts)]
+#[instruction(order_id: u64)]
+pub struct ResolveDispute<'info> {
+    #[account(
+        constraint = escrow.arbitrator == Some(arbitrator.key()) @ ErrorCode::Unauthorized
+    )]
+    pub arbitrator: Signer<'info>,
+    // SOL订单退款直接转入买家账户
+    #[account(mut)]
+    pub buyer: SystemAccount<'info>,
+    // NFT托管账户关闭后租金退还卖家
+    #[account(mut)]
+    pub seller: SystemAccount<'info>,
+    // 代币订单需要提供，SOL订单传空
+    #[account(constraint = mint.key() == escrow.token_mint @ ErrorCode::InvalidMint)]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"order", buyer.key().as_ref(), order_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = escrow.seller == Some(seller.key()) @ ErrorCode::InvalidSeller
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+        constraint = escrow_vault.key() == escrow.escrow_vault @ ErrorCode::InvalidEscrowVault
+    )]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = buyer,
+        associated_token::token_program = token_program
+    )]
+    pub buyer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = seller,
+        associated_token::token_program = token_program
+    )]
+    pub seller_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // 卖家获得的款项与正常放款一样收取协议手续费
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: 代币手续费配置PDA，未初始化时使用全局默认手续费，SOL订单按原生SOL的mint派生
+    #[account(seeds = [b"mint_fee", escrow.token_mint.as_ref()], bump)]
+    pub mint_fee: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::token_program = token_program,
+        constraint = treasury_token_account.owner == config.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // SOL订单的手续费直接转入收款方账户
+    #[account(
+        mut,
+        address = config.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury: Option<SystemAccount<'info>>,
+
+    // NFT订单：卖家获得款项时NFT交付买家，全额退款时NFT退还卖家
+    pub nft_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub nft_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub nft_destination: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // NFT所属的代币程序，可能与计价代币的程序不同
+    pub nft_token_program: Option<Interface<'info, TokenInterface>>,
+
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>
+}
+
+
+pub fn process_open_dispute(ctx: Context<OpenDispute>) -> Result<()> {
+    let escrow_account = &mut ctx.accounts.escrow;
+
+    // 只有已付款或卖家已确认的订单可以发起争议
+    require!(
+        escrow_account.status == TransactionStatus::Funded
+            || escrow_account.status == TransactionStatus::InTransit,
+        ErrorCode::DisputeNotAllowed
+    );
+    // 卖家确认前买家仍可取消或退款，卖家只能对已确认的订单发起争议
+    if ctx.accounts.signer.key() != escrow_account.buyer {
+        require!(escrow_account.status == TransactionStatus::InTransit, ErrorCode::DisputeNotAllowed);
+    }
+    // 争议需要明确的卖家与仲裁者
+    require!(escrow_account.seller.is_some(), ErrorCode::DisputeNotAllowed);
+    let arbitrator = match escrow_account.arbitrator {
+        Some(arbitrator) => arbitrator,
+        None => return Err(ErrorCode::DisputeNotAllowed.into()),
+    };
+
+    escrow_account.status = escrow_account.status.transition(TransactionStatus::Disputed)?;
+
+    emit!(DisputeOpened {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        opened_by: ctx.accounts.signer.key(),
+        arbitrator,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+
+pub fn process_resolve_dispute<'info>(ctx: Context<'_, '_, 'info, 'info, ResolveDispute<'info>>, seller_bps: u16) -> Result<()> {
+    let escrow_account = &ctx.accounts.escrow;
+
+    require!(escrow_account.status == TransactionStatus::Disputed, ErrorCode::OrderNotDisputed);
+    require!(seller_bps <= BASIS_POINTS_DENOMINATOR, ErrorCode::InvalidBasisPoints);
+
+    // 按基点拆分尚未放款的托管资金，余数归买家
+    let unreleased_amount = escrow_account.unreleased_amount();
+    let seller_amount = (unreleased_amount as u128 * seller_bps as u128
+        / BASIS_POINTS_DENOMINATOR as u128) as u64;
+    let buyer_amount = unreleased_amount - seller_amount;
+
+    let order_id_bytes = escrow_account.order_id.to_le_bytes();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"order",
+        ctx.accounts.buyer.to_account_info().key.as_ref(),
+        order_id_bytes.as_ref(),
+        &[ctx.bumps.escrow]
+    ]];
+
+    // 卖家所得与正常放款一样支付NFT版税，创作者收款账户位于remaining_accounts开头
+    let royalty_total = calculate_fee(seller_amount, escrow_account.royalty_bps);
+    let (creator_accounts, hook_accounts) = split_creator_accounts(escrow_account, royalty_total, ctx.remaining_accounts)?;
+
+    let funds = EscrowFunds {
+        escrow: escrow_account,
+        escrow_vault: ctx.accounts.escrow_vault.as_ref(),
+        mint: ctx.accounts.mint.as_ref(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        hook_accounts,
+        signer_seeds
+    };
+
+    // SOL订单直接转入双方账户，代币订单转入双方的代币账户
+    let (seller_destination, buyer_destination, treasury_destination) = if escrow_account.is_native {
+        let treasury = match &ctx.accounts.treasury {
+            Some(treasury) => treasury,
+            None => return Err(ErrorCode::InvalidTreasury.into()),
+        };
+        (ctx.accounts.seller.to_account_info(), ctx.accounts.buyer.to_account_info(), treasury.to_account_info())
+    } else {
+        match (&ctx.accounts.seller_token_account, &ctx.accounts.buyer_token_account, &ctx.accounts.treasury_token_account) {
+            (Some(seller_token_account), Some(buyer_token_account), Some(treasury_token_account)) => (
+                seller_token_account.to_account_info(),
+                buyer_token_account.to_account_info(),
+                treasury_token_account.to_account_info()
+            ),
+            _ => return Err(ErrorCode::MissingTokenAccount.into()),
+        }
+    };
+
+    // 卖家所得扣除协议手续费，退还买家的部分不收费
+    let fee_bps = resolve_fee_bps(&ctx.accounts.config, &ctx.accounts.mint_fee.to_account_info())?;
+    let fee = calculate_fee(seller_amount, fee_bps);
+
+    let royalty = pay_royalties(escrow_account, royalty_total, creator_accounts, |to, amount| funds.pay(to, amount))?;
+    let seller_net = seller_amount
+        .checked_sub(fee)
+        .and_then(|amount| amount.checked_sub(royalty))
+        .ok_or(ErrorCode::RoyaltyTooHigh)?;
+
+    funds.pay(seller_destination, seller_net)?;
+
+    if fee > 0 {
+        funds.pay(treasury_destination, fee)?;
+    }
+
+    funds.pay(buyer_destination, buyer_amount)?;
+
+    if let Some(expected_nft_vault) = escrow_account.nft_vault {
+        let nft_vault = match &ctx.accounts.nft_vault {
+            Some(nft_vault) => nft_vault,
+            None => return Err(ErrorCode::MissingNftVault.into())
+        };
+        require!(nft_vault.key() == expected_nft_vault, ErrorCode::InvalidNftVault);
+
+        let nft_mint = match &ctx.accounts.nft_mint {
+            Some(nft_mint) => nft_mint,
+            None => return Err(ErrorCode::MissingNftMint.into())
+        };
+        require!(escrow_account.nft_mint == Some(nft_mint.key()), ErrorCode::InvalidNftAccount);
+
+        let nft_destination = match &ctx.accounts.nft_destination {
+            Some(nft_destination) => nft_destination,
+            None => return Err(ErrorCode::MissingNftAccount.into())
+        };
+        require!(nft_destination.mint == nft_mint.key(), ErrorCode::InvalidNftAccount);
+        if seller_amount > 0 {
+            require!(escrow_account.buyer_nft_account == Some(nft_destination.key()), ErrorCode::InvalidNftAccount);
+        } else {
+            require!(nft_destination.owner == ctx.accounts.seller.key(), ErrorCode::InvalidNftOwner);
+        }
+
+        let nft_token_program = match &ctx.accounts.nft_token_program {
+            Some(nft_token_program) => nft_token_program,
+            None => return Err(ErrorCode::InvalidNftTokenProgram.into())
+        };
+
+        withdraw_nft_vault(
+            ctx.accounts.escrow.to_account_info(),
+            nft_vault.to_account_info(),
+            nft_mint.to_account_info(),
+            nft_destination.to_account_info(),
+            ctx.accounts.seller.to_account_info(),
+            nft_token_program.to_account_info(),
+            signer_seeds
+        )?;
+    }
+
+    msg!("争议已裁决");
+
+    // 卖家获得任意款项视为交易完成，全额退款视为取消
+    let escrow_account = &mut ctx.accounts.escrow;
+    let resolved_status = if seller_amount > 0 || escrow_account.released_amount > 0 {
+        TransactionStatus::Success
+    } else {
+        TransactionStatus::Cancelled
+    };
+    escrow_account.status = escrow_account.status.transition(resolved_status)?;
+    // 卖家所得含手续费与版税，与放款一致计入已放款金额
+    escrow_account.released_amount += seller_amount;
+
+    emit!(DisputeResolved {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        arbitrator: ctx.accounts.arbitrator.key(),
+        seller_amount,
+        buyer_amount,
+        fee,
+        royalty,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
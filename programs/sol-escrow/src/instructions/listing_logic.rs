@@ -0,0 +1,486 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, metadata::MetadataAccount, token_interface::{CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked}};
+
+use crate::{
+    constants::{ANCHOR_DISCRIMINATOR, MAX_EXPIRATION_TIME, MIN_EXPIRATION_TIME},
+    error::ErrorCode,
+    instructions::{
+        calculate_fee, check_mint_extensions, close_escrow_vault, pay_royalties, record_royalties, resolve_fee_bps, split_creator_accounts,
+        transfer_checked_with_hook
+    },
+    state::{Config, Escrow, TransactionStatus}
+};
+
+
+#[event]
+pub struct ListingCreated {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub seller: Pubkey,
+    pub asset_mint: Pubkey,
+    pub asset_amount: u64,
+    pub price: u64,
+    pub expiration: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ListingFilled {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub price: u64,
+    pub fee: u64,
+    pub royalty: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ListingCancelled {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub seller: Pubkey,
+    pub timestamp: i64,
+}
+
+
+/// 创建挂单参数
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CreateListingArgs {
+    pub price: u64,  // 售价，以 token_mint 计价
+    pub asset_amount: u64,  // 挂单资产数量，NFT 为 1
+    pub expiration: i64,  // 挂单超时时间
+    pub is_nft: bool  // 是否为NFT挂单，NFT挂单需提供元数据账户，成交时支付版税
+}
+
+
+#[derive(Accounts)]
+#[instruction(listing_id: u64)]
+pub struct CreateListing<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    // 买家付款所用代币
+    #[account(mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    // 挂单出售的资产
+    #[account(mint::token_program = asset_token_program)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = seller,
+        space = ANCHOR_DISCRIMINATOR + Escrow::INIT_SPACE,
+        seeds = [b"listing", seller.key().as_ref(), listing_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    // 资产托管账户
+    #[account(
+        init,
+        payer = seller,
+        associated_token::mint = asset_mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = asset_token_program
+    )]
+    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = asset_mint,
+        associated_token::authority = seller,
+        associated_token::token_program = asset_token_program
+    )]
+    pub seller_asset_account: InterfaceAccount<'info, TokenAccount>,
+
+    // NFT元数据账户，NFT挂单需要提供，用于记录成交时的版税
+    pub nft_metadata: Option<Account<'info, MetadataAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub asset_token_program: Interface<'info, TokenInterface>
+}
+
+
+#[derive(Accounts)]
+#[instruction(listing_id: u64)]
+pub struct FillListing<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    // 挂单关闭后租金退还卖家
+    #[account(mut)]
+    pub seller: SystemAccount<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+    // 关闭托管账户前归集其中预扣的转账手续费，需要可写
+    #[account(mut)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"listing", seller.key().as_ref(), listing_id.to_le_bytes().as_ref()],
+        bump,
+        close = seller,
+        has_one = escrow_vault @ ErrorCode::InvalidEscrowVault,
+        constraint = escrow.token_mint == mint.key() @ ErrorCode::InvalidMint,
+        constraint = escrow.nft_mint == Some(asset_mint.key()) @ ErrorCode::InvalidMint
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        associated_token::mint = asset_mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = asset_token_program
+    )]
+    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = buyer,
+        associated_token::token_program = token_program
+    )]
+    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = seller,
+        associated_token::token_program = token_program
+    )]
+    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = asset_mint,
+        associated_token::authority = buyer,
+        associated_token::token_program = asset_token_program
+    )]
+    pub buyer_asset_account: InterfaceAccount<'info, TokenAccount>,
+
+    // 挂单成交与订单放款一样收取协议手续费
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: 代币手续费配置PDA，未初始化时使用全局默认手续费
+    #[account(seeds = [b"mint_fee", mint.key().as_ref()], bump)]
+    pub mint_fee: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::token_program = token_program,
+        constraint = treasury_token_account.owner == config.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub asset_token_program: Interface<'info, TokenInterface>
+}
+
+
+#[derive(Accounts)]
+#[instruction(listing_id: u64)]
+pub struct CancelListing<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    // 关闭托管账户前归集其中预扣的转账手续费，需要可写
+    #[account(mut)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"listing", seller.key().as_ref(), listing_id.to_le_bytes().as_ref()],
+        bump,
+        close = seller,
+        has_one = escrow_vault @ ErrorCode::InvalidEscrowVault,
+        constraint = escrow.nft_mint == Some(asset_mint.key()) @ ErrorCode::InvalidMint
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        associated_token::mint = asset_mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = asset_token_program
+    )]
+    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = asset_mint,
+        associated_token::authority = seller,
+        associated_token::token_program = asset_token_program
+    )]
+    pub seller_asset_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub asset_token_program: Interface<'info, TokenInterface>
+}
+
+
+pub fn process_create_listing<'info>(ctx: Context<'_, '_, 'info, 'info, CreateListing<'info>>, listing_id: u64, args: CreateListingArgs) -> Result<()> {
+    let CreateListingArgs {
+        price,
+        asset_amount,
+        expiration,
+        is_nft
+    } = args;
+
+    let clock = Clock::get()?;
+    let min_allowed_expiration = clock.unix_timestamp + MIN_EXPIRATION_TIME;
+    let max_allowed_expiration = clock.unix_timestamp + MAX_EXPIRATION_TIME;
+
+    require!(expiration >= min_allowed_expiration, ErrorCode::ExpirationTooSoon);
+    require!(expiration <= max_allowed_expiration, ErrorCode::ExpirationTooFar);
+    require!(price > 0, ErrorCode::AmountZero);
+    require!(asset_amount > 0, ErrorCode::AmountZero);
+    if is_nft {
+        require!(ctx.accounts.asset_mint.decimals == 0 && asset_amount == 1, ErrorCode::InvalidNftAmount);
+    }
+
+    // 与订单一样拒绝不可转让与永久委托的代币
+    check_mint_extensions(&ctx.accounts.mint.to_account_info())?;
+    check_mint_extensions(&ctx.accounts.asset_mint.to_account_info())?;
+
+    // 将挂单资产存入托管账户，转账钩子的额外账户通过remaining_accounts传入
+    let cpi_accounts = TransferChecked {
+        from: ctx.accounts.seller_asset_account.to_account_info(),
+        to: ctx.accounts.escrow_vault.to_account_info(),
+        mint: ctx.accounts.asset_mint.to_account_info(),
+        authority: ctx.accounts.seller.to_account_info()
+    };
+
+    let cpi_ctx = CpiContext::new(
+        ctx.accounts.asset_token_program.to_account_info(),
+        cpi_accounts
+    ).with_remaining_accounts(ctx.remaining_accounts.to_vec());
+
+    transfer_checked_with_hook(cpi_ctx, asset_amount, ctx.accounts.asset_mint.decimals)?;
+
+    // 带转账手续费的资产实际到账少于转出数量，按托管账户实际收到的数量记录
+    ctx.accounts.escrow_vault.reload()?;
+    let received_amount = ctx.accounts.escrow_vault.amount;
+    require!(received_amount > 0, ErrorCode::AmountZero);
+
+    // 挂单复用订单账户：买家在成交时确定
+    let escrow_account = &mut ctx.accounts.escrow;
+    escrow_account.buyer = Pubkey::default();
+    escrow_account.order_id = listing_id;
+    escrow_account.seller = Some(ctx.accounts.seller.key());
+    escrow_account.token_mint = ctx.accounts.mint.key();
+    escrow_account.nft_mint = Some(ctx.accounts.asset_mint.key());
+    escrow_account.amount = price;
+    escrow_account.escrow_vault = ctx.accounts.escrow_vault.key();
+    escrow_account.is_nft = is_nft;
+    escrow_account.expiration = expiration;
+    escrow_account.status = TransactionStatus::Created;
+    escrow_account.is_listing = true;
+    escrow_account.asset_amount = received_amount;
+
+    if escrow_account.is_nft {
+        let metadata = match &ctx.accounts.nft_metadata {
+            Some(metadata) => metadata,
+            None => return Err(ErrorCode::MissingMetadata.into()),
+        };
+        require!(metadata.mint == ctx.accounts.asset_mint.key(), ErrorCode::InvalidMetadata);
+        record_royalties(escrow_account, metadata)?;
+    }
+
+    emit!(ListingCreated {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        seller: ctx.accounts.seller.key(),
+        asset_mint: ctx.accounts.asset_mint.key(),
+        asset_amount: received_amount,
+        price,
+        expiration,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+
+pub fn process_fill_listing<'info>(ctx: Context<'_, '_, 'info, 'info, FillListing<'info>>) -> Result<()> {
+    let escrow_account = &ctx.accounts.escrow;
+    let clock = Clock::get()?;
+
+    require!(escrow_account.is_listing, ErrorCode::NotAListing);
+    require!(escrow_account.status == TransactionStatus::Created, ErrorCode::ListingNotAvailable);
+    require!(escrow_account.expiration > clock.unix_timestamp, ErrorCode::OrderExpired);
+
+    // 买家直接向卖家付款，协议手续费转入收款方
+    let fee_bps = resolve_fee_bps(&ctx.accounts.config, &ctx.accounts.mint_fee.to_account_info())?;
+    let fee = calculate_fee(escrow_account.amount, fee_bps);
+
+    // NFT挂单由买家按份额向已验证的创作者支付版税，创作者代币账户位于remaining_accounts开头
+    // 其后为付款代币与挂单资产转账钩子所需的额外账户，钩子按地址从中查找所需账户
+    let royalty_total = calculate_fee(escrow_account.amount, escrow_account.royalty_bps);
+    let (creator_accounts, hook_accounts) = split_creator_accounts(escrow_account, royalty_total, ctx.remaining_accounts)?;
+    let royalty = pay_royalties(escrow_account, royalty_total, creator_accounts, |to, amount| ctx.accounts.pay(to, amount, hook_accounts))?;
+    let seller_amount = escrow_account.amount
+        .checked_sub(fee)
+        .and_then(|amount| amount.checked_sub(royalty))
+        .ok_or(ErrorCode::RoyaltyTooHigh)?;
+
+    ctx.accounts.pay(ctx.accounts.seller_token_account.to_account_info(), seller_amount, hook_accounts)?;
+
+    if fee > 0 {
+        ctx.accounts.pay(ctx.accounts.treasury_token_account.to_account_info(), fee, hook_accounts)?;
+    }
+
+    let order_id_bytes = escrow_account.order_id.to_le_bytes();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"listing",
+        ctx.accounts.seller.to_account_info().key.as_ref(),
+        order_id_bytes.as_ref(),
+        &[ctx.bumps.escrow]
+    ]];
+
+    // 托管资产交付买家
+    let cpi_accounts = TransferChecked {
+        from: ctx.accounts.escrow_vault.to_account_info(),
+        to: ctx.accounts.buyer_asset_account.to_account_info(),
+        mint: ctx.accounts.asset_mint.to_account_info(),
+        authority: ctx.accounts.escrow.to_account_info()
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.asset_token_program.to_account_info(),
+        cpi_accounts,
+        signer_seeds
+    ).with_remaining_accounts(hook_accounts.to_vec());
+
+    transfer_checked_with_hook(cpi_ctx, escrow_account.asset_amount, ctx.accounts.asset_mint.decimals)?;
+
+    // 关闭资产托管账户，租金退还卖家
+    let cpi_accounts = CloseAccount {
+        account: ctx.accounts.escrow_vault.to_account_info(),
+        destination: ctx.accounts.seller.to_account_info(),
+        authority: ctx.accounts.escrow.to_account_info()
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.asset_token_program.to_account_info(),
+        cpi_accounts,
+        signer_seeds
+    );
+
+    close_escrow_vault(cpi_ctx, ctx.accounts.asset_mint.to_account_info())?;
+
+    msg!("挂单成交");
+
+    let escrow_account = &mut ctx.accounts.escrow;
+    escrow_account.buyer = ctx.accounts.buyer.key();
+    escrow_account.status = escrow_account.status.transition_fill(escrow_account.is_listing)?;
+
+    emit!(ListingFilled {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        seller: ctx.accounts.seller.key(),
+        buyer: ctx.accounts.buyer.key(),
+        price: escrow_account.amount,
+        fee,
+        royalty,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+
+impl<'info> FillListing<'info> {
+    // 从买家代币账户付款
+    fn pay(&self, to: AccountInfo<'info>, amount: u64, hook_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: self.buyer_token_account.to_account_info(),
+            to,
+            mint: self.mint.to_account_info(),
+            authority: self.buyer.to_account_info()
+        };
+
+        let cpi_ctx = CpiContext::new(
+            self.token_program.to_account_info(),
+            cpi_accounts
+        ).with_remaining_accounts(hook_accounts.to_vec());
+
+        transfer_checked_with_hook(cpi_ctx, amount, self.mint.decimals)
+    }
+}
+
+
+pub fn process_cancel_listing<'info>(ctx: Context<'_, '_, 'info, 'info, CancelListing<'info>>) -> Result<()> {
+    let escrow_account = &ctx.accounts.escrow;
+
+    require!(escrow_account.is_listing, ErrorCode::NotAListing);
+    require!(escrow_account.status == TransactionStatus::Created, ErrorCode::ListingNotAvailable);
+
+    let order_id_bytes = escrow_account.order_id.to_le_bytes();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"listing",
+        ctx.accounts.seller.to_account_info().key.as_ref(),
+        order_id_bytes.as_ref(),
+        &[ctx.bumps.escrow]
+    ]];
+
+    // 托管资产退还卖家，转账钩子的额外账户通过remaining_accounts传入
+    let cpi_accounts = TransferChecked {
+        from: ctx.accounts.escrow_vault.to_account_info(),
+        to: ctx.accounts.seller_asset_account.to_account_info(),
+        mint: ctx.accounts.asset_mint.to_account_info(),
+        authority: ctx.accounts.escrow.to_account_info()
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.asset_token_program.to_account_info(),
+        cpi_accounts,
+        signer_seeds
+    ).with_remaining_accounts(ctx.remaining_accounts.to_vec());
+
+    transfer_checked_with_hook(cpi_ctx, escrow_account.asset_amount, ctx.accounts.asset_mint.decimals)?;
+
+    let cpi_accounts = CloseAccount {
+        account: ctx.accounts.escrow_vault.to_account_info(),
+        destination: ctx.accounts.seller.to_account_info(),
+        authority: ctx.accounts.escrow.to_account_info()
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.asset_token_program.to_account_info(),
+        cpi_accounts,
+        signer_seeds
+    );
+
+    close_escrow_vault(cpi_ctx, ctx.accounts.asset_mint.to_account_info())?;
+
+    msg!("卖家取消挂单");
+
+    let escrow_account = &mut ctx.accounts.escrow;
+    escrow_account.status = escrow_account.status.transition(TransactionStatus::Cancelled)?;
+
+    emit!(ListingCancelled {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        seller: ctx.accounts.seller.key(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
@@ -1,37 +1,105 @@
-use anchor_lang::prelude::*;
-use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}};
+use anchor_lang::{prelude::*, solana_program::program::{invoke, invoke_signed}};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account,
+        spl_token_2022::{
+            self,
+            extension::{transfer_fee::TransferFeeAmount, BaseStateWithExtensions, StateWithExtensions},
+            state::Account as TokenAccountState
+        },
+        CloseAccount, Mint, Token2022, TokenAccount, TokenInterface, TransferChecked
+    }
+};
 
-use crate::{error::ErrorCode, state::{Escrow, TransactionStatus}};
+use crate::{
+    error::ErrorCode,
+    instructions::{calculate_fee, resolve_fee_bps, withdraw_nft_vault},
+    state::{Config, Escrow, TransactionStatus}
+};
 
 
 #[event]
 pub struct FundsReleased {
     pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
     pub seller: Pubkey,
     pub buyer: Pubkey,
     pub amount: u64,
+    pub fee: u64,
+    pub royalty: u64,
     pub timestamp: i64,
 }
 
 #[event]
 pub struct OrderTimedOut {
     pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
     pub buyer: Pubkey,
     pub refunded_amount: u64,
     pub timestamp: i64,
 }
 
+#[event]
+pub struct MilestoneReleased {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub seller: Pubkey,
+    pub index: u8,
+    pub amount: u64,
+    pub fee: u64,
+    pub released_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderCranked {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub keeper: Pubkey,
+    pub buyer: Pubkey,
+    pub refunded_amount: u64,
+    pub bounty: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderClosed {
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
+    pub buyer: Pubkey,
+    pub timestamp: i64,
+}
+
 
 #[derive(Accounts)]
+#[instruction(order_id: u64)]
 pub struct EscrowRelease<'info> {
+    /// 放款授权人：买家、订单指定的仲裁者，或自动放款时间、里程碑截止时间已到的卖家
+    #[account(
+        constraint = authority.key() == escrow.buyer
+            || escrow.arbitrator == Some(authority.key())
+            || escrow.seller == Some(authority.key()) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
     pub buyer: SystemAccount<'info>,
+    // NFT托管账户关闭后租金退还卖家
+    #[account(mut)]
     pub seller: SystemAccount<'info>,
-    pub mint: InterfaceAccount<'info, Mint>,
+    // 代币订单需要提供，SOL订单传空
+    #[account(constraint = mint.key() == escrow.token_mint @ ErrorCode::InvalidMint)]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
 
     #[account(
         mut,
-        seeds = [b"order", buyer.key().as_ref()],
-        bump
+        seeds = [b"order", buyer.key().as_ref(), order_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = escrow.seller == Some(seller.key()) @ ErrorCode::InvalidSeller
     )]
     pub escrow: Account<'info, Escrow>,
 
@@ -39,9 +107,10 @@ pub struct EscrowRelease<'info> {
         mut,
         associated_token::mint = mint,
         associated_token::authority = escrow,
-        associated_token::token_program = token_program
+        associated_token::token_program = token_program,
+        constraint = escrow_vault.key() == escrow.escrow_vault @ ErrorCode::InvalidEscrowVault
     )]
-    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         mut,
@@ -49,7 +118,42 @@ pub struct EscrowRelease<'info> {
         associated_token::authority = seller,
         associated_token::token_program = token_program
     )]
-    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub seller_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // 协议手续费配置
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: 代币手续费配置PDA，未初始化时使用全局默认手续费，SOL订单按原生SOL的mint派生
+    #[account(seeds = [b"mint_fee", escrow.token_mint.as_ref()], bump)]
+    pub mint_fee: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::token_program = token_program,
+        constraint = treasury_token_account.owner == config.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // SOL订单的手续费直接转入收款方账户
+    #[account(
+        mut,
+        address = config.treasury @ ErrorCode::InvalidTreasury
+    )]
+    pub treasury: Option<SystemAccount<'info>>,
+
+    // NFT订单：托管的NFT交付买家
+    pub nft_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub nft_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub buyer_nft_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // NFT所属的代币程序，可能与计价代币的程序不同
+    pub nft_token_program: Option<Interface<'info, TokenInterface>>,
 
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
@@ -58,13 +162,18 @@ pub struct EscrowRelease<'info> {
 
 
 #[derive(Accounts)]
+#[instruction(order_id: u64)]
 pub struct TimeoutCheck<'info> {
+    // 超时处理无需买家签名，退款只会进入买家账户或买家的关联代币账户
+    #[account(mut)]
     pub buyer: SystemAccount<'info>,
-    pub mint: InterfaceAccount<'info, Mint>,
+    // 代币订单需要提供，SOL订单传空；清理时归集托管账户中预扣的转账手续费，需要可写
+    #[account(mut, constraint = mint.key() == escrow.token_mint @ ErrorCode::InvalidMint)]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
 
     #[account(
         mut,
-        seeds = [b"order", buyer.key().as_ref()],
+        seeds = [b"order", buyer.key().as_ref(), order_id.to_le_bytes().as_ref()],
         bump
     )]
     pub escrow: Account<'info, Escrow>,
@@ -73,9 +182,10 @@ pub struct TimeoutCheck<'info> {
         mut,
         associated_token::mint = mint,
         associated_token::authority = escrow,
-        associated_token::token_program = token_program
+        associated_token::token_program = token_program,
+        constraint = escrow_vault.key() == escrow.escrow_vault @ ErrorCode::InvalidEscrowVault
     )]
-    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         mut,
@@ -83,7 +193,22 @@ pub struct TimeoutCheck<'info> {
         associated_token::authority = buyer,
         associated_token::token_program = token_program
     )]
-    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub buyer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // NFT订单：托管的NFT退还卖家，托管账户租金退还卖家
+    #[account(mut)]
+    pub seller: Option<SystemAccount<'info>>,
+
+    pub nft_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub nft_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub seller_nft_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // NFT所属的代币程序，可能与计价代币的程序不同
+    pub nft_token_program: Option<Interface<'info, TokenInterface>>,
 
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
@@ -91,42 +216,154 @@ pub struct TimeoutCheck<'info> {
 }
 
 
-pub fn process_escrow_release(ctx: Context<EscrowRelease>) -> Result<()> {
+// 不声明 #[instruction]：外层反序列化参数后会把剩余的指令数据传给内层账户，TimeoutCheck 将无法读取 order_id
+#[derive(Accounts)]
+pub struct CrankExpired<'info> {
+    // 任何人都可以清理超时订单并获得奖励
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+
+    pub timeout: TimeoutCheck<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>
+}
+
+
+#[derive(Accounts)]
+#[instruction(order_id: u64)]
+pub struct CloseOrder<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    // 代币订单需要提供，SOL订单传空；关闭时归集托管账户中预扣的转账手续费，需要可写
+    #[account(mut, constraint = mint.key() == escrow.token_mint @ ErrorCode::InvalidMint)]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"order", buyer.key().as_ref(), order_id.to_le_bytes().as_ref()],
+        bump,
+        close = buyer
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+        constraint = escrow_vault.key() == escrow.escrow_vault @ ErrorCode::InvalidEscrowVault
+    )]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>
+}
+
+
+pub fn process_escrow_release<'info>(ctx: Context<'_, '_, 'info, 'info, EscrowRelease<'info>>) -> Result<()> {
     let escrow_account = &ctx.accounts.escrow;
 
-    require!(escrow_account.status == TransactionStatus::InTransit as u8, ErrorCode::FundsReleaseNotAllowed);
+    require!(escrow_account.status == TransactionStatus::InTransit, ErrorCode::FundsReleaseNotAllowed);
+    check_release_authority(ctx.accounts, None)?;
 
+    let order_id_bytes = escrow_account.order_id.to_le_bytes();
     let signer_seeds: &[&[&[u8]]] = &[&[
         b"order",
         ctx.accounts.buyer.to_account_info().key.as_ref(),
+        order_id_bytes.as_ref(),
         &[ctx.bumps.escrow]
     ]];
 
-    let cpi_accounts = TransferChecked {
-        from: ctx.accounts.escrow_vault.to_account_info(),
-        to: ctx.accounts.seller_token_account.to_account_info(),
-        mint: ctx.accounts.mint.to_account_info(),
-        authority: ctx.accounts.escrow.to_account_info()
-    };
+    // 放出尚未按里程碑释放的全部余额
+    let release_amount = escrow_account.unreleased_amount();
+
+    // 扣除协议手续费后支付卖家
+    let fee_bps = resolve_fee_bps(&ctx.accounts.config, &ctx.accounts.mint_fee.to_account_info())?;
+    let fee = calculate_fee(release_amount, fee_bps);
 
-    let cpi_ctx = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(), 
-        cpi_accounts, 
+    // NFT版税：按份额支付给已验证的创作者
+    let royalty_total = calculate_fee(release_amount, escrow_account.royalty_bps);
+    let (creator_accounts, hook_accounts) = split_creator_accounts(escrow_account, royalty_total, ctx.remaining_accounts)?;
+
+    let funds = EscrowFunds {
+        escrow: escrow_account,
+        escrow_vault: ctx.accounts.escrow_vault.as_ref(),
+        mint: ctx.accounts.mint.as_ref(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        hook_accounts,
         signer_seeds
-    );
+    };
 
-    transfer_checked(cpi_ctx, escrow_account.amount, ctx.accounts.mint.decimals)?;
+    let royalty_paid = pay_royalties(escrow_account, royalty_total, creator_accounts, |to, amount| funds.pay(to, amount))?;
+
+    let seller_amount = release_amount
+        .checked_sub(fee)
+        .and_then(|amount| amount.checked_sub(royalty_paid))
+        .ok_or(ErrorCode::RoyaltyTooHigh)?;
+
+    let (seller_destination, treasury_destination) = settlement_destinations(ctx.accounts)?;
+
+    funds.pay(seller_destination, seller_amount)?;
+
+    if fee > 0 {
+        funds.pay(treasury_destination, fee)?;
+    }
+
+    if escrow_account.is_nft {
+        let nft_vault = match &ctx.accounts.nft_vault {
+            Some(nft_vault) => nft_vault,
+            None => return Err(ErrorCode::MissingNftVault.into())
+        };
+        require!(escrow_account.nft_vault == Some(nft_vault.key()), ErrorCode::InvalidNftVault);
+
+        let nft_mint = match &ctx.accounts.nft_mint {
+            Some(nft_mint) => nft_mint,
+            None => return Err(ErrorCode::MissingNftMint.into())
+        };
+        require!(escrow_account.nft_mint == Some(nft_mint.key()), ErrorCode::InvalidNftAccount);
+
+        let buyer_nft_account = match &ctx.accounts.buyer_nft_account {
+            Some(buyer_nft_account) => buyer_nft_account,
+            None => return Err(ErrorCode::MissingBuyerNftAccount.into())
+        };
+        require!(escrow_account.buyer_nft_account == Some(buyer_nft_account.key()), ErrorCode::InvalidNftAccount);
+
+        let nft_token_program = match &ctx.accounts.nft_token_program {
+            Some(nft_token_program) => nft_token_program,
+            None => return Err(ErrorCode::InvalidNftTokenProgram.into())
+        };
+
+        withdraw_nft_vault(
+            ctx.accounts.escrow.to_account_info(),
+            nft_vault.to_account_info(),
+            nft_mint.to_account_info(),
+            buyer_nft_account.to_account_info(),
+            ctx.accounts.seller.to_account_info(),
+            nft_token_program.to_account_info(),
+            signer_seeds
+        )?;
+    }
 
     msg!("托管账户释放资金完毕");
 
     let escrow_account = &mut ctx.accounts.escrow;
-    escrow_account.status = TransactionStatus::Success as u8;
+    escrow_account.released_amount = escrow_account.amount;
+    for milestone in escrow_account.milestones.iter_mut() {
+        milestone.released = true;
+    }
+    escrow_account.status = escrow_account.status.transition(TransactionStatus::Success)?;
 
     emit!(FundsReleased {
         escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
         seller: ctx.accounts.seller.key(),
         buyer: ctx.accounts.buyer.key(),
-        amount: escrow_account.amount,
+        amount: release_amount,
+        fee,
+        royalty: royalty_paid,
         timestamp: Clock::get()?.unix_timestamp,
     });
 
@@ -134,47 +371,546 @@ pub fn process_escrow_release(ctx: Context<EscrowRelease>) -> Result<()> {
 }
 
 
-pub fn process_timeout(ctx: Context<TimeoutCheck>) -> Result<()> {
+pub fn process_release_milestone<'info>(ctx: Context<'_, '_, 'info, 'info, EscrowRelease<'info>>, index: u8) -> Result<()> {
     let escrow_account = &ctx.accounts.escrow;
+
+    require!(escrow_account.status == TransactionStatus::InTransit, ErrorCode::FundsReleaseNotAllowed);
+
+    let milestone = match escrow_account.milestones.get(index as usize) {
+        Some(milestone) => milestone,
+        None => return Err(ErrorCode::InvalidMilestoneIndex.into()),
+    };
+    require!(!milestone.released, ErrorCode::MilestoneAlreadyReleased);
+    // 里程碑截止时间过后买家仍未放款的，卖家可自行领取该里程碑款项
+    check_release_authority(ctx.accounts, milestone.deadline)?;
+    let amount = milestone.amount;
+
+    let order_id_bytes = escrow_account.order_id.to_le_bytes();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"order",
+        ctx.accounts.buyer.to_account_info().key.as_ref(),
+        order_id_bytes.as_ref(),
+        &[ctx.bumps.escrow]
+    ]];
+
+    let funds = EscrowFunds {
+        escrow: escrow_account,
+        escrow_vault: ctx.accounts.escrow_vault.as_ref(),
+        mint: ctx.accounts.mint.as_ref(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        hook_accounts: ctx.remaining_accounts,
+        signer_seeds
+    };
+
+    // 扣除协议手续费后支付卖家
+    let fee_bps = resolve_fee_bps(&ctx.accounts.config, &ctx.accounts.mint_fee.to_account_info())?;
+    let fee = calculate_fee(amount, fee_bps);
+
+    let (seller_destination, treasury_destination) = settlement_destinations(ctx.accounts)?;
+
+    funds.pay(seller_destination, amount - fee)?;
+
+    if fee > 0 {
+        funds.pay(treasury_destination, fee)?;
+    }
+
+    msg!("里程碑放款完毕");
+
+    let escrow_account = &mut ctx.accounts.escrow;
+    escrow_account.milestones[index as usize].released = true;
+    escrow_account.released_amount += amount;
+
+    // 全部里程碑放款后交易完成
+    if escrow_account.released_amount == escrow_account.amount {
+        escrow_account.status = escrow_account.status.transition(TransactionStatus::Success)?;
+    }
+
+    emit!(MilestoneReleased {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        seller: ctx.accounts.seller.key(),
+        index,
+        amount,
+        fee,
+        released_amount: escrow_account.released_amount,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+
+pub fn process_timeout<'info>(ctx: Context<'_, '_, 'info, 'info, TimeoutCheck<'info>>) -> Result<()> {
     let clock = Clock::get()?;
 
-    // 订单超出处理
-    if escrow_account.expiration <= clock.unix_timestamp {
-        if escrow_account.status == TransactionStatus::Funded as u8 {
-            let signer_seeds: &[&[&[u8]]] = &[&[
-                b"order",
-                ctx.accounts.buyer.to_account_info().key.as_ref(),
-                &[ctx.bumps.escrow]
-            ]];
-    
-            let cpi_accounts = TransferChecked {
-                from: ctx.accounts.escrow_vault.to_account_info(),
-                to: ctx.accounts.buyer_token_account.to_account_info(),
-                mint: ctx.accounts.mint.to_account_info(),
-                authority: ctx.accounts.escrow.to_account_info()
+    let refunded_amount = ctx.accounts.expire(ctx.remaining_accounts, ctx.bumps.escrow, clock.unix_timestamp)?;
+
+    let escrow_account = &ctx.accounts.escrow;
+    emit!(OrderTimedOut {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        buyer: ctx.accounts.buyer.key(),
+        refunded_amount,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+
+pub fn process_crank_expired<'info>(ctx: Context<'_, '_, 'info, 'info, CrankExpired<'info>>) -> Result<()> {
+    let clock = Clock::get()?;
+
+    // 超时处理尚未执行时先按订单状态处理超时，已完成或已取消的订单由买家自行关闭
+    let refunded_amount = if ctx.accounts.timeout.escrow.status == TransactionStatus::Expired {
+        0
+    } else {
+        ctx.accounts.timeout.expire(ctx.remaining_accounts, ctx.bumps.timeout.escrow, clock.unix_timestamp)?
+    };
+    // 卖家已确认的订单超时后转入争议，不能清理
+    require!(ctx.accounts.timeout.escrow.status == TransactionStatus::Expired, ErrorCode::OrderInTransit);
+
+    let accounts = &ctx.accounts.timeout;
+    let escrow_account = &accounts.escrow;
+
+    let order_id_bytes = escrow_account.order_id.to_le_bytes();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"order",
+        accounts.buyer.to_account_info().key.as_ref(),
+        order_id_bytes.as_ref(),
+        &[ctx.bumps.timeout.escrow]
+    ]];
+
+    // 关闭托管代币账户，租金退还买家
+    if !escrow_account.is_native {
+        let escrow_vault = match &accounts.escrow_vault {
+            Some(escrow_vault) => escrow_vault,
+            None => return Err(ErrorCode::MissingTokenAccount.into()),
+        };
+
+        let mint = match &accounts.mint {
+            Some(mint) => mint,
+            None => return Err(ErrorCode::MissingTokenAccount.into()),
+        };
+
+        let cpi_accounts = CloseAccount {
+            account: escrow_vault.to_account_info(),
+            destination: accounts.buyer.to_account_info(),
+            authority: escrow_account.to_account_info()
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds
+        );
+
+        close_escrow_vault(cpi_ctx, mint.to_account_info())?;
+    }
+
+    // 从订单账户租金中支付执行者奖励，剩余租金退还买家
+    let escrow_info = escrow_account.to_account_info();
+    let bounty = ctx.accounts.config.keeper_bounty.min(escrow_info.lamports());
+    if bounty > 0 {
+        escrow_info.sub_lamports(bounty)?;
+        ctx.accounts.keeper.add_lamports(bounty)?;
+    }
+    escrow_account.close(accounts.buyer.to_account_info())?;
+
+    msg!("超时订单已清理");
+
+    emit!(OrderCranked {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        keeper: ctx.accounts.keeper.key(),
+        buyer: accounts.buyer.key(),
+        refunded_amount,
+        bounty,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+
+impl<'info> TimeoutCheck<'info> {
+    // 按订单状态处理超时，返回实际退款金额
+    //   Created：买家尚未付款，无需退款，标记为 Expired
+    //   Funded：卖家尚未确认，全额退款给买家，标记为 Expired
+    //   InTransit：卖家已确认，不自动退款。设置了自动放款时间的订单由卖家到时领取款项，
+    //     买家在此之前仍可放款或发起争议；没有自动放款时间的订单转入争议由仲裁者裁决，没有仲裁者时拒绝处理
+    //   Success / Cancelled / Expired：订单已结束，拒绝处理
+    //   Disputed：只能由仲裁者处理
+    fn expire(&mut self, hook_accounts: &[AccountInfo<'info>], escrow_bump: u8, now: i64) -> Result<u64> {
+        let escrow_account = &self.escrow;
+        let status = escrow_account.status;
+        require!(status != TransactionStatus::Disputed, ErrorCode::OrderInDispute);
+        require!(!status.is_settled(), ErrorCode::OrderAlreadySettled);
+        require!(escrow_account.expiration <= now, ErrorCode::OrderNotExpired);
+
+        match status {
+            TransactionStatus::Created => {
+                self.escrow.status = self.escrow.status.transition(TransactionStatus::Expired)?;
+                Ok(0)
+            }
+            TransactionStatus::Funded => {
+                let refunded_amount = self.refund_expired(hook_accounts, escrow_bump)?;
+                self.escrow.status = self.escrow.status.transition(TransactionStatus::Expired)?;
+                Ok(refunded_amount)
+            }
+            TransactionStatus::InTransit => {
+                // 超时处理无需签名，不能替买卖双方发起争议而取消卖家的自行领取
+                if let Some(release_after) = escrow_account.release_after {
+                    require!(release_after > now, ErrorCode::SellerReleaseAvailable);
+                    return Err(ErrorCode::OrderInTransit.into());
+                }
+                require!(escrow_account.arbitrator.is_some(), ErrorCode::OrderInTransit);
+
+                self.escrow.status = self.escrow.status.transition(TransactionStatus::Disputed)?;
+                Ok(0)
+            }
+            _ => Err(ErrorCode::InvalidStatusTransition.into()),
+        }
+    }
+
+    // 超时退款：未放款的金额退还买家，卖家已托管的NFT一并退还，返回退款金额
+    // 只有卖家确认前的订单会超时退款，此时通常还没有NFT托管账户
+    fn refund_expired(&self, hook_accounts: &[AccountInfo<'info>], escrow_bump: u8) -> Result<u64> {
+        let escrow_account = &self.escrow;
+
+        let order_id_bytes = escrow_account.order_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"order",
+            self.buyer.to_account_info().key.as_ref(),
+            order_id_bytes.as_ref(),
+            &[escrow_bump]
+        ]];
+
+        let funds = EscrowFunds {
+            escrow: escrow_account,
+            escrow_vault: self.escrow_vault.as_ref(),
+            mint: self.mint.as_ref(),
+            token_program: self.token_program.to_account_info(),
+            hook_accounts,
+            signer_seeds
+        };
+
+        let refund_destination = if escrow_account.is_native {
+            self.buyer.to_account_info()
+        } else {
+            match &self.buyer_token_account {
+                Some(buyer_token_account) => buyer_token_account.to_account_info(),
+                None => return Err(ErrorCode::MissingTokenAccount.into()),
+            }
+        };
+
+        let refunded_amount = escrow_account.unreleased_amount();
+        funds.pay(refund_destination, refunded_amount)?;
+
+        // 卖家已托管NFT时一并退还
+        if let Some(expected_nft_vault) = escrow_account.nft_vault {
+            let nft_vault = match &self.nft_vault {
+                Some(nft_vault) => nft_vault,
+                None => return Err(ErrorCode::MissingNftVault.into())
+            };
+            require!(nft_vault.key() == expected_nft_vault, ErrorCode::InvalidNftVault);
+
+            let nft_mint = match &self.nft_mint {
+                Some(nft_mint) => nft_mint,
+                None => return Err(ErrorCode::MissingNftMint.into())
             };
-    
-            let cpi_ctx = CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(), 
-                cpi_accounts, 
+            require!(escrow_account.nft_mint == Some(nft_mint.key()), ErrorCode::InvalidNftAccount);
+
+            let seller = match &self.seller {
+                Some(seller) => seller,
+                None => return Err(ErrorCode::InvalidSeller.into())
+            };
+            require!(escrow_account.seller == Some(seller.key()), ErrorCode::InvalidSeller);
+
+            let seller_nft_account = match &self.seller_nft_account {
+                Some(seller_nft_account) => seller_nft_account,
+                None => return Err(ErrorCode::MissingNftAccount.into())
+            };
+            require!(seller_nft_account.owner == seller.key(), ErrorCode::InvalidNftOwner);
+            require!(seller_nft_account.mint == nft_mint.key(), ErrorCode::InvalidNftAccount);
+
+            let nft_token_program = match &self.nft_token_program {
+                Some(nft_token_program) => nft_token_program,
+                None => return Err(ErrorCode::InvalidNftTokenProgram.into())
+            };
+
+            withdraw_nft_vault(
+                escrow_account.to_account_info(),
+                nft_vault.to_account_info(),
+                nft_mint.to_account_info(),
+                seller_nft_account.to_account_info(),
+                seller.to_account_info(),
+                nft_token_program.to_account_info(),
                 signer_seeds
+            )?;
+        }
+
+        msg!("订单超时退换资金给买家");
+
+        Ok(refunded_amount)
+    }
+}
+
+
+pub fn process_close_order(ctx: Context<CloseOrder>) -> Result<()> {
+    let escrow_account = &ctx.accounts.escrow;
+
+    // 只有已结束的订单才能关闭
+    require!(escrow_account.status.is_settled(), ErrorCode::OrderNotSettled);
+    // SOL订单的资金存放在托管PDA中，随订单账户一并关闭
+    if !escrow_account.is_native {
+        let escrow_vault = match &ctx.accounts.escrow_vault {
+            Some(escrow_vault) => escrow_vault,
+            None => return Err(ErrorCode::MissingTokenAccount.into()),
+        };
+        // 托管账户必须已清空
+        require!(escrow_vault.amount == 0, ErrorCode::VaultNotEmpty);
+        let mint = match &ctx.accounts.mint {
+            Some(mint) => mint,
+            None => return Err(ErrorCode::MissingTokenAccount.into()),
+        };
+
+        let order_id_bytes = escrow_account.order_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"order",
+            ctx.accounts.buyer.to_account_info().key.as_ref(),
+            order_id_bytes.as_ref(),
+            &[ctx.bumps.escrow]
+        ]];
+
+        // 关闭托管代币账户，租金退还买家
+        let cpi_accounts = CloseAccount {
+            account: escrow_vault.to_account_info(),
+            destination: ctx.accounts.buyer.to_account_info(),
+            authority: ctx.accounts.escrow.to_account_info()
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(), 
+            cpi_accounts, 
+            signer_seeds
+        );
+
+        close_escrow_vault(cpi_ctx, mint.to_account_info())?;
+    }
+
+    msg!("订单账户已关闭，租金退还买家");
+
+    let escrow_account = &ctx.accounts.escrow;
+    emit!(OrderClosed {
+        escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
+        buyer: ctx.accounts.buyer.key(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+
+// 卖家只能在自动放款时间或里程碑截止时间到达后自行领取，争议中的订单不会处于InTransit状态
+fn check_release_authority(accounts: &EscrowRelease, deadline: Option<i64>) -> Result<()> {
+    let authority = accounts.authority.key();
+    let escrow = &accounts.escrow;
+    if authority == escrow.buyer || escrow.arbitrator == Some(authority) {
+        return Ok(());
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    let release_ready = escrow.release_after.is_some_and(|release_after| release_after <= now);
+    let deadline_passed = deadline.is_some_and(|deadline| deadline <= now);
+    require!(release_ready || deadline_passed, ErrorCode::ReleaseNotReady);
+
+    Ok(())
+}
+
+
+// 放款收款方：SOL订单为卖家与手续费收款方账户，代币订单为对应的代币账户
+fn settlement_destinations<'info>(accounts: &EscrowRelease<'info>) -> Result<(AccountInfo<'info>, AccountInfo<'info>)> {
+    if accounts.escrow.is_native {
+        let treasury = match &accounts.treasury {
+            Some(treasury) => treasury,
+            None => return Err(ErrorCode::InvalidTreasury.into()),
+        };
+        return Ok((accounts.seller.to_account_info(), treasury.to_account_info()));
+    }
+
+    match (&accounts.seller_token_account, &accounts.treasury_token_account) {
+        (Some(seller_token_account), Some(treasury_token_account)) => Ok((
+            seller_token_account.to_account_info(),
+            treasury_token_account.to_account_info()
+        )),
+        _ => Err(ErrorCode::MissingTokenAccount.into()),
+    }
+}
+
+
+// 版税收款账户按记录顺序位于remaining_accounts开头，其后的账户为转账钩子的额外账户
+pub fn split_creator_accounts<'a, 'info>(
+    escrow: &Escrow,
+    royalty_total: u64,
+    remaining_accounts: &'a [AccountInfo<'info>]
+) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+    let creator_count = if royalty_total > 0 { escrow.royalty_creators.len() } else { 0 };
+    require!(remaining_accounts.len() >= creator_count, ErrorCode::MissingCreatorAccount);
+    Ok(remaining_accounts.split_at(creator_count))
+}
+
+
+// 按份额向已验证的创作者支付版税，返回实际支付的总额
+// 代币订单传入创作者的代币账户，SOL订单直接传入创作者地址
+pub fn pay_royalties<'info>(
+    escrow: &Escrow,
+    royalty_total: u64,
+    creator_accounts: &'info [AccountInfo<'info>],
+    mut pay: impl FnMut(AccountInfo<'info>, u64) -> Result<()>
+) -> Result<u64> {
+    let mut royalty_paid = 0u64;
+    for (creator, creator_info) in escrow.royalty_creators.iter().zip(creator_accounts.iter()) {
+        if escrow.is_native {
+            require!(creator_info.key() == creator.address, ErrorCode::InvalidCreatorAccount);
+        } else {
+            let creator_token_account = InterfaceAccount::<TokenAccount>::try_from(creator_info)?;
+            require!(creator_token_account.owner == creator.address, ErrorCode::InvalidCreatorAccount);
+            require!(creator_token_account.mint == escrow.token_mint, ErrorCode::InvalidCreatorAccount);
+        }
+
+        let royalty = (royalty_total as u128 * creator.share as u128 / 100) as u64;
+        pay(creator_info.clone(), royalty)?;
+        royalty_paid += royalty;
+    }
+
+    Ok(royalty_paid)
+}
+
+
+/// 托管资金来源：SOL订单为托管PDA本身，代币订单为托管代币账户
+pub struct EscrowFunds<'a, 'info> {
+    pub escrow: &'a Account<'info, Escrow>,
+    pub escrow_vault: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    pub mint: Option<&'a InterfaceAccount<'info, Mint>>,
+    pub token_program: AccountInfo<'info>,
+    // 转账钩子所需的额外账户
+    pub hook_accounts: &'a [AccountInfo<'info>],
+    pub signer_seeds: &'a [&'a [&'a [u8]]]
+}
+
+impl<'info> EscrowFunds<'_, 'info> {
+    // 从托管资金中付款：SOL订单直接划转lamports并保留免租金额，代币订单从托管代币账户转出
+    pub fn pay(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        if self.escrow.is_native {
+            let escrow_info = self.escrow.to_account_info();
+            let rent_exempt_minimum = Rent::get()?.minimum_balance(escrow_info.data_len());
+            let remaining = escrow_info.lamports().checked_sub(amount);
+            require!(
+                remaining.is_some_and(|remaining| remaining >= rent_exempt_minimum),
+                ErrorCode::InsufficientEscrowBalance
             );
-    
-            transfer_checked(cpi_ctx, escrow_account.amount, ctx.accounts.mint.decimals)?;
 
-            msg!("订单超时退换资金给买家");
+            escrow_info.sub_lamports(amount)?;
+            to.add_lamports(amount)?;
+            return Ok(());
+        }
+
+        let (escrow_vault, mint) = match (self.escrow_vault, self.mint) {
+            (Some(escrow_vault), Some(mint)) => (escrow_vault, mint),
+            _ => return Err(ErrorCode::MissingTokenAccount.into()),
+        };
+
+        let cpi_accounts = TransferChecked {
+            from: escrow_vault.to_account_info(),
+            to,
+            mint: mint.to_account_info(),
+            authority: self.escrow.to_account_info()
         };
 
-        let escrow_account = &mut ctx.accounts.escrow;
-        escrow_account.status = TransactionStatus::Expired as u8;
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.clone(),
+            cpi_accounts,
+            self.signer_seeds
+        ).with_remaining_accounts(self.hook_accounts.to_vec());
 
-        emit!(OrderTimedOut {
-            escrow: escrow_account.key(),
-            buyer: ctx.accounts.buyer.key(),
-            refunded_amount: escrow_account.amount,
-            timestamp: Clock::get()?.unix_timestamp,
+        transfer_checked_with_hook(cpi_ctx, amount, mint.decimals)
+    }
+}
+
+
+// 带转账钩子额外账户的transfer_checked，额外账户通过CpiContext的remaining_accounts传入
+pub fn transfer_checked_with_hook<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>,
+    amount: u64,
+    decimals: u8
+) -> Result<()> {
+    let mut ix = spl_token_2022::instruction::transfer_checked(
+        ctx.program.key,
+        ctx.accounts.from.key,
+        ctx.accounts.mint.key,
+        ctx.accounts.to.key,
+        ctx.accounts.authority.key,
+        &[],
+        amount,
+        decimals
+    )?;
+
+    let mut account_infos = vec![
+        ctx.accounts.from,
+        ctx.accounts.mint,
+        ctx.accounts.to,
+        ctx.accounts.authority
+    ];
+    for account in ctx.remaining_accounts {
+        ix.accounts.push(AccountMeta {
+            pubkey: account.key(),
+            is_signer: account.is_signer,
+            is_writable: account.is_writable
         });
+        account_infos.push(account);
     }
 
-    Ok(())
+    invoke_signed(&ix, &account_infos, ctx.signer_seeds).map_err(Into::into)
+}
+
+
+// 关闭托管代币账户：带转账手续费的代币账户中留有预扣手续费时无法关闭，先将其归集到mint
+pub fn close_escrow_vault<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, CloseAccount<'info>>,
+    mint: AccountInfo<'info>
+) -> Result<()> {
+    if withheld_transfer_fee(&ctx.accounts.account)? > 0 {
+        let ix = spl_token_2022::extension::transfer_fee::instruction::harvest_withheld_tokens_to_mint(
+            ctx.program.key,
+            mint.key,
+            &[ctx.accounts.account.key]
+        )?;
+        invoke(&ix, &[mint, ctx.accounts.account.clone()])?;
+    }
+
+    close_account(ctx)
+}
+
+
+// 代币账户中预扣的转账手续费，非Token-2022账户或没有转账手续费扩展时为0
+fn withheld_transfer_fee(token_account: &AccountInfo) -> Result<u64> {
+    if *token_account.owner != Token2022::id() {
+        return Ok(0);
+    }
+
+    let data = token_account.try_borrow_data()?;
+    let state = StateWithExtensions::<TokenAccountState>::unpack(&data)?;
+    Ok(state
+        .get_extension::<TransferFeeAmount>()
+        .map_or(0, |transfer_fee_amount| u64::from(transfer_fee_amount.withheld_amount)))
 }
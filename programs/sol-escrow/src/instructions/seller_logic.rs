@@ -1,17 +1,28 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+use anchor_spl::{
+    associated_token::{create_idempotent, get_associated_token_address_with_program_id, AssociatedToken, Create},
+    metadata::MetadataAccount,
+    token_interface::{close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked}
+};
 
-use crate::{error::ErrorCode, state::{Escrow, TransactionStatus}};
+use crate::{
+    constants::{BASIS_POINTS_DENOMINATOR, MAX_FEE_BPS},
+    error::ErrorCode,
+    state::{Escrow, RoyaltyCreator, TransactionStatus}
+};
 
 #[event]
 pub struct SellerConfirmed {
     pub escrow: Pubkey,
+    pub mint: Pubkey,
+    pub status: TransactionStatus,
     pub seller: Pubkey,
     pub buyer: Pubkey,
     pub timestamp: i64,
 }
 
 #[derive(Accounts)]
+#[instruction(order_id: u64)]
 pub struct SellerConfirmation<'info> {
     #[account(mut)]
     pub seller: Signer<'info>,
@@ -20,7 +31,7 @@ pub struct SellerConfirmation<'info> {
 
     #[account(
         mut,
-        seeds = [b"order", buyer.key().as_ref()],
+        seeds = [b"order", buyer.key().as_ref(), order_id.to_le_bytes().as_ref()],
         bump
     )]
     pub escrow: Account<'info, Escrow>,
@@ -31,17 +42,34 @@ pub struct SellerConfirmation<'info> {
     #[account(mut)]
     pub buyer_nft_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
+    // NFT元数据账户，NFT订单需要提供
+    pub nft_metadata: Option<Account<'info, MetadataAccount>>,
+
+    /// CHECK: 托管账户的NFT关联代币账户，地址在指令中校验，不存在时由卖家付费创建
+    #[account(mut)]
+    pub nft_vault: Option<UncheckedAccount<'info>>,
+
     pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>
 }
 
 
-pub fn process_seller_confirmation(ctx: Context<SellerConfirmation>) -> Result<()> {
+pub fn process_seller_confirmation(ctx: Context<SellerConfirmation>, expected_amount: u64, expected_expiration: i64) -> Result<()> {
     let escrow_account = &mut ctx.accounts.escrow;
     let clock = Clock::get()?;
 
-    require!(escrow_account.expiration > clock.unix_timestamp, ErrorCode::ExpirationTooFar);
-    require!(escrow_account.status == TransactionStatus::Funded as u8, ErrorCode::SellerConfirmationNotAllowed);
+    require!(escrow_account.expiration > clock.unix_timestamp, ErrorCode::OrderExpired);
+    require!(escrow_account.status == TransactionStatus::Funded, ErrorCode::SellerConfirmationNotAllowed);
+    // 指定卖家的订单只能由该卖家确认
+    if !escrow_account.open_offer {
+        require!(escrow_account.seller == Some(ctx.accounts.seller.key()), ErrorCode::InvalidSeller);
+    }
+    // 买家在确认前可以修改订单，只确认卖家签名时看到的条款
+    require!(
+        escrow_account.amount == expected_amount && escrow_account.expiration == expected_expiration,
+        ErrorCode::OrderTermsChanged
+    );
 
     if escrow_account.is_nft {
         // 获取seller_nft_account，没有报错
@@ -50,18 +78,43 @@ pub fn process_seller_confirmation(ctx: Context<SellerConfirmation>) -> Result<(
             None => return Err(ErrorCode::MissingNftAccount.into()),
         };
 
-        let nft_mint = escrow_account.nft_mint.unwrap();
-        require!(seller_nft_account.mint == nft_mint, ErrorCode::InvalidNftAccount);
+        let metadata = match &ctx.accounts.nft_metadata {
+            Some(metadata) => metadata,
+            None => return Err(ErrorCode::MissingMetadata.into()),
+        };
+        require!(metadata.mint == seller_nft_account.mint, ErrorCode::InvalidMetadata);
+
+        match escrow_account.collection_mint {
+            // 集合订单：接受该集合内任意已验证的NFT
+            Some(collection_mint) => {
+                let collection = match &metadata.collection {
+                    Some(collection) => collection,
+                    None => return Err(ErrorCode::InvalidMetadata.into()),
+                };
+                require!(collection.key == collection_mint, ErrorCode::CollectionMismatch);
+                require!(collection.verified, ErrorCode::UnverifiedCollection);
+            }
+            None => {
+                let nft_mint = escrow_account.nft_mint.unwrap();
+                require!(seller_nft_account.mint == nft_mint, ErrorCode::InvalidNftAccount);
+            }
+        }
+
+        // 记录版税信息，放款时按份额支付给已验证的创作者
+        record_royalties(escrow_account, metadata)?;
         
         // 验证卖家是否拥有此nft，nft是否有效
         require!(seller_nft_account.owner == ctx.accounts.seller.key(), ErrorCode::InvalidNftOwner);
         require!(seller_nft_account.amount == 1, ErrorCode::InvalidNftAmount);
 
-        // 将nft所有权转交给买家
+        // 将nft存入托管账户，买家放款时再交付
         let nft_mint = match &ctx.accounts.nft_mint {
             Some(nft_mint) => nft_mint,
             None => return Err(ErrorCode::MissingNftMint.into())
         };
+        require!(nft_mint.key() == seller_nft_account.mint, ErrorCode::InvalidNftAccount);
+        // NFT托管账户按NFT所属的代币程序创建
+        require!(ctx.accounts.token_program.key() == *nft_mint.to_account_info().owner, ErrorCode::InvalidNftTokenProgram);
 
         let buyer_nft_account = match &ctx.accounts.buyer_nft_account {
             Some(buyer_nft_account) => buyer_nft_account,
@@ -69,10 +122,39 @@ pub fn process_seller_confirmation(ctx: Context<SellerConfirmation>) -> Result<(
         };
 
         require!(buyer_nft_account.owner == ctx.accounts.buyer.key(), ErrorCode::InvalidNftOwner);
+        require!(buyer_nft_account.mint == nft_mint.key(), ErrorCode::InvalidNftAccount);
+
+        let nft_vault = match &ctx.accounts.nft_vault {
+            Some(nft_vault) => nft_vault,
+            None => return Err(ErrorCode::MissingNftVault.into())
+        };
+
+        let expected_nft_vault = get_associated_token_address_with_program_id(
+            &escrow_account.key(),
+            &nft_mint.key(),
+            &ctx.accounts.token_program.key()
+        );
+        require!(nft_vault.key() == expected_nft_vault, ErrorCode::InvalidNftVault);
+
+        let cpi_accounts = Create {
+            payer: ctx.accounts.seller.to_account_info(),
+            associated_token: nft_vault.to_account_info(),
+            authority: escrow_account.to_account_info(),
+            mint: nft_mint.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info()
+        };
+
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.associated_token_program.to_account_info(),
+            cpi_accounts
+        );
+
+        create_idempotent(cpi_ctx)?;
 
         let cpi_accounts = TransferChecked {
             from: seller_nft_account.to_account_info(),
-            to: buyer_nft_account.to_account_info(),
+            to: nft_vault.to_account_info(),
             mint: nft_mint.to_account_info(),
             authority: ctx.accounts.seller.to_account_info()
         };
@@ -83,13 +165,22 @@ pub fn process_seller_confirmation(ctx: Context<SellerConfirmation>) -> Result<(
         );
 
         transfer_checked(cpi_ctx, 1, 0)?;
+
+        // 记录实际交付的NFT
+        escrow_account.nft_mint = Some(nft_mint.key());
+        escrow_account.buyer_nft_account = Some(buyer_nft_account.key());
+        escrow_account.nft_vault = Some(nft_vault.key());
     }
 
     escrow_account.seller = Some(ctx.accounts.seller.key());
-    escrow_account.status = TransactionStatus::InTransit as u8;
+    // 买家在等待时间内未放款也未发起争议时，卖家可自行领取款项
+    escrow_account.release_after = escrow_account.release_delay.map(|release_delay| clock.unix_timestamp + release_delay);
+    escrow_account.status = escrow_account.status.transition(TransactionStatus::InTransit)?;
 
     emit!(SellerConfirmed {
         escrow: escrow_account.key(),
+        mint: escrow_account.token_mint,
+        status: escrow_account.status,
         seller: ctx.accounts.seller.key(),
         buyer: ctx.accounts.buyer.key(),
         timestamp: clock.unix_timestamp,
@@ -97,3 +188,75 @@ pub fn process_seller_confirmation(ctx: Context<SellerConfirmation>) -> Result<(
 
     Ok(())
 }
+
+
+// 从NFT元数据记录版税信息，只有已验证的创作者参与分成
+pub fn record_royalties(escrow: &mut Escrow, metadata: &MetadataAccount) -> Result<()> {
+    require!(metadata.seller_fee_basis_points <= BASIS_POINTS_DENOMINATOR, ErrorCode::InvalidMetadata);
+    // 手续费可能在成交前调高，版税加上最高手续费不超过成交金额，避免订单无法放款
+    require!(
+        metadata.seller_fee_basis_points + MAX_FEE_BPS <= BASIS_POINTS_DENOMINATOR,
+        ErrorCode::RoyaltyTooHigh
+    );
+    escrow.royalty_bps = metadata.seller_fee_basis_points;
+    escrow.royalty_creators = metadata
+        .creators
+        .as_ref()
+        .map(|creators| {
+            creators
+                .iter()
+                .filter(|creator| creator.verified)
+                .map(|creator| RoyaltyCreator {
+                    address: creator.address,
+                    share: creator.share
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(())
+}
+
+
+// 从NFT托管账户转出NFT并关闭托管账户，租金退还卖家，token_program为NFT所属的代币程序
+pub fn withdraw_nft_vault<'info>(
+    escrow: AccountInfo<'info>,
+    nft_vault: AccountInfo<'info>,
+    nft_mint: AccountInfo<'info>,
+    to: AccountInfo<'info>,
+    seller: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]]
+) -> Result<()> {
+    // NFT可能与计价代币属于不同的代币程序，托管账户按NFT所属的程序创建
+    require!(token_program.key() == *nft_mint.owner, ErrorCode::InvalidNftTokenProgram);
+
+    let cpi_accounts = TransferChecked {
+        from: nft_vault.clone(),
+        to,
+        mint: nft_mint,
+        authority: escrow.clone()
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.clone(),
+        cpi_accounts,
+        signer_seeds
+    );
+
+    transfer_checked(cpi_ctx, 1, 0)?;
+
+    let cpi_accounts = CloseAccount {
+        account: nft_vault,
+        destination: seller,
+        authority: escrow
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program,
+        cpi_accounts,
+        signer_seeds
+    );
+
+    close_account(cpi_ctx)
+}
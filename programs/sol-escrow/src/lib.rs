@@ -13,46 +13,160 @@ declare_id!("FeY4DZcAk56DpiEi7diUJi9wcwdrXkHdgBYu8ofRao8z");
 pub mod sol_escrow {
     use super::*;
 
-    // 创建订单
-    pub fn create_order(
-        ctx: Context<CreateOrder>, 
-        amount: u64, 
-        expiration: i64, 
-        nft_mint: Option<Pubkey>,
-        buyer_nft_account: Option<Pubkey>,
-        is_nft: bool
+    // 初始化协议配置
+    pub fn initialize_config(ctx: Context<InitializeConfig>, treasury: Pubkey, fee_bps: u16) -> Result<()> {
+        msg!("初始化协议配置");
+        process_initialize_config(ctx, treasury, fee_bps)
+    }
+
+    // 更新协议配置
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        new_admin: Option<Pubkey>,
+        treasury: Pubkey,
+        fee_bps: u16,
+        keeper_bounty: u64
     ) -> Result<()> {
+        msg!("更新协议配置");
+        process_update_config(ctx, new_admin, treasury, fee_bps, keeper_bounty)
+    }
+
+    // 设置单个代币的手续费
+    pub fn set_mint_fee(ctx: Context<SetMintFee>, fee_bps: u16) -> Result<()> {
+        msg!("设置代币手续费");
+        process_set_mint_fee(ctx, fee_bps)
+    }
+
+    // 创建订单
+    pub fn create_order(ctx: Context<CreateOrder>, order_id: u64, args: CreateOrderArgs) -> Result<()> {
         msg!("创建订单");
-        process_order(ctx, amount, expiration, nft_mint, buyer_nft_account, is_nft)
+        process_order(ctx, order_id, args)
+    }
+
+    // 创建订单并同时付款
+    pub fn create_and_fund_order<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CreateAndFundOrder<'info>>,
+        order_id: u64,
+        args: CreateOrderArgs
+    ) -> Result<()> {
+        msg!("创建订单并付款");
+        process_create_and_fund_order(ctx, order_id, args)
     }
 
     // 买家付款确认
-    pub fn buyer_payment(ctx: Context<BuyerPayment>) -> Result<()> {
+    pub fn buyer_payment<'info>(ctx: Context<'_, '_, 'info, 'info, BuyerPayment<'info>>, _order_id: u64) -> Result<()> {
         msg!("买家确认付款");
         process_buyer_payment(ctx)
     }
 
+    // 卖家确认前修改订单金额与超时时间
+    pub fn amend_order<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BuyerPayment<'info>>,
+        _order_id: u64,
+        amount: u64,
+        expiration: i64
+    ) -> Result<()> {
+        msg!("修改订单");
+        process_amend_order(ctx, amount, expiration)
+    }
+
+    // 卖家还价
+    pub fn propose_counter(ctx: Context<ProposeCounter>, _order_id: u64, amount: u64, expiration: i64) -> Result<()> {
+        msg!("卖家还价");
+        process_propose_counter(ctx, amount, expiration)
+    }
+
+    // 买家接受还价
+    pub fn accept_counter<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AcceptCounter<'info>>,
+        _order_id: u64,
+        expected_amount: u64,
+        expected_expiration: i64
+    ) -> Result<()> {
+        msg!("买家接受还价");
+        process_accept_counter(ctx, expected_amount, expected_expiration)
+    }
+
+    // 卖家撤回或买家拒绝还价，关闭还价账户
+    pub fn close_counter(ctx: Context<CloseCounter>, _order_id: u64) -> Result<()> {
+        msg!("关闭还价");
+        process_close_counter(ctx)
+    }
+
     // 买家取消订单
-    pub fn order_cancellation(ctx: Context<OrderCancellation>) -> Result<()> {
+    pub fn order_cancellation<'info>(ctx: Context<'_, '_, 'info, 'info, OrderCancellation<'info>>, _order_id: u64) -> Result<()> {
         msg!("买家取消订单");
         process_order_cancellation(ctx)
     }
 
     // 卖家确认
-    pub fn seller_confirmation(ctx: Context<SellerConfirmation>) -> Result<()> {
+    pub fn seller_confirmation(
+        ctx: Context<SellerConfirmation>,
+        _order_id: u64,
+        expected_amount: u64,
+        expected_expiration: i64
+    ) -> Result<()> {
         msg!("卖家确认");
-        process_seller_confirmation(ctx)
+        process_seller_confirmation(ctx, expected_amount, expected_expiration)
     }
 
     // 合约转账
-    pub fn escrow_release(ctx: Context<EscrowRelease>) -> Result<()> {
+    pub fn escrow_release<'info>(ctx: Context<'_, '_, 'info, 'info, EscrowRelease<'info>>, _order_id: u64) -> Result<()> {
         msg!("托管账户资金释放");
         process_escrow_release(ctx)
     }
 
+    // 按里程碑分期放款
+    pub fn release_milestone<'info>(ctx: Context<'_, '_, 'info, 'info, EscrowRelease<'info>>, _order_id: u64, index: u8) -> Result<()> {
+        msg!("里程碑放款");
+        process_release_milestone(ctx, index)
+    }
+
     // 超时处理
-    pub fn timeou_check(ctx: Context<TimeoutCheck>) -> Result<()> {
+    pub fn timeout_check<'info>(ctx: Context<'_, '_, 'info, 'info, TimeoutCheck<'info>>, _order_id: u64) -> Result<()> {
         msg!("超时判断");
         process_timeout(ctx)
     }
+
+    // 任何人都可以清理超时订单，退款并关闭账户后获得奖励
+    pub fn crank_expired<'info>(ctx: Context<'_, '_, 'info, 'info, CrankExpired<'info>>, _order_id: u64) -> Result<()> {
+        msg!("清理超时订单");
+        process_crank_expired(ctx)
+    }
+
+    // 关闭已结束的订单，回收租金
+    pub fn close_order(ctx: Context<CloseOrder>, _order_id: u64) -> Result<()> {
+        msg!("关闭订单");
+        process_close_order(ctx)
+    }
+
+    // 买家或卖家发起争议
+    pub fn open_dispute(ctx: Context<OpenDispute>, _order_id: u64) -> Result<()> {
+        msg!("发起争议");
+        process_open_dispute(ctx)
+    }
+
+    // 仲裁者裁决争议
+    pub fn resolve_dispute<'info>(ctx: Context<'_, '_, 'info, 'info, ResolveDispute<'info>>, _order_id: u64, seller_bps: u16) -> Result<()> {
+        msg!("仲裁争议");
+        process_resolve_dispute(ctx, seller_bps)
+    }
+
+    // 卖家创建挂单
+    pub fn create_listing<'info>(ctx: Context<'_, '_, 'info, 'info, CreateListing<'info>>, listing_id: u64, args: CreateListingArgs) -> Result<()> {
+        msg!("创建挂单");
+        process_create_listing(ctx, listing_id, args)
+    }
+
+    // 买家购买挂单
+    pub fn fill_listing<'info>(ctx: Context<'_, '_, 'info, 'info, FillListing<'info>>, _listing_id: u64) -> Result<()> {
+        msg!("买家购买挂单");
+        process_fill_listing(ctx)
+    }
+
+    // 卖家取消挂单
+    pub fn cancel_listing<'info>(ctx: Context<'_, '_, 'info, 'info, CancelListing<'info>>, _listing_id: u64) -> Result<()> {
+        msg!("卖家取消挂单");
+        process_cancel_listing(ctx)
+    }
 }
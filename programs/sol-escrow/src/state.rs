@@ -1,8 +1,13 @@
 use anchor_lang::prelude::*;
 
+use crate::{
+    constants::{MAX_CREATORS, MAX_MILESTONES},
+    error::ErrorCode
+};
+
 
 #[repr(u8)]
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
 pub enum TransactionStatus {
      /// 交易刚创建，等待买家存款
     Created,
@@ -21,18 +26,56 @@ pub enum TransactionStatus {
 }
 
 impl TransactionStatus {
-    pub fn from_u8(value: u8) -> Option<Self> {
-        match value {
-            0 => Some(TransactionStatus::Created),
-            1 => Some(TransactionStatus::Funded),
-            2 => Some(TransactionStatus::InTransit),
-            3 => Some(TransactionStatus::Success),
-            4 => Some(TransactionStatus::Cancelled),
-            5 => Some(TransactionStatus::Disputed),
-            6 => Some(TransactionStatus::Expired),
-            _ => None
-        }
+    // 订单是否已结束：完成、取消或超时
+    pub fn is_settled(self) -> bool {
+        matches!(self, TransactionStatus::Success | TransactionStatus::Cancelled | TransactionStatus::Expired)
     }
+
+    // 状态流转表，所有修改订单状态的指令都通过这里校验，返回目标状态
+    //   Created   -> Funded（买家付款）、Cancelled（取消）、Expired（超时）
+    //   Funded    -> InTransit（卖家确认）、Cancelled（取消）、Disputed（发起争议）、Expired（超时）
+    //   InTransit -> Success（放款）、Disputed（发起争议）、Expired（超时）
+    //   Disputed  -> Success、Cancelled（仲裁结果）
+    //   Success / Cancelled / Expired 为终态
+    // 挂单成交由 transition_fill 单独校验
+    pub fn transition(self, to: TransactionStatus) -> Result<TransactionStatus> {
+        use TransactionStatus::*;
+
+        let allowed = matches!(
+            (self, to),
+            (Created, Funded) | (Created, Cancelled) | (Created, Expired)
+                | (Funded, InTransit) | (Funded, Cancelled) | (Funded, Disputed) | (Funded, Expired)
+                | (InTransit, Success) | (InTransit, Disputed) | (InTransit, Expired)
+                | (Disputed, Success) | (Disputed, Cancelled)
+        );
+        require!(allowed, ErrorCode::InvalidStatusTransition);
+
+        Ok(to)
+    }
+
+    // 挂单成交：只有挂单可以不经付款从 Created 直接进入 Success
+    pub fn transition_fill(self, is_listing: bool) -> Result<TransactionStatus> {
+        require!(is_listing && self == TransactionStatus::Created, ErrorCode::InvalidStatusTransition);
+
+        Ok(TransactionStatus::Success)
+    }
+}
+
+
+/// 订单里程碑
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct Milestone {
+    pub amount: u64,  // 里程碑金额
+    pub deadline: Option<i64>,  // 里程碑截止时间，过后买家仍未放款时卖家可自行领取
+    pub released: bool  // 是否已放款
+}
+
+
+/// NFT版税收款创作者
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct RoyaltyCreator {
+    pub address: Pubkey,  // 创作者地址
+    pub share: u8  // 版税份额，所有创作者之和为100
 }
 
 
@@ -40,20 +83,143 @@ impl TransactionStatus {
 #[derive(InitSpace)]
 pub struct Escrow {
     pub buyer: Pubkey,  // 买家
+    pub order_id: u64,  // 订单编号，买家自定义，参与PDA派生
     pub seller: Option<Pubkey>,  // 卖家
     pub token_mint: Pubkey,  // 交易的spl代币
     pub buyer_nft_account: Option<Pubkey>, // 买家nft账户
-    pub nft_mint: Option<Pubkey>, // 购买的NFT的mint地址
+    pub nft_mint: Option<Pubkey>, // 购买的NFT的mint地址，挂单时为出售资产的mint地址
+    pub collection_mint: Option<Pubkey>, // 购买的NFT所属集合的mint地址
+    pub nft_vault: Option<Pubkey>, // NFT托管账户，卖家确认后持有NFT直至结算
     pub amount: u64,  // 交易金额
-    pub escrow_vault: Pubkey,  // 资金托管账户
+    pub escrow_vault: Pubkey,  // 资金托管账户，挂单时托管出售资产
     pub is_nft: bool, // 是否是nft交易
     pub expiration: i64,  // 交易超时时间
-    pub status: u8,  // 交易状态
-    pub arbitrator: Option<Pubkey>  // 仲裁者
+    pub status: TransactionStatus,  // 交易状态
+    pub arbitrator: Option<Pubkey>,  // 仲裁者
+    pub open_offer: bool,  // 是否允许任意卖家确认
+    pub is_listing: bool,  // 是否是卖家挂单
+    pub asset_amount: u64,  // 挂单资产数量
+    #[max_len(MAX_MILESTONES)]
+    pub milestones: Vec<Milestone>,  // 分期放款里程碑，为空时一次性放款
+    pub released_amount: u64,  // 已放款给卖家的金额
+    pub royalty_bps: u16,  // NFT版税，单位：基点
+    #[max_len(MAX_CREATORS)]
+    pub royalty_creators: Vec<RoyaltyCreator>,  // 已验证的版税创作者
+    pub is_native: bool,  // 是否以原生SOL计价，资金直接存放在托管PDA中
+    pub release_delay: Option<i64>,  // 卖家确认后的自动放款等待时间
+    pub release_after: Option<i64>  // 卖家可自行领取款项的时间，卖家确认时设置
 }
 
 impl Escrow {
-    pub fn get_transaction_status(&self) -> Option<TransactionStatus> {
-        TransactionStatus::from_u8(self.status)
+    // 尚未放款的托管金额
+    pub fn unreleased_amount(&self) -> u64 {
+        self.amount - self.released_amount
+    }
+}
+
+
+/// 全局协议配置
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    pub admin: Pubkey,  // 管理员
+    pub treasury: Pubkey,  // 手续费收款地址
+    pub fee_bps: u16,  // 默认手续费，单位：基点
+    pub bump: u8,
+    pub keeper_bounty: u64  // 清理超时订单的执行者奖励，单位：lamports
+}
+
+
+/// 单个代币的手续费配置，覆盖全局默认值
+#[account]
+#[derive(InitSpace)]
+pub struct MintFeeConfig {
+    pub mint: Pubkey,  // 代币mint地址
+    pub fee_bps: u16,  // 手续费，单位：基点
+    pub bump: u8
+}
+
+
+/// 卖家对订单的还价，每个卖家一个
+#[account]
+#[derive(InitSpace)]
+pub struct CounterOffer {
+    pub escrow: Pubkey,  // 订单托管账户
+    pub seller: Pubkey,  // 还价卖家
+    pub amount: u64,  // 还价金额
+    pub expiration: i64,  // 还价后的订单超时时间
+    pub bump: u8
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::TransactionStatus::{self, *};
+
+    const ALL_STATUSES: [TransactionStatus; 7] = [Created, Funded, InTransit, Success, Cancelled, Disputed, Expired];
+
+    // 允许的状态流转，与 TransactionStatus::transition 中的流转表一一对应
+    const ALLOWED_TRANSITIONS: [(TransactionStatus, TransactionStatus); 12] = [
+        (Created, Funded),
+        (Created, Cancelled),
+        (Created, Expired),
+        (Funded, InTransit),
+        (Funded, Cancelled),
+        (Funded, Disputed),
+        (Funded, Expired),
+        (InTransit, Success),
+        (InTransit, Disputed),
+        (InTransit, Expired),
+        (Disputed, Success),
+        (Disputed, Cancelled),
+    ];
+
+    #[test]
+    fn transition_table_is_exhaustive() {
+        for from in ALL_STATUSES {
+            for to in ALL_STATUSES {
+                let result = from.transition(to);
+                if ALLOWED_TRANSITIONS.contains(&(from, to)) {
+                    assert_eq!(result.unwrap(), to, "{from:?} -> {to:?} 应当允许");
+                } else {
+                    assert!(result.is_err(), "{from:?} -> {to:?} 应当拒绝");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn illegal_transition_returns_dedicated_error() {
+        let err = Success.transition(Funded).unwrap_err();
+        assert_eq!(err, crate::error::ErrorCode::InvalidStatusTransition.into());
+    }
+
+    #[test]
+    fn only_listings_fill_from_created() {
+        // 普通订单必须先付款，不能从 Created 直接完成
+        assert!(Created.transition(Success).is_err());
+        assert!(Created.transition_fill(false).is_err());
+        assert_eq!(Created.transition_fill(true).unwrap(), Success);
+
+        for from in ALL_STATUSES.into_iter().filter(|status| *status != Created) {
+            assert!(from.transition_fill(true).is_err(), "{from:?} 的挂单不能成交");
+        }
+    }
+
+    #[test]
+    fn settled_statuses_are_terminal() {
+        for from in ALL_STATUSES {
+            let has_exit = ALL_STATUSES.iter().any(|to| from.transition(*to).is_ok());
+            assert_eq!(from.is_settled(), !has_exit, "{from:?} 的终态判断不一致");
+        }
+    }
+
+    #[test]
+    fn status_serializes_as_single_byte() {
+        // 与原先 u8 存储的账户布局保持一致
+        for (index, status) in ALL_STATUSES.iter().enumerate() {
+            let bytes = anchor_lang::AnchorSerialize::try_to_vec(status).unwrap();
+            assert_eq!(bytes, vec![index as u8]);
+        }
     }
 }
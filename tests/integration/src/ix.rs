@@ -0,0 +1,26 @@
+//! sol-escrow 指令构造，复用 `sol-escrow-client` 的构造函数
+
+use sol_escrow_client::CreateOrderArgs;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::DEFAULT_EXPIRATION;
+
+pub use sol_escrow_client::instructions::*;
+
+
+// 指定卖家、一次性放款的订单参数
+pub fn order_args(amount: u64, now: i64, seller: Option<Pubkey>) -> CreateOrderArgs {
+    CreateOrderArgs {
+        amount,
+        expiration: now + DEFAULT_EXPIRATION,
+        nft_mint: None,
+        collection_mint: None,
+        buyer_nft_account: None,
+        is_nft: false,
+        arbitrator: None,
+        open_offer: seller.is_none(),
+        seller,
+        milestones: Vec::new(),
+        release_delay: None,
+    }
+}
@@ -0,0 +1,388 @@
+//! sol-escrow 进程内集成测试工具
+//!
+//! 基于 LiteSVM 加载 `anchor build` 编译出的程序，无需本地验证节点即可运行。
+
+pub mod ix;
+
+use anchor_lang::AccountDeserialize;
+use anchor_spl::{
+    associated_token::spl_associated_token_account,
+    metadata::mpl_token_metadata::{
+        self,
+        accounts::Metadata,
+        types::{Collection, Creator, Key, TokenStandard},
+    },
+    token_2022::spl_token_2022::{
+        self,
+        extension::{transfer_fee::instruction::initialize_transfer_fee_config, BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+    },
+};
+use borsh::BorshSerialize;
+use litesvm::{types::TransactionResult, LiteSVM};
+use sol_escrow_client::{ErrorCode, Escrow};
+use solana_loader_v3_interface::state::UpgradeableLoaderState;
+pub use sol_escrow_client::{pda, Asset};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    instruction::{Instruction, InstructionError},
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+};
+
+// anchor build 的输出路径
+const PROGRAM_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../target/deploy/sol_escrow.so");
+
+// 测试账户的初始 SOL 余额
+const INITIAL_LAMPORTS: u64 = 100 * LAMPORTS_PER_SOL;
+
+// 默认协议手续费，单位：基点
+pub const DEFAULT_FEE_BPS: u16 = 100;
+
+// 默认订单超时时间，单位：秒
+pub const DEFAULT_EXPIRATION: i64 = 3600;
+
+// 买家初始代币余额
+pub const BUYER_BALANCE: u64 = 1_000_000;
+
+
+/// 断言交易因指定的程序错误失败
+pub fn assert_error(result: TransactionResult, error: ErrorCode) {
+    let failed = match result {
+        Ok(_) => panic!("交易应当因 {error:?} 失败"),
+        Err(failed) => failed,
+    };
+    assert_eq!(
+        failed.err,
+        TransactionError::InstructionError(0, InstructionError::Custom(u32::from(error))),
+        "{:#?}",
+        failed.meta.logs
+    );
+}
+
+/// 断言交易成功，失败时输出程序日志
+pub fn assert_ok(result: TransactionResult) {
+    if let Err(failed) = result {
+        panic!("交易失败: {:?}\n{:#?}", failed.err, failed.meta.logs);
+    }
+}
+
+
+/// 买卖双方与订单计价资产
+pub struct Market {
+    pub buyer: Keypair,
+    pub seller: Keypair,
+    pub asset: Asset,
+}
+
+
+/// 测试环境：加载程序、管理员与手续费收款账户
+pub struct TestEnv {
+    pub svm: LiteSVM,
+    pub admin: Keypair,
+    pub treasury: Keypair,
+}
+
+impl TestEnv {
+    // 以可升级程序部署 sol-escrow，管理员为升级权限持有者
+    pub fn new() -> Self {
+        let mut svm = LiteSVM::new();
+        let admin = Keypair::new();
+        let treasury = Keypair::new();
+
+        let elf = std::fs::read(PROGRAM_PATH).expect("缺少 target/deploy/sol_escrow.so，请先执行 anchor build");
+
+        let mut program_data = bincode::serialize(&UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address: Some(admin.pubkey()),
+        })
+        .unwrap();
+        program_data.resize(UpgradeableLoaderState::size_of_programdata_metadata(), 0);
+        program_data.extend_from_slice(&elf);
+
+        let program = bincode::serialize(&UpgradeableLoaderState::Program {
+            programdata_address: pda::program_data(),
+        })
+        .unwrap();
+
+        // 程序数据账户必须先于程序账户写入
+        svm.set_account(
+            pda::program_data(),
+            Account {
+                lamports: svm.minimum_balance_for_rent_exemption(program_data.len()),
+                data: program_data,
+                owner: solana_sdk_ids::bpf_loader_upgradeable::ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .unwrap();
+        svm.set_account(
+            sol_escrow::ID,
+            Account {
+                lamports: svm.minimum_balance_for_rent_exemption(program.len()),
+                data: program,
+                owner: solana_sdk_ids::bpf_loader_upgradeable::ID,
+                executable: true,
+                rent_epoch: 0,
+            },
+        )
+        .unwrap();
+
+        svm.airdrop(&admin.pubkey(), INITIAL_LAMPORTS).unwrap();
+        svm.airdrop(&treasury.pubkey(), INITIAL_LAMPORTS).unwrap();
+
+        Self { svm, admin, treasury }
+    }
+
+    // 部署程序并以默认手续费初始化协议配置
+    pub fn with_config() -> Self {
+        let mut env = Self::new();
+        let admin = env.admin.insecure_clone();
+        let treasury = env.treasury.pubkey();
+        assert_ok(env.send(&[ix::initialize_config(&admin.pubkey(), &treasury, DEFAULT_FEE_BPS)], &[&admin]));
+        env
+    }
+
+    // 第一个签名者支付交易费用；每笔交易使用新的区块哈希，避免重复交易被拒绝
+    // 返回类型来自 LiteSVM，失败时的交易元数据较大
+    #[allow(clippy::result_large_err)]
+    pub fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> TransactionResult {
+        self.svm.expire_blockhash();
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&signers[0].pubkey()),
+            signers,
+            self.svm.latest_blockhash(),
+        );
+        self.svm.send_transaction(tx)
+    }
+
+    pub fn create_user(&mut self) -> Keypair {
+        let user = Keypair::new();
+        self.svm.airdrop(&user.pubkey(), INITIAL_LAMPORTS).unwrap();
+        user
+    }
+
+    // 创建买卖双方；代币订单为买家铸造代币，并为卖家与手续费收款方创建代币账户
+    pub fn market(&mut self, asset: Asset) -> Market {
+        let buyer = self.create_user();
+        let seller = self.create_user();
+        if asset.mint().is_some() {
+            let treasury = self.treasury.pubkey();
+            self.fund_token_account(&asset, &buyer.pubkey(), BUYER_BALANCE);
+            self.fund_token_account(&asset, &seller.pubkey(), 0);
+            self.fund_token_account(&asset, &treasury, 0);
+        }
+        Market { buyer, seller, asset }
+    }
+
+    pub fn create_mint(&mut self, token_program: &Pubkey, decimals: u8) -> Asset {
+        self.create_mint_with_extensions(Keypair::new(), token_program, decimals, &[], Vec::new())
+    }
+
+    // Token-2022 转账手续费代币
+    pub fn create_transfer_fee_mint(&mut self, fee_bps: u16, maximum_fee: u64) -> Asset {
+        let mint = Keypair::new();
+        let init_fee = initialize_transfer_fee_config(
+            &spl_token_2022::ID,
+            &mint.pubkey(),
+            Some(&self.admin.pubkey()),
+            Some(&self.admin.pubkey()),
+            fee_bps,
+            maximum_fee,
+        )
+        .unwrap();
+        self.create_mint_with_extensions(mint, &spl_token_2022::ID, 6, &[ExtensionType::TransferFeeConfig], vec![init_fee])
+    }
+
+    // Token-2022 永久委托代币，委托人可随时转走任意持有人的代币
+    pub fn create_permanent_delegate_mint(&mut self, delegate: &Pubkey, decimals: u8) -> Asset {
+        let mint = Keypair::new();
+        let init_delegate = spl_token_2022::instruction::initialize_permanent_delegate(&spl_token_2022::ID, &mint.pubkey(), delegate).unwrap();
+        self.create_mint_with_extensions(mint, &spl_token_2022::ID, decimals, &[ExtensionType::PermanentDelegate], vec![init_delegate])
+    }
+
+    // 扩展初始化指令必须在 initialize_mint2 之前执行
+    fn create_mint_with_extensions(
+        &mut self,
+        mint: Keypair,
+        token_program: &Pubkey,
+        decimals: u8,
+        extensions: &[ExtensionType],
+        extension_instructions: Vec<Instruction>,
+    ) -> Asset {
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions).unwrap();
+        let admin = self.admin.insecure_clone();
+
+        let mut instructions = vec![system_instruction::create_account(
+            &admin.pubkey(),
+            &mint.pubkey(),
+            self.svm.minimum_balance_for_rent_exemption(space),
+            space as u64,
+            token_program,
+        )];
+        instructions.extend(extension_instructions);
+        instructions.push(
+            spl_token_2022::instruction::initialize_mint2(token_program, &mint.pubkey(), &admin.pubkey(), None, decimals).unwrap(),
+        );
+
+        assert_ok(self.send(&instructions, &[&admin, &mint]));
+
+        Asset::Token { mint: mint.pubkey(), program: *token_program }
+    }
+
+    // 创建NFT并铸造给 owner；元数据账户直接写入，无需加载元数据程序
+    pub fn create_nft(
+        &mut self,
+        token_program: &Pubkey,
+        owner: &Pubkey,
+        royalty_bps: u16,
+        creators: Vec<Creator>,
+        collection: Option<Collection>,
+    ) -> Asset {
+        let nft = self.create_mint(token_program, 0);
+        self.fund_token_account(&nft, owner, 1);
+
+        let mint = nft.mint().unwrap();
+        let metadata = Metadata {
+            key: Key::MetadataV1,
+            update_authority: self.admin.pubkey(),
+            mint,
+            name: "Escrow NFT".to_string(),
+            symbol: "ENFT".to_string(),
+            uri: String::new(),
+            seller_fee_basis_points: royalty_bps,
+            creators: (!creators.is_empty()).then_some(creators),
+            primary_sale_happened: false,
+            is_mutable: true,
+            edition_nonce: None,
+            token_standard: Some(TokenStandard::NonFungible),
+            collection,
+            uses: None,
+            collection_details: None,
+            programmable_config: None,
+        };
+        let data = metadata.try_to_vec().unwrap();
+        self.svm
+            .set_account(
+                pda::nft_metadata(&mint),
+                Account {
+                    lamports: self.svm.minimum_balance_for_rent_exemption(data.len()),
+                    data,
+                    owner: mpl_token_metadata::ID,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            )
+            .unwrap();
+
+        nft
+    }
+
+    // 创建关联代币账户并铸造代币
+    pub fn fund_token_account(&mut self, asset: &Asset, owner: &Pubkey, amount: u64) -> Pubkey {
+        let admin = self.admin.insecure_clone();
+        let mint = asset.mint().expect("SOL订单无需代币账户");
+        let token_account = asset.ata(owner).unwrap();
+
+        let mut instructions = Vec::new();
+        if self.svm.get_account(&token_account).is_none() {
+            instructions.push(spl_associated_token_account::instruction::create_associated_token_account(
+                &admin.pubkey(),
+                owner,
+                &mint,
+                &asset.program(),
+            ));
+        }
+        if amount > 0 {
+            instructions.push(
+                spl_token_2022::instruction::mint_to(&asset.program(), &mint, &token_account, &admin.pubkey(), &[], amount).unwrap(),
+            );
+        }
+        if !instructions.is_empty() {
+            assert_ok(self.send(&instructions, &[&admin]));
+        }
+
+        token_account
+    }
+
+    pub fn token_balance(&self, token_account: &Pubkey) -> u64 {
+        let account = self.svm.get_account(token_account).expect("代币账户不存在");
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+            .unwrap()
+            .base
+            .amount
+    }
+
+    // Token-2022 代币账户中被扣留的转账手续费
+    pub fn withheld_fee(&self, token_account: &Pubkey) -> u64 {
+        let account = self.svm.get_account(token_account).expect("代币账户不存在");
+        let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data).unwrap();
+        state
+            .get_extension::<spl_token_2022::extension::transfer_fee::TransferFeeAmount>()
+            .map(|extension| u64::from(extension.withheld_amount))
+            .unwrap_or(0)
+    }
+
+    // Token-2022 mint 中已归集的转账手续费
+    pub fn harvested_fee(&self, mint: &Pubkey) -> u64 {
+        let account = self.svm.get_account(mint).expect("mint不存在");
+        let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data).unwrap();
+        state
+            .get_extension::<spl_token_2022::extension::transfer_fee::TransferFeeConfig>()
+            .map(|extension| u64::from(extension.withheld_amount))
+            .unwrap_or(0)
+    }
+
+    pub fn lamports(&self, address: &Pubkey) -> u64 {
+        self.svm.get_account(address).map(|account| account.lamports).unwrap_or(0)
+    }
+
+    pub fn account_exists(&self, address: &Pubkey) -> bool {
+        self.svm.get_account(address).is_some_and(|account| account.lamports > 0)
+    }
+
+    pub fn escrow(&self, address: &Pubkey) -> Escrow {
+        let account = self.svm.get_account(address).expect("订单账户不存在");
+        Escrow::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    // 卖家按订单当前的金额与超时时间确认
+    pub fn seller_confirmation(&self, seller: &Pubkey, buyer: &Pubkey, order_id: u64) -> Instruction {
+        let escrow = self.escrow(&pda::order(buyer, order_id));
+        ix::seller_confirmation(seller, buyer, order_id, escrow.amount, escrow.expiration)
+    }
+
+    pub fn seller_confirmation_nft(&self, seller: &Pubkey, buyer: &Pubkey, order_id: u64, nft: &ix::SellerNft) -> Instruction {
+        let escrow = self.escrow(&pda::order(buyer, order_id));
+        ix::seller_confirmation_nft(seller, buyer, order_id, escrow.amount, escrow.expiration, nft)
+    }
+
+    pub fn fetch<T: AccountDeserialize>(&self, address: &Pubkey) -> T {
+        let account = self.svm.get_account(address).expect("账户不存在");
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub fn now(&self) -> i64 {
+        self.svm.get_sysvar::<Clock>().unix_timestamp
+    }
+
+    // 调整链上时间，用于测试超时路径
+    pub fn warp(&mut self, seconds: i64) {
+        let mut clock = self.svm.get_sysvar::<Clock>();
+        clock.unix_timestamp += seconds;
+        clock.slot += 1;
+        self.svm.set_sysvar(&clock);
+    }
+}
+
+impl Default for TestEnv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
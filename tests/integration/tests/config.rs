@@ -0,0 +1,87 @@
+use anchor_spl::token::spl_token;
+use sol_escrow::{
+    error::ErrorCode,
+    state::{Config, MintFeeConfig},
+};
+use sol_escrow_integration_tests::{assert_error, assert_ok, ix, pda, TestEnv, DEFAULT_FEE_BPS};
+use solana_sdk::signer::Signer;
+
+
+#[test]
+fn initialize_config_requires_upgrade_authority() {
+    let mut env = TestEnv::new();
+    let attacker = env.create_user();
+    let admin = env.admin.insecure_clone();
+    let treasury = env.treasury.pubkey();
+
+    assert_error(
+        env.send(&[ix::initialize_config(&attacker.pubkey(), &treasury, DEFAULT_FEE_BPS)], &[&attacker]),
+        ErrorCode::Unauthorized,
+    );
+
+    assert_ok(env.send(&[ix::initialize_config(&admin.pubkey(), &treasury, DEFAULT_FEE_BPS)], &[&admin]));
+
+    let config: Config = env.fetch(&pda::config());
+    assert_eq!(config.admin, admin.pubkey());
+    assert_eq!(config.treasury, treasury);
+    assert_eq!(config.fee_bps, DEFAULT_FEE_BPS);
+    assert_eq!(config.keeper_bounty, 0);
+}
+
+#[test]
+fn update_config_is_admin_only_and_bounded() {
+    let mut env = TestEnv::with_config();
+    let attacker = env.create_user();
+    let admin = env.admin.insecure_clone();
+    let new_treasury = env.create_user().pubkey();
+
+    assert_error(
+        env.send(&[ix::update_config(&attacker.pubkey(), None, &new_treasury, 50, 0)], &[&attacker]),
+        ErrorCode::Unauthorized,
+    );
+    assert_error(
+        env.send(&[ix::update_config(&admin.pubkey(), None, &new_treasury, 1_001, 0)], &[&admin]),
+        ErrorCode::FeeTooHigh,
+    );
+    assert_error(
+        env.send(&[ix::update_config(&admin.pubkey(), None, &new_treasury, 50, 1_000_001)], &[&admin]),
+        ErrorCode::KeeperBountyTooHigh,
+    );
+
+    assert_ok(env.send(&[ix::update_config(&admin.pubkey(), Some(attacker.pubkey()), &new_treasury, 50, 5_000)], &[&admin]));
+
+    let config: Config = env.fetch(&pda::config());
+    assert_eq!(config.admin, attacker.pubkey());
+    assert_eq!(config.treasury, new_treasury);
+    assert_eq!(config.fee_bps, 50);
+    assert_eq!(config.keeper_bounty, 5_000);
+
+    // 管理员移交后原管理员失去权限
+    assert_error(
+        env.send(&[ix::update_config(&admin.pubkey(), None, &new_treasury, 50, 0)], &[&admin]),
+        ErrorCode::Unauthorized,
+    );
+}
+
+#[test]
+fn set_mint_fee_is_admin_only() {
+    let mut env = TestEnv::with_config();
+    let attacker = env.create_user();
+    let admin = env.admin.insecure_clone();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let mint = asset.mint().unwrap();
+
+    assert_error(
+        env.send(&[ix::set_mint_fee(&attacker.pubkey(), &mint, 250)], &[&attacker]),
+        ErrorCode::Unauthorized,
+    );
+    assert_error(env.send(&[ix::set_mint_fee(&admin.pubkey(), &mint, 1_001)], &[&admin]), ErrorCode::FeeTooHigh);
+
+    assert_ok(env.send(&[ix::set_mint_fee(&admin.pubkey(), &mint, 250)], &[&admin]));
+    // 已存在的配置可以再次修改
+    assert_ok(env.send(&[ix::set_mint_fee(&admin.pubkey(), &mint, 200)], &[&admin]));
+
+    let mint_fee: MintFeeConfig = env.fetch(&pda::mint_fee(&mint));
+    assert_eq!(mint_fee.mint, mint);
+    assert_eq!(mint_fee.fee_bps, 200);
+}
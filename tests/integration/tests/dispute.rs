@@ -0,0 +1,153 @@
+use anchor_spl::token::spl_token;
+use sol_escrow::{error::ErrorCode, state::TransactionStatus};
+use sol_escrow_integration_tests::{assert_error, assert_ok, ix, pda, Asset, Market, TestEnv, BUYER_BALANCE};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, signature::Keypair, signer::Signer};
+
+
+// 指定仲裁者、卖家已确认的订单
+fn arbitrated_order(env: &mut TestEnv, market: &Market, arbitrator: &Keypair, amount: u64) {
+    let Market { buyer, seller, asset } = market;
+    let mut args = ix::order_args(amount, env.now(), Some(seller.pubkey()));
+    args.arbitrator = Some(arbitrator.pubkey());
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+}
+
+
+#[test]
+fn arbitrator_splits_disputed_funds() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let arbitrator = env.create_user();
+    let attacker = env.create_user();
+    arbitrated_order(&mut env, &market, &arbitrator, 1_000);
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+    let treasury = env.treasury.pubkey();
+
+    assert_error(
+        env.send(&[ix::open_dispute(&attacker.pubkey(), &buyer.pubkey(), 1)], &[&attacker]),
+        ErrorCode::Unauthorized,
+    );
+    assert_ok(env.send(&[ix::open_dispute(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Disputed);
+
+    // 争议期间不能放款
+    assert_error(
+        env.send(&[ix::escrow_release(&buyer.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury)], &[buyer]),
+        ErrorCode::FundsReleaseNotAllowed,
+    );
+
+    assert_error(
+        env.send(&[ix::resolve_dispute(&attacker.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, 3_000)], &[&attacker]),
+        ErrorCode::Unauthorized,
+    );
+    assert_error(
+        env.send(&[ix::resolve_dispute(&arbitrator.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, 10_001)], &[&arbitrator]),
+        ErrorCode::InvalidBasisPoints,
+    );
+
+    assert_ok(env.send(&[ix::resolve_dispute(&arbitrator.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, 3_000)], &[&arbitrator]));
+
+    let escrow_data = env.escrow(&escrow);
+    assert_eq!(escrow_data.status, TransactionStatus::Success);
+    // 卖家所得含手续费计入已放款金额
+    assert_eq!(escrow_data.released_amount, 300);
+    // 卖家所得 300 扣除 1% 协议手续费，退还买家的部分不收费
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 297);
+    assert_eq!(env.token_balance(&asset.ata(&treasury).unwrap()), 3);
+    assert_eq!(env.token_balance(&asset.ata(&buyer.pubkey()).unwrap()), BUYER_BALANCE - 300);
+
+    // 已裁决的订单不能再次裁决
+    assert_error(
+        env.send(&[ix::resolve_dispute(&arbitrator.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, 3_000)], &[&arbitrator]),
+        ErrorCode::OrderNotDisputed,
+    );
+}
+
+#[test]
+fn full_refund_cancels_native_order() {
+    let mut env = TestEnv::with_config();
+    let market = env.market(Asset::Native);
+    let arbitrator = env.create_user();
+    arbitrated_order(&mut env, &market, &arbitrator, LAMPORTS_PER_SOL);
+    let Market { buyer, seller, asset } = &market;
+
+    let treasury = env.treasury.pubkey();
+    assert_ok(env.send(&[ix::open_dispute(&buyer.pubkey(), &buyer.pubkey(), 1)], &[buyer]));
+
+    let buyer_before = env.lamports(&buyer.pubkey());
+    let seller_before = env.lamports(&seller.pubkey());
+    let treasury_before = env.lamports(&treasury);
+    assert_ok(env.send(&[ix::resolve_dispute(&arbitrator.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, 0)], &[&arbitrator]));
+
+    let escrow_data = env.escrow(&pda::order(&buyer.pubkey(), 1));
+    assert_eq!(escrow_data.status, TransactionStatus::Cancelled);
+    assert_eq!(escrow_data.released_amount, 0);
+    assert_eq!(env.lamports(&buyer.pubkey()) - buyer_before, LAMPORTS_PER_SOL);
+    assert_eq!(env.lamports(&seller.pubkey()), seller_before);
+    assert_eq!(env.lamports(&treasury), treasury_before);
+}
+
+#[test]
+fn native_dispute_split_pays_protocol_fee() {
+    let mut env = TestEnv::with_config();
+    let market = env.market(Asset::Native);
+    let arbitrator = env.create_user();
+    arbitrated_order(&mut env, &market, &arbitrator, LAMPORTS_PER_SOL);
+    let Market { buyer, seller, asset } = &market;
+    let treasury = env.treasury.pubkey();
+
+    assert_ok(env.send(&[ix::open_dispute(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+
+    let buyer_before = env.lamports(&buyer.pubkey());
+    let seller_before = env.lamports(&seller.pubkey());
+    let treasury_before = env.lamports(&treasury);
+    assert_ok(env.send(&[ix::resolve_dispute(&arbitrator.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, 5_000)], &[&arbitrator]));
+
+    let seller_amount = LAMPORTS_PER_SOL / 2;
+    let fee = seller_amount / 100;
+    assert_eq!(env.escrow(&pda::order(&buyer.pubkey(), 1)).status, TransactionStatus::Success);
+    assert_eq!(env.lamports(&seller.pubkey()) - seller_before, seller_amount - fee);
+    assert_eq!(env.lamports(&treasury) - treasury_before, fee);
+    assert_eq!(env.lamports(&buyer.pubkey()) - buyer_before, LAMPORTS_PER_SOL - seller_amount);
+}
+
+#[test]
+fn dispute_requires_arbitrator() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let Market { buyer, seller, asset } = &market;
+
+    let args = ix::order_args(1_000, env.now(), Some(seller.pubkey()));
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    assert_error(
+        env.send(&[ix::open_dispute(&buyer.pubkey(), &buyer.pubkey(), 1)], &[buyer]),
+        ErrorCode::DisputeNotAllowed,
+    );
+}
+
+#[test]
+fn seller_cannot_dispute_unconfirmed_order() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let arbitrator = env.create_user();
+    let Market { buyer, seller, asset } = &market;
+
+    let mut args = ix::order_args(1_000, env.now(), Some(seller.pubkey()));
+    args.arbitrator = Some(arbitrator.pubkey());
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    // 卖家确认前不能发起争议，买家仍可取消订单
+    assert_error(
+        env.send(&[ix::open_dispute(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]),
+        ErrorCode::DisputeNotAllowed,
+    );
+    assert_eq!(env.escrow(&pda::order(&buyer.pubkey(), 1)).status, TransactionStatus::Funded);
+    assert_ok(env.send(&[ix::order_cancellation(&buyer.pubkey(), 1, asset)], &[buyer]));
+    assert_eq!(env.token_balance(&asset.ata(&buyer.pubkey()).unwrap()), BUYER_BALANCE);
+}
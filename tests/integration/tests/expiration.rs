@@ -0,0 +1,327 @@
+use anchor_spl::token::spl_token;
+use sol_escrow::{error::ErrorCode, state::TransactionStatus};
+use sol_escrow_integration_tests::{
+    assert_error, assert_ok, ix, pda, Market, TestEnv, BUYER_BALANCE, DEFAULT_EXPIRATION,
+};
+use solana_sdk::signer::Signer;
+
+
+fn token_market(env: &mut TestEnv) -> Market {
+    let asset = env.create_mint(&spl_token::ID, 6);
+    env.market(asset)
+}
+
+fn fund_order(env: &mut TestEnv, market: &Market, order_id: u64, amount: u64) {
+    let Market { buyer, seller, asset } = market;
+    let args = ix::order_args(amount, env.now(), Some(seller.pubkey()));
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), order_id, asset, args)], &[buyer]));
+}
+
+
+#[test]
+fn cancellation_refunds_before_expiration() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    fund_order(&mut env, &market, 1, 1_000);
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+
+    assert_ok(env.send(&[ix::order_cancellation(&buyer.pubkey(), 1, asset)], &[buyer]));
+
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Cancelled);
+    assert_eq!(env.token_balance(&asset.ata(&buyer.pubkey()).unwrap()), BUYER_BALANCE);
+
+    // 已取消的订单不能确认
+    assert_error(
+        env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]),
+        ErrorCode::SellerConfirmationNotAllowed,
+    );
+}
+
+#[test]
+fn cancellation_rejected_after_confirmation_or_expiration() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    fund_order(&mut env, &market, 1, 1_000);
+    fund_order(&mut env, &market, 2, 1_000);
+    let Market { buyer, seller, asset } = &market;
+
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+    assert_error(
+        env.send(&[ix::order_cancellation(&buyer.pubkey(), 1, asset)], &[buyer]),
+        ErrorCode::CancellationNotAllowed,
+    );
+
+    env.warp(DEFAULT_EXPIRATION + 1);
+    assert_error(env.send(&[ix::order_cancellation(&buyer.pubkey(), 2, asset)], &[buyer]), ErrorCode::OrderExpired);
+    // 超时后也不能再付款或确认
+    assert_error(
+        env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 2)], &[seller]),
+        ErrorCode::OrderExpired,
+    );
+}
+
+#[test]
+fn timeout_rejects_unexpired_order() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    fund_order(&mut env, &market, 1, 1_000);
+    let Market { buyer, asset, .. } = &market;
+
+    assert_error(env.send(&[ix::timeout_check(&buyer.pubkey(), 1, asset)], &[buyer]), ErrorCode::OrderNotExpired);
+    assert_eq!(env.escrow(&pda::order(&buyer.pubkey(), 1)).status, TransactionStatus::Funded);
+}
+
+#[test]
+fn timeout_refunds_funded_order() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    fund_order(&mut env, &market, 1, 1_000);
+    let Market { buyer, asset, .. } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+
+    env.warp(DEFAULT_EXPIRATION);
+    let buyer_before = env.token_balance(&asset.ata(&buyer.pubkey()).unwrap());
+    assert_ok(env.send(&[ix::timeout_check(&buyer.pubkey(), 1, asset)], &[buyer]));
+
+    // 卖家确认前超时，托管的 1000 全额退还买家
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Expired);
+    assert_eq!(env.token_balance(&asset.ata(&buyer.pubkey()).unwrap()) - buyer_before, 1_000);
+    assert_eq!(env.token_balance(&asset.ata(&buyer.pubkey()).unwrap()), BUYER_BALANCE);
+    assert_eq!(env.token_balance(&asset.ata(&escrow).unwrap()), 0);
+
+    // 已超时的订单不能重复处理
+    assert_error(env.send(&[ix::timeout_check(&buyer.pubkey(), 1, asset)], &[buyer]), ErrorCode::OrderAlreadySettled);
+
+    assert_ok(env.send(&[ix::close_order(&buyer.pubkey(), 1, asset)], &[buyer]));
+    assert!(!env.account_exists(&escrow));
+}
+
+#[test]
+fn timeout_expires_unfunded_order_without_refund() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+
+    let args = ix::order_args(1_000, env.now(), Some(seller.pubkey()));
+    assert_ok(env.send(&[ix::create_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    env.warp(DEFAULT_EXPIRATION);
+    assert_ok(env.send(&[ix::timeout_check(&buyer.pubkey(), 1, asset)], &[buyer]));
+
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Expired);
+    assert_eq!(env.token_balance(&asset.ata(&buyer.pubkey()).unwrap()), BUYER_BALANCE);
+}
+
+#[test]
+fn timeout_routes_in_transit_order_to_arbitrator() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let arbitrator = env.create_user();
+    let keeper = env.create_user();
+    let treasury = env.treasury.pubkey();
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+
+    let mut args = ix::order_args(1_000, env.now(), Some(seller.pubkey()));
+    args.arbitrator = Some(arbitrator.pubkey());
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+
+    env.warp(DEFAULT_EXPIRATION);
+    // 卖家已确认的订单不能被清理
+    assert_error(
+        env.send(&[ix::crank_expired(&keeper.pubkey(), &buyer.pubkey(), 1, asset)], &[&keeper]),
+        ErrorCode::OrderInTransit,
+    );
+    assert_ok(env.send(&[ix::timeout_check(&buyer.pubkey(), 1, asset)], &[buyer]));
+
+    // 超时不退款，订单转入争议，资金仍在托管账户
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Disputed);
+    assert_eq!(env.token_balance(&asset.ata(&buyer.pubkey()).unwrap()), BUYER_BALANCE - 1_000);
+    assert_eq!(env.token_balance(&asset.ata(&escrow).unwrap()), 1_000);
+
+    // 争议中的订单跳过超时处理，由仲裁者裁决
+    assert_error(env.send(&[ix::timeout_check(&buyer.pubkey(), 1, asset)], &[buyer]), ErrorCode::OrderInDispute);
+    assert_error(
+        env.send(&[ix::crank_expired(&keeper.pubkey(), &buyer.pubkey(), 1, asset)], &[&keeper]),
+        ErrorCode::OrderInDispute,
+    );
+
+    assert_ok(env.send(
+        &[ix::resolve_dispute(&arbitrator.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, 10_000)],
+        &[&arbitrator],
+    ));
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 990);
+}
+
+#[test]
+fn timeout_rejects_in_transit_order_without_arbitrator() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    fund_order(&mut env, &market, 1, 1_000);
+    let Market { buyer, seller, asset } = &market;
+
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+
+    env.warp(DEFAULT_EXPIRATION);
+    assert_error(env.send(&[ix::timeout_check(&buyer.pubkey(), 1, asset)], &[buyer]), ErrorCode::OrderInTransit);
+
+    // 买家不能在卖家确认后通过超时拿回资金
+    assert_eq!(env.escrow(&pda::order(&buyer.pubkey(), 1)).status, TransactionStatus::InTransit);
+    assert_eq!(env.token_balance(&asset.ata(&buyer.pubkey()).unwrap()), BUYER_BALANCE - 1_000);
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 0);
+}
+
+#[test]
+fn timeout_keeps_in_transit_order_with_pending_release_time() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let arbitrator = env.create_user();
+    let treasury = env.treasury.pubkey();
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+
+    // 自动放款时间晚于订单超时时间
+    let mut args = ix::order_args(1_000, env.now(), Some(seller.pubkey()));
+    args.arbitrator = Some(arbitrator.pubkey());
+    args.release_delay = Some(2 * DEFAULT_EXPIRATION);
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+
+    // 超时后任何人都不能替买卖双方把订单转入争议
+    env.warp(DEFAULT_EXPIRATION);
+    assert_error(env.send(&[ix::timeout_check(&buyer.pubkey(), 1, asset)], &[buyer]), ErrorCode::OrderInTransit);
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::InTransit);
+
+    // 自动放款时间到达后卖家自行领取
+    env.warp(DEFAULT_EXPIRATION);
+    let release = ix::escrow_release(&seller.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury);
+    assert_ok(env.send(&[release], &[seller]));
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Success);
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 990);
+}
+
+#[test]
+fn timeout_defers_to_seller_once_release_time_passed() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let treasury = env.treasury.pubkey();
+    let Market { buyer, seller, asset } = &market;
+
+    let mut args = ix::order_args(1_000, env.now(), Some(seller.pubkey()));
+    args.release_delay = Some(600);
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+
+    let release = ix::escrow_release(&seller.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury);
+
+    // 自动放款时间未到
+    assert_error(env.send(std::slice::from_ref(&release), &[seller]), ErrorCode::ReleaseNotReady);
+
+    env.warp(DEFAULT_EXPIRATION);
+    assert_error(
+        env.send(&[ix::timeout_check(&buyer.pubkey(), 1, asset)], &[buyer]),
+        ErrorCode::SellerReleaseAvailable,
+    );
+
+    assert_ok(env.send(&[release], &[seller]));
+    assert_eq!(env.escrow(&pda::order(&buyer.pubkey(), 1)).status, TransactionStatus::Success);
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 990);
+}
+
+#[test]
+fn timeout_rejects_disputed_and_settled_orders() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let arbitrator = env.create_user();
+    let treasury = env.treasury.pubkey();
+    let Market { buyer, seller, asset } = &market;
+
+    let mut args = ix::order_args(1_000, env.now(), Some(seller.pubkey()));
+    args.arbitrator = Some(arbitrator.pubkey());
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_ok(env.send(&[ix::open_dispute(&buyer.pubkey(), &buyer.pubkey(), 1)], &[buyer]));
+
+    fund_order(&mut env, &market, 2, 1_000);
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 2)], &[seller]));
+    assert_ok(env.send(&[ix::escrow_release(&buyer.pubkey(), &buyer.pubkey(), &seller.pubkey(), 2, asset, &treasury)], &[buyer]));
+
+    env.warp(DEFAULT_EXPIRATION);
+
+    assert_error(env.send(&[ix::timeout_check(&buyer.pubkey(), 1, asset)], &[buyer]), ErrorCode::OrderInDispute);
+    assert_error(env.send(&[ix::timeout_check(&buyer.pubkey(), 2, asset)], &[buyer]), ErrorCode::OrderAlreadySettled);
+    assert_eq!(env.escrow(&pda::order(&buyer.pubkey(), 2)).status, TransactionStatus::Success);
+}
+
+#[test]
+fn crank_expired_refunds_closes_and_pays_bounty() {
+    let mut env = TestEnv::with_config();
+    let admin = env.admin.insecure_clone();
+    let treasury = env.treasury.pubkey();
+    assert_ok(env.send(&[ix::update_config(&admin.pubkey(), None, &treasury, 100, 5_000)], &[&admin]));
+
+    let market = token_market(&mut env);
+    let keeper = env.create_user();
+    fund_order(&mut env, &market, 1, 1_000);
+    let Market { buyer, asset, .. } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+
+    assert_error(
+        env.send(&[ix::crank_expired(&keeper.pubkey(), &buyer.pubkey(), 1, asset)], &[&keeper]),
+        ErrorCode::OrderNotExpired,
+    );
+
+    env.warp(DEFAULT_EXPIRATION);
+    let escrow_lamports = env.lamports(&escrow);
+    let vault_lamports = env.lamports(&asset.ata(&escrow).unwrap());
+    let buyer_before = env.lamports(&buyer.pubkey());
+    let keeper_before = env.lamports(&keeper.pubkey());
+
+    // 管理员支付交易费用，便于核对执行者奖励
+    assert_ok(env.send(&[ix::crank_expired(&keeper.pubkey(), &buyer.pubkey(), 1, asset)], &[&admin, &keeper]));
+
+    assert!(!env.account_exists(&escrow));
+    assert!(!env.account_exists(&asset.ata(&escrow).unwrap()));
+    assert_eq!(env.token_balance(&asset.ata(&buyer.pubkey()).unwrap()), BUYER_BALANCE);
+    assert_eq!(env.lamports(&keeper.pubkey()) - keeper_before, 5_000);
+    assert_eq!(env.lamports(&buyer.pubkey()) - buyer_before, escrow_lamports + vault_lamports - 5_000);
+}
+
+#[test]
+fn crank_closes_order_already_timed_out() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let keeper = env.create_user();
+    fund_order(&mut env, &market, 1, 1_000);
+    let Market { buyer, asset, .. } = &market;
+
+    env.warp(DEFAULT_EXPIRATION);
+    assert_ok(env.send(&[ix::timeout_check(&buyer.pubkey(), 1, asset)], &[buyer]));
+    assert_ok(env.send(&[ix::crank_expired(&keeper.pubkey(), &buyer.pubkey(), 1, asset)], &[&keeper]));
+
+    assert!(!env.account_exists(&pda::order(&buyer.pubkey(), 1)));
+    assert_eq!(env.token_balance(&asset.ata(&buyer.pubkey()).unwrap()), BUYER_BALANCE);
+}
+
+#[test]
+fn crank_expired_closes_transfer_fee_vault() {
+    let mut env = TestEnv::with_config();
+    // 1% 转账手续费，托管账户实际收到 990
+    let asset = env.create_transfer_fee_mint(100, u64::MAX);
+    let market = env.market(asset);
+    let keeper = env.create_user();
+    fund_order(&mut env, &market, 1, 1_000);
+    let Market { buyer, asset, .. } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+
+    env.warp(DEFAULT_EXPIRATION);
+    assert_ok(env.send(&[ix::crank_expired(&keeper.pubkey(), &buyer.pubkey(), 1, asset)], &[&keeper]));
+
+    // 退款 990 时再扣除 10 转账手续费，托管账户中预扣的 10 归集到mint
+    assert!(!env.account_exists(&escrow));
+    assert!(!env.account_exists(&asset.ata(&escrow).unwrap()));
+    assert_eq!(env.token_balance(&asset.ata(&buyer.pubkey()).unwrap()), BUYER_BALANCE - 20);
+    assert_eq!(env.harvested_fee(&asset.mint().unwrap()), 10);
+}
@@ -0,0 +1,290 @@
+use anchor_spl::{metadata::mpl_token_metadata::types::Creator, token::spl_token, token_2022::spl_token_2022};
+use sol_escrow::{error::ErrorCode, instructions::CreateListingArgs};
+use sol_escrow_integration_tests::{assert_error, assert_ok, ix, pda, TestEnv, DEFAULT_EXPIRATION};
+use solana_sdk::{instruction::AccountMeta, signer::Signer};
+
+
+fn listing_args(env: &TestEnv, price: u64, asset_amount: u64) -> CreateListingArgs {
+    CreateListingArgs {
+        price,
+        asset_amount,
+        expiration: env.now() + DEFAULT_EXPIRATION,
+        is_nft: false,
+    }
+}
+
+// NFT挂单参数：出售一枚NFT，成交时支付版税
+fn nft_listing_args(env: &TestEnv, price: u64) -> CreateListingArgs {
+    CreateListingArgs { is_nft: true, ..listing_args(env, price, 1) }
+}
+
+
+#[test]
+fn buyer_fills_listing() {
+    let mut env = TestEnv::with_config();
+    let seller = env.create_user();
+    let buyer = env.create_user();
+    let payment = env.create_mint(&spl_token::ID, 6);
+    // Token-2022 的 NFT 形式资产
+    let listed = env.create_nft(&spl_token_2022::ID, &seller.pubkey(), 0, Vec::new(), None);
+
+    env.fund_token_account(&payment, &buyer.pubkey(), 1_000);
+    env.fund_token_account(&payment, &seller.pubkey(), 0);
+    env.fund_token_account(&listed, &buyer.pubkey(), 0);
+    let treasury = env.treasury.pubkey();
+    env.fund_token_account(&payment, &treasury, 0);
+
+    let args = nft_listing_args(&env, 500);
+    assert_ok(env.send(&[ix::create_listing_nft(&seller.pubkey(), 1, &payment, &listed, args).unwrap()], &[&seller]));
+
+    let escrow = pda::listing(&seller.pubkey(), 1);
+    let escrow_data = env.escrow(&escrow);
+    assert!(escrow_data.is_listing);
+    assert!(escrow_data.is_nft);
+    assert_eq!(env.token_balance(&listed.ata(&escrow).unwrap()), 1);
+    assert_eq!(env.token_balance(&listed.ata(&seller.pubkey()).unwrap()), 0);
+
+    assert_ok(env.send(&[ix::fill_listing(&buyer.pubkey(), &seller.pubkey(), 1, &payment, &listed, &treasury).unwrap()], &[&buyer]));
+
+    assert_eq!(env.token_balance(&listed.ata(&buyer.pubkey()).unwrap()), 1);
+    // 成交价扣除 1% 协议手续费后归卖家
+    assert_eq!(env.token_balance(&payment.ata(&seller.pubkey()).unwrap()), 495);
+    assert_eq!(env.token_balance(&payment.ata(&treasury).unwrap()), 5);
+    assert_eq!(env.token_balance(&payment.ata(&buyer.pubkey()).unwrap()), 500);
+    // 成交后挂单与资产托管账户关闭
+    assert!(!env.account_exists(&escrow));
+    assert!(!env.account_exists(&listed.ata(&escrow).unwrap()));
+}
+
+#[test]
+fn seller_cancels_listing() {
+    let mut env = TestEnv::new();
+    let seller = env.create_user();
+    let payment = env.create_mint(&spl_token::ID, 6);
+    let listed = env.create_mint(&spl_token::ID, 6);
+
+    env.fund_token_account(&listed, &seller.pubkey(), 5_000);
+
+    let args = listing_args(&env, 700, 5_000);
+    assert_ok(env.send(&[ix::create_listing(&seller.pubkey(), 1, &payment, &listed, args).unwrap()], &[&seller]));
+    assert!(!env.escrow(&pda::listing(&seller.pubkey(), 1)).is_nft);
+
+    assert_ok(env.send(&[ix::cancel_listing(&seller.pubkey(), 1, &listed).unwrap()], &[&seller]));
+
+    assert_eq!(env.token_balance(&listed.ata(&seller.pubkey()).unwrap()), 5_000);
+    assert!(!env.account_exists(&pda::listing(&seller.pubkey(), 1)));
+}
+
+#[test]
+fn expired_listing_cannot_be_filled() {
+    let mut env = TestEnv::with_config();
+    let seller = env.create_user();
+    let buyer = env.create_user();
+    let payment = env.create_mint(&spl_token::ID, 6);
+    let listed = env.create_nft(&spl_token::ID, &seller.pubkey(), 0, Vec::new(), None);
+
+    env.fund_token_account(&payment, &buyer.pubkey(), 1_000);
+    env.fund_token_account(&payment, &seller.pubkey(), 0);
+    env.fund_token_account(&listed, &buyer.pubkey(), 0);
+    let treasury = env.treasury.pubkey();
+    env.fund_token_account(&payment, &treasury, 0);
+
+    let mut args = nft_listing_args(&env, 500);
+    args.price = 0;
+    assert_error(
+        env.send(&[ix::create_listing_nft(&seller.pubkey(), 1, &payment, &listed, args).unwrap()], &[&seller]),
+        ErrorCode::AmountZero,
+    );
+
+    let args = nft_listing_args(&env, 500);
+    assert_ok(env.send(&[ix::create_listing_nft(&seller.pubkey(), 1, &payment, &listed, args).unwrap()], &[&seller]));
+
+    env.warp(DEFAULT_EXPIRATION);
+    assert_error(
+        env.send(&[ix::fill_listing(&buyer.pubkey(), &seller.pubkey(), 1, &payment, &listed, &treasury).unwrap()], &[&buyer]),
+        ErrorCode::OrderExpired,
+    );
+
+    // 超时后卖家仍可取回资产
+    assert_ok(env.send(&[ix::cancel_listing(&seller.pubkey(), 1, &listed).unwrap()], &[&seller]));
+    assert_eq!(env.token_balance(&listed.ata(&seller.pubkey()).unwrap()), 1);
+}
+
+#[test]
+fn nft_listing_pays_royalty() {
+    let mut env = TestEnv::with_config();
+    let seller = env.create_user();
+    let buyer = env.create_user();
+    let creator = env.create_user();
+    let payment = env.create_mint(&spl_token::ID, 6);
+
+    // 5% 版税，两位已验证创作者按 60/40 分成，未验证的创作者不参与
+    let creators = vec![
+        Creator { address: creator.pubkey(), verified: true, share: 60 },
+        Creator { address: seller.pubkey(), verified: true, share: 40 },
+        Creator { address: buyer.pubkey(), verified: false, share: 0 },
+    ];
+    let listed = env.create_nft(&spl_token::ID, &seller.pubkey(), 500, creators, None);
+
+    env.fund_token_account(&payment, &buyer.pubkey(), 1_000);
+    let seller_account = env.fund_token_account(&payment, &seller.pubkey(), 0);
+    let creator_account = env.fund_token_account(&payment, &creator.pubkey(), 0);
+    env.fund_token_account(&listed, &buyer.pubkey(), 0);
+    let treasury = env.treasury.pubkey();
+    env.fund_token_account(&payment, &treasury, 0);
+
+    let args = nft_listing_args(&env, 1_000);
+    assert_ok(env.send(&[ix::create_listing_nft(&seller.pubkey(), 1, &payment, &listed, args).unwrap()], &[&seller]));
+
+    let escrow_data = env.escrow(&pda::listing(&seller.pubkey(), 1));
+    assert_eq!(escrow_data.royalty_bps, 500);
+    assert_eq!(escrow_data.royalty_creators.len(), 2);
+
+    // 缺少创作者账户时无法成交
+    assert_error(
+        env.send(&[ix::fill_listing(&buyer.pubkey(), &seller.pubkey(), 1, &payment, &listed, &treasury).unwrap()], &[&buyer]),
+        ErrorCode::MissingCreatorAccount,
+    );
+
+    let mut fill = ix::fill_listing(&buyer.pubkey(), &seller.pubkey(), 1, &payment, &listed, &treasury).unwrap();
+    fill.accounts.push(AccountMeta::new(creator_account, false));
+    fill.accounts.push(AccountMeta::new(seller_account, false));
+    assert_ok(env.send(&[fill], &[&buyer]));
+
+    // 成交价 1000：手续费 10，版税 50 按份额拆分为 30 与 20，卖家另得 940
+    assert_eq!(env.token_balance(&creator_account), 30);
+    assert_eq!(env.token_balance(&seller_account), 960);
+    assert_eq!(env.token_balance(&payment.ata(&treasury).unwrap()), 10);
+    assert_eq!(env.token_balance(&listed.ata(&buyer.pubkey()).unwrap()), 1);
+}
+
+#[test]
+fn nft_listing_requires_metadata() {
+    let mut env = TestEnv::new();
+    let seller = env.create_user();
+    let payment = env.create_mint(&spl_token::ID, 6);
+    let listed = env.create_nft(&spl_token::ID, &seller.pubkey(), 500, Vec::new(), None);
+
+    // 不提供元数据无法记录版税，NFT 挂单被拒绝
+    let args = nft_listing_args(&env, 500);
+    assert_error(
+        env.send(&[ix::create_listing(&seller.pubkey(), 1, &payment, &listed, args).unwrap()], &[&seller]),
+        ErrorCode::MissingMetadata,
+    );
+}
+
+#[test]
+fn single_unit_fungible_listing_needs_no_metadata() {
+    let mut env = TestEnv::with_config();
+    let seller = env.create_user();
+    let buyer = env.create_user();
+    let payment = env.create_mint(&spl_token::ID, 6);
+    // 0 位精度的普通代币，只出售一枚也不按NFT处理
+    let listed = env.create_mint(&spl_token::ID, 0);
+
+    env.fund_token_account(&listed, &seller.pubkey(), 10);
+    env.fund_token_account(&listed, &buyer.pubkey(), 0);
+    env.fund_token_account(&payment, &buyer.pubkey(), 1_000);
+    env.fund_token_account(&payment, &seller.pubkey(), 0);
+    let treasury = env.treasury.pubkey();
+    env.fund_token_account(&payment, &treasury, 0);
+
+    // NFT挂单只能出售一枚
+    let mut args = listing_args(&env, 500, 10);
+    args.is_nft = true;
+    assert_error(
+        env.send(&[ix::create_listing(&seller.pubkey(), 1, &payment, &listed, args).unwrap()], &[&seller]),
+        ErrorCode::InvalidNftAmount,
+    );
+
+    let args = listing_args(&env, 500, 1);
+    assert_ok(env.send(&[ix::create_listing(&seller.pubkey(), 1, &payment, &listed, args).unwrap()], &[&seller]));
+    assert!(!env.escrow(&pda::listing(&seller.pubkey(), 1)).is_nft);
+
+    assert_ok(env.send(&[ix::fill_listing(&buyer.pubkey(), &seller.pubkey(), 1, &payment, &listed, &treasury).unwrap()], &[&buyer]));
+    assert_eq!(env.token_balance(&listed.ata(&buyer.pubkey()).unwrap()), 1);
+    assert_eq!(env.token_balance(&payment.ata(&seller.pubkey()).unwrap()), 495);
+}
+
+#[test]
+fn nft_listing_rejects_royalty_above_fee_headroom() {
+    let mut env = TestEnv::new();
+    let seller = env.create_user();
+    let payment = env.create_mint(&spl_token::ID, 6);
+    let listed = env.create_nft(&spl_token::ID, &seller.pubkey(), 9_500, Vec::new(), None);
+
+    // 版税加上最高手续费超过成交金额，成交时无法放款
+    let args = nft_listing_args(&env, 500);
+    assert_error(
+        env.send(&[ix::create_listing_nft(&seller.pubkey(), 1, &payment, &listed, args).unwrap()], &[&seller]),
+        ErrorCode::RoyaltyTooHigh,
+    );
+}
+
+#[test]
+fn permanent_delegate_asset_cannot_be_listed() {
+    let mut env = TestEnv::new();
+    let seller = env.create_user();
+    let delegate = env.create_user();
+    let payment = env.create_mint(&spl_token::ID, 6);
+    // 永久委托人可以直接转走托管账户中的挂单资产
+    let listed = env.create_permanent_delegate_mint(&delegate.pubkey(), 6);
+    env.fund_token_account(&listed, &seller.pubkey(), 5_000);
+
+    let args = listing_args(&env, 700, 5_000);
+    assert_error(
+        env.send(&[ix::create_listing(&seller.pubkey(), 1, &payment, &listed, args).unwrap()], &[&seller]),
+        ErrorCode::PermanentDelegateMint,
+    );
+}
+
+#[test]
+fn transfer_fee_asset_listing_records_received_amount() {
+    let mut env = TestEnv::new();
+    let seller = env.create_user();
+    let payment = env.create_mint(&spl_token::ID, 6);
+    // 1% 转账手续费，托管账户实际收到 4_950
+    let listed = env.create_transfer_fee_mint(100, 1_000_000);
+    env.fund_token_account(&listed, &seller.pubkey(), 5_000);
+
+    let args = listing_args(&env, 700, 5_000);
+    assert_ok(env.send(&[ix::create_listing(&seller.pubkey(), 1, &payment, &listed, args).unwrap()], &[&seller]));
+
+    let escrow = pda::listing(&seller.pubkey(), 1);
+    assert_eq!(env.escrow(&escrow).asset_amount, 4_950);
+    assert_eq!(env.token_balance(&listed.ata(&escrow).unwrap()), 4_950);
+
+    // 按实际托管数量退还，退还时再扣除 50 转账手续费
+    assert_ok(env.send(&[ix::cancel_listing(&seller.pubkey(), 1, &listed).unwrap()], &[&seller]));
+    assert!(!env.account_exists(&escrow));
+    assert_eq!(env.token_balance(&listed.ata(&seller.pubkey()).unwrap()), 4_900);
+    // 托管账户中预扣的手续费归集到mint后才能关闭
+    assert!(!env.account_exists(&listed.ata(&escrow).unwrap()));
+    assert_eq!(env.harvested_fee(&listed.mint().unwrap()), 50);
+}
+
+#[test]
+fn transfer_fee_asset_listing_can_be_filled() {
+    let mut env = TestEnv::with_config();
+    let seller = env.create_user();
+    let buyer = env.create_user();
+    let treasury = env.treasury.pubkey();
+    let payment = env.create_mint(&spl_token::ID, 6);
+    let listed = env.create_transfer_fee_mint(100, 1_000_000);
+    env.fund_token_account(&listed, &seller.pubkey(), 5_000);
+    env.fund_token_account(&listed, &buyer.pubkey(), 0);
+    env.fund_token_account(&payment, &buyer.pubkey(), 1_000);
+    env.fund_token_account(&payment, &seller.pubkey(), 0);
+    env.fund_token_account(&payment, &treasury, 0);
+
+    let args = listing_args(&env, 700, 5_000);
+    assert_ok(env.send(&[ix::create_listing(&seller.pubkey(), 1, &payment, &listed, args).unwrap()], &[&seller]));
+    assert_ok(env.send(&[ix::fill_listing(&buyer.pubkey(), &seller.pubkey(), 1, &payment, &listed, &treasury).unwrap()], &[&buyer]));
+
+    // 买家收到托管的 4_950，交付时再扣除 50 转账手续费
+    let escrow = pda::listing(&seller.pubkey(), 1);
+    assert!(!env.account_exists(&escrow));
+    assert!(!env.account_exists(&listed.ata(&escrow).unwrap()));
+    assert_eq!(env.token_balance(&listed.ata(&buyer.pubkey()).unwrap()), 4_900);
+    assert_eq!(env.harvested_fee(&listed.mint().unwrap()), 50);
+}
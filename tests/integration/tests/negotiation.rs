@@ -0,0 +1,228 @@
+use anchor_spl::token::spl_token;
+use sol_escrow::{error::ErrorCode, state::TransactionStatus};
+use sol_escrow_integration_tests::{
+    assert_error, assert_ok, ix, pda, Market, TestEnv, BUYER_BALANCE, DEFAULT_EXPIRATION,
+};
+use solana_sdk::signer::Signer;
+
+
+fn token_market(env: &mut TestEnv) -> Market {
+    let asset = env.create_mint(&spl_token::ID, 6);
+    env.market(asset)
+}
+
+
+#[test]
+fn amend_order_rebalances_funded_order() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+    let buyer_token_account = asset.ata(&buyer.pubkey()).unwrap();
+
+    let args = ix::order_args(1_000, env.now(), Some(seller.pubkey()));
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    // 提高金额时补足差额
+    let expiration = env.now() + 2 * DEFAULT_EXPIRATION;
+    assert_ok(env.send(&[ix::amend_order(&buyer.pubkey(), 1, asset, 1_500, expiration)], &[buyer]));
+    let escrow_data = env.escrow(&escrow);
+    assert_eq!(escrow_data.amount, 1_500);
+    assert_eq!(escrow_data.expiration, expiration);
+    assert_eq!(env.token_balance(&asset.ata(&escrow).unwrap()), 1_500);
+    assert_eq!(env.token_balance(&buyer_token_account), BUYER_BALANCE - 1_500);
+
+    // 降低金额时退还多余资金
+    assert_ok(env.send(&[ix::amend_order(&buyer.pubkey(), 1, asset, 800, expiration)], &[buyer]));
+    assert_eq!(env.escrow(&escrow).amount, 800);
+    assert_eq!(env.token_balance(&buyer_token_account), BUYER_BALANCE - 800);
+
+    assert_error(
+        env.send(&[ix::amend_order(&buyer.pubkey(), 1, asset, 800, env.now() + 10)], &[buyer]),
+        ErrorCode::ExpirationTooSoon,
+    );
+
+    // 卖家确认后不能再修改
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+    assert_error(
+        env.send(&[ix::amend_order(&buyer.pubkey(), 1, asset, 900, expiration)], &[buyer]),
+        ErrorCode::AmendNotAllowed,
+    );
+}
+
+#[test]
+fn amend_unfunded_order_moves_no_funds() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+
+    let args = ix::order_args(1_000, env.now(), Some(seller.pubkey()));
+    assert_ok(env.send(&[ix::create_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    let expiration = env.now() + DEFAULT_EXPIRATION;
+    assert_ok(env.send(&[ix::amend_order(&buyer.pubkey(), 1, asset, 2_000, expiration)], &[buyer]));
+    assert_eq!(env.escrow(&escrow).amount, 2_000);
+    assert_eq!(env.token_balance(&asset.ata(&escrow).unwrap()), 0);
+
+    // 付款时按修改后的金额存入
+    assert_ok(env.send(&[ix::buyer_payment(&buyer.pubkey(), 1, asset)], &[buyer]));
+    assert_eq!(env.token_balance(&asset.ata(&escrow).unwrap()), 2_000);
+}
+
+#[test]
+fn seller_confirmation_rejects_amended_terms() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+
+    let args = ix::order_args(1_000, env.now(), Some(seller.pubkey()));
+    let expiration = args.expiration;
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    // 卖家签名后买家抢先降低金额，按原条款的确认被拒绝
+    let confirmation = ix::seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1, 1_000, expiration);
+    assert_ok(env.send(&[ix::amend_order(&buyer.pubkey(), 1, asset, 500, expiration)], &[buyer]));
+    assert_error(env.send(&[confirmation], &[seller]), ErrorCode::OrderTermsChanged);
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Funded);
+
+    assert_ok(env.send(&[ix::seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1, 500, expiration)], &[seller]));
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::InTransit);
+}
+
+#[test]
+fn accepted_counter_offer_binds_seller() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let other_seller = env.create_user();
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+    let counter_offer = pda::counter_offer(&escrow, &seller.pubkey());
+
+    // 公开报价订单
+    let args = ix::order_args(1_000, env.now(), None);
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    let expiration = env.now() + DEFAULT_EXPIRATION;
+    assert_ok(env.send(&[ix::propose_counter(&seller.pubkey(), &buyer.pubkey(), 1, 1_200, expiration)], &[seller]));
+    assert!(env.account_exists(&counter_offer));
+
+    assert_ok(env.send(&[ix::accept_counter(&buyer.pubkey(), &seller.pubkey(), 1, asset, 1_200, expiration)], &[buyer]));
+
+    let escrow_data = env.escrow(&escrow);
+    assert_eq!(escrow_data.amount, 1_200);
+    assert_eq!(escrow_data.seller, Some(seller.pubkey()));
+    assert!(!escrow_data.open_offer);
+    assert_eq!(escrow_data.status, TransactionStatus::Funded);
+    assert_eq!(env.token_balance(&asset.ata(&escrow).unwrap()), 1_200);
+    assert!(!env.account_exists(&counter_offer));
+
+    // 绑定卖家后其他卖家不能确认或还价
+    assert_error(
+        env.send(&[env.seller_confirmation(&other_seller.pubkey(), &buyer.pubkey(), 1)], &[&other_seller]),
+        ErrorCode::InvalidSeller,
+    );
+    assert_error(
+        env.send(&[ix::propose_counter(&other_seller.pubkey(), &buyer.pubkey(), 1, 900, expiration)], &[&other_seller]),
+        ErrorCode::InvalidSeller,
+    );
+}
+
+#[test]
+fn counter_offer_rejected_after_confirmation() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let Market { buyer, seller, asset } = &market;
+
+    let args = ix::order_args(1_000, env.now(), Some(seller.pubkey()));
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+
+    let expiration = env.now() + DEFAULT_EXPIRATION;
+    assert_error(
+        env.send(&[ix::propose_counter(&seller.pubkey(), &buyer.pubkey(), 1, 1_200, expiration)], &[seller]),
+        ErrorCode::CounterNotAllowed,
+    );
+}
+
+#[test]
+fn changed_counter_offer_cannot_be_accepted() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let Market { buyer, seller, asset } = &market;
+
+    let args = ix::order_args(1_000, env.now(), None);
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    let expiration = env.now() + DEFAULT_EXPIRATION;
+    assert_ok(env.send(&[ix::propose_counter(&seller.pubkey(), &buyer.pubkey(), 1, 1_200, expiration)], &[seller]));
+    // 买家签名前卖家覆盖了还价
+    assert_ok(env.send(&[ix::propose_counter(&seller.pubkey(), &buyer.pubkey(), 1, 1_500, expiration)], &[seller]));
+
+    assert_error(
+        env.send(&[ix::accept_counter(&buyer.pubkey(), &seller.pubkey(), 1, asset, 1_200, expiration)], &[buyer]),
+        ErrorCode::CounterOfferChanged,
+    );
+    assert_error(
+        env.send(&[ix::accept_counter(&buyer.pubkey(), &seller.pubkey(), 1, asset, 1_500, expiration - 1)], &[buyer]),
+        ErrorCode::CounterOfferChanged,
+    );
+    assert_eq!(env.escrow(&pda::order(&buyer.pubkey(), 1)).amount, 1_000);
+}
+
+#[test]
+fn buyer_rejects_counter_offer() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let attacker = env.create_user();
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+    let counter_offer = pda::counter_offer(&escrow, &seller.pubkey());
+
+    let args = ix::order_args(1_000, env.now(), None);
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    let expiration = env.now() + DEFAULT_EXPIRATION;
+    assert_ok(env.send(&[ix::propose_counter(&seller.pubkey(), &buyer.pubkey(), 1, 1_200, expiration)], &[seller]));
+
+    // 只有卖家与买家可以关闭还价
+    assert_error(
+        env.send(&[ix::close_counter(&attacker.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1)], &[&attacker]),
+        ErrorCode::Unauthorized,
+    );
+
+    let seller_lamports = env.lamports(&seller.pubkey());
+    let counter_rent = env.lamports(&counter_offer);
+    assert_ok(env.send(&[ix::close_counter(&buyer.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1)], &[buyer]));
+
+    // 租金退还卖家，订单保持不变
+    assert!(!env.account_exists(&counter_offer));
+    assert_eq!(env.lamports(&seller.pubkey()), seller_lamports + counter_rent);
+    assert_eq!(env.escrow(&escrow).amount, 1_000);
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Funded);
+}
+
+#[test]
+fn seller_reclaims_counter_offer_after_order_closed() {
+    let mut env = TestEnv::with_config();
+    let market = token_market(&mut env);
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+    let counter_offer = pda::counter_offer(&escrow, &seller.pubkey());
+
+    let args = ix::order_args(1_000, env.now(), None);
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    let expiration = env.now() + DEFAULT_EXPIRATION;
+    assert_ok(env.send(&[ix::propose_counter(&seller.pubkey(), &buyer.pubkey(), 1, 1_200, expiration)], &[seller]));
+
+    // 买家取消并关闭订单后，还价账户仍然存在
+    assert_ok(env.send(&[ix::order_cancellation(&buyer.pubkey(), 1, asset)], &[buyer]));
+    assert_ok(env.send(&[ix::close_order(&buyer.pubkey(), 1, asset)], &[buyer]));
+    assert!(!env.account_exists(&escrow));
+    assert!(env.account_exists(&counter_offer));
+
+    assert_ok(env.send(&[ix::close_counter(&seller.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1)], &[seller]));
+    assert!(!env.account_exists(&counter_offer));
+}
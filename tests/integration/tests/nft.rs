@@ -0,0 +1,278 @@
+use anchor_spl::{metadata::mpl_token_metadata::types::{Collection, Creator}, token::spl_token, token_2022::spl_token_2022};
+use sol_escrow::{error::ErrorCode, state::TransactionStatus};
+use sol_escrow_client::CreateOrderArgs;
+use sol_escrow_integration_tests::{assert_error, assert_ok, ix, pda, Asset, Market, TestEnv};
+use solana_sdk::{instruction::AccountMeta, pubkey::Pubkey, signer::Signer};
+
+
+// 指定NFT的订单参数，NFT交付到买家的关联代币账户
+fn nft_order_args(env: &TestEnv, amount: u64, seller: &Pubkey, buyer: &Pubkey, nft: &Asset) -> CreateOrderArgs {
+    let mut args = ix::order_args(amount, env.now(), Some(*seller));
+    args.is_nft = true;
+    args.nft_mint = nft.mint();
+    args.buyer_nft_account = nft.ata(buyer);
+    args
+}
+
+// 集合订单参数，买家NFT账户在卖家确认时确定
+fn collection_order_args(env: &TestEnv, amount: u64, collection_mint: &Pubkey) -> CreateOrderArgs {
+    let mut args = ix::order_args(amount, env.now(), None);
+    args.is_nft = true;
+    args.collection_mint = Some(*collection_mint);
+    args
+}
+
+fn seller_nft(market: &Market, nft: &Asset) -> ix::SellerNft {
+    ix::SellerNft {
+        mint: nft.mint().unwrap(),
+        token_program: nft.program(),
+        seller_nft_account: nft.ata(&market.seller.pubkey()).unwrap(),
+        buyer_nft_account: nft.ata(&market.buyer.pubkey()).unwrap(),
+    }
+}
+
+fn nft_transfer(nft: &Asset, destination: &Pubkey) -> ix::NftTransfer {
+    ix::NftTransfer {
+        mint: nft.mint().unwrap(),
+        token_program: nft.program(),
+        destination: nft.ata(destination).unwrap(),
+    }
+}
+
+
+#[test]
+fn token_2022_nft_delivered_for_spl_payment() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let Market { buyer, seller, asset } = &market;
+    let treasury = env.treasury.pubkey();
+
+    // 计价代币属于 SPL Token，NFT 属于 Token-2022
+    let nft = env.create_nft(&spl_token_2022::ID, &seller.pubkey(), 0, Vec::new(), None);
+    env.fund_token_account(&nft, &buyer.pubkey(), 0);
+
+    let args = nft_order_args(&env, 1_000, &seller.pubkey(), &buyer.pubkey(), &nft);
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_ok(env.send(&[env.seller_confirmation_nft(&seller.pubkey(), &buyer.pubkey(), 1, &seller_nft(&market, &nft))], &[seller]));
+
+    let escrow = pda::order(&buyer.pubkey(), 1);
+    let nft_vault = nft.ata(&escrow).unwrap();
+    assert_eq!(env.escrow(&escrow).nft_vault, Some(nft_vault));
+    assert_eq!(env.token_balance(&nft_vault), 1);
+
+    let delivery = nft_transfer(&nft, &buyer.pubkey());
+    assert_ok(env.send(
+        &[ix::escrow_release_nft(&buyer.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, &delivery)],
+        &[buyer],
+    ));
+
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Success);
+    assert_eq!(env.token_balance(&nft.ata(&buyer.pubkey()).unwrap()), 1);
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 990);
+    assert!(!env.account_exists(&nft_vault));
+}
+
+#[test]
+fn token_2022_nft_returned_when_dispute_refunds_buyer() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let arbitrator = env.create_user();
+    let Market { buyer, seller, asset } = &market;
+
+    let nft = env.create_nft(&spl_token_2022::ID, &seller.pubkey(), 0, Vec::new(), None);
+    env.fund_token_account(&nft, &buyer.pubkey(), 0);
+
+    let mut args = nft_order_args(&env, 1_000, &seller.pubkey(), &buyer.pubkey(), &nft);
+    args.arbitrator = Some(arbitrator.pubkey());
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_ok(env.send(&[env.seller_confirmation_nft(&seller.pubkey(), &buyer.pubkey(), 1, &seller_nft(&market, &nft))], &[seller]));
+    assert_ok(env.send(&[ix::open_dispute(&buyer.pubkey(), &buyer.pubkey(), 1)], &[buyer]));
+
+    // 全额退款时NFT退还卖家
+    let treasury = env.treasury.pubkey();
+    let refund = nft_transfer(&nft, &seller.pubkey());
+    assert_ok(env.send(
+        &[ix::resolve_dispute_nft(&arbitrator.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, 0, &refund)],
+        &[&arbitrator],
+    ));
+
+    let escrow = pda::order(&buyer.pubkey(), 1);
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Cancelled);
+    assert_eq!(env.token_balance(&nft.ata(&seller.pubkey()).unwrap()), 1);
+    assert!(!env.account_exists(&nft.ata(&escrow).unwrap()));
+}
+
+#[test]
+fn dispute_split_pays_nft_royalty() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let arbitrator = env.create_user();
+    let creator = env.create_user();
+    let Market { buyer, seller, asset } = &market;
+    let treasury = env.treasury.pubkey();
+    let creator_account = env.fund_token_account(asset, &creator.pubkey(), 0);
+
+    // 5% 版税，唯一的已验证创作者获得全部份额
+    let creators = vec![Creator { address: creator.pubkey(), verified: true, share: 100 }];
+    let nft = env.create_nft(&spl_token::ID, &seller.pubkey(), 500, creators, None);
+    env.fund_token_account(&nft, &buyer.pubkey(), 0);
+
+    let mut args = nft_order_args(&env, 1_000, &seller.pubkey(), &buyer.pubkey(), &nft);
+    args.arbitrator = Some(arbitrator.pubkey());
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_ok(env.send(&[env.seller_confirmation_nft(&seller.pubkey(), &buyer.pubkey(), 1, &seller_nft(&market, &nft))], &[seller]));
+    assert_ok(env.send(&[ix::open_dispute(&buyer.pubkey(), &buyer.pubkey(), 1)], &[buyer]));
+
+    // 五五分账：卖家所得 500 扣除 1% 手续费与 5% 版税，NFT 交付买家
+    let delivery = nft_transfer(&nft, &buyer.pubkey());
+    let mut resolve = ix::resolve_dispute_nft(&arbitrator.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, 5_000, &delivery);
+    resolve.accounts.push(AccountMeta::new(creator_account, false));
+    assert_ok(env.send(&[resolve], &[&arbitrator]));
+
+    assert_eq!(env.token_balance(&creator_account), 25);
+    assert_eq!(env.token_balance(&asset.ata(&treasury).unwrap()), 5);
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 470);
+    assert_eq!(env.token_balance(&nft.ata(&buyer.pubkey()).unwrap()), 1);
+}
+
+#[test]
+fn dispute_royalty_requires_creator_accounts() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let arbitrator = env.create_user();
+    let creator = env.create_user();
+    let Market { buyer, seller, asset } = &market;
+    let treasury = env.treasury.pubkey();
+
+    let creators = vec![Creator { address: creator.pubkey(), verified: true, share: 100 }];
+    let nft = env.create_nft(&spl_token::ID, &seller.pubkey(), 500, creators, None);
+    env.fund_token_account(&nft, &buyer.pubkey(), 0);
+
+    let mut args = nft_order_args(&env, 1_000, &seller.pubkey(), &buyer.pubkey(), &nft);
+    args.arbitrator = Some(arbitrator.pubkey());
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_ok(env.send(&[env.seller_confirmation_nft(&seller.pubkey(), &buyer.pubkey(), 1, &seller_nft(&market, &nft))], &[seller]));
+    assert_ok(env.send(&[ix::open_dispute(&buyer.pubkey(), &buyer.pubkey(), 1)], &[buyer]));
+
+    // 卖家获得款项时不能绕过版税
+    let delivery = nft_transfer(&nft, &buyer.pubkey());
+    assert_error(
+        env.send(
+            &[ix::resolve_dispute_nft(&arbitrator.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, 5_000, &delivery)],
+            &[&arbitrator],
+        ),
+        ErrorCode::MissingCreatorAccount,
+    );
+}
+
+#[test]
+fn collection_order_delivers_verified_nft_and_pays_royalty() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let creator = env.create_user();
+    let Market { buyer, seller, asset } = &market;
+    let treasury = env.treasury.pubkey();
+    let creator_account = env.fund_token_account(asset, &creator.pubkey(), 0);
+
+    // 买家对整个集合出价，任意卖家可用集合内已验证的NFT成交
+    let collection_mint = env.create_nft(&spl_token::ID, &env.admin.pubkey(), 0, Vec::new(), None).mint().unwrap();
+    let creators = vec![Creator { address: creator.pubkey(), verified: true, share: 100 }];
+    let collection = Collection { verified: true, key: collection_mint };
+    let nft = env.create_nft(&spl_token::ID, &seller.pubkey(), 500, creators, Some(collection));
+    env.fund_token_account(&nft, &buyer.pubkey(), 0);
+
+    let args = collection_order_args(&env, 1_000, &collection_mint);
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_ok(env.send(&[env.seller_confirmation_nft(&seller.pubkey(), &buyer.pubkey(), 1, &seller_nft(&market, &nft))], &[seller]));
+
+    let escrow = pda::order(&buyer.pubkey(), 1);
+    let state = env.escrow(&escrow);
+    assert_eq!(state.nft_mint, nft.mint());
+    assert_eq!(state.buyer_nft_account, nft.ata(&buyer.pubkey()));
+    assert_eq!(state.royalty_bps, 500);
+
+    // 放款时 1% 手续费与 5% 版税从卖家所得中扣除
+    let delivery = nft_transfer(&nft, &buyer.pubkey());
+    let mut release = ix::escrow_release_nft(&buyer.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, &delivery);
+    release.accounts.push(AccountMeta::new(creator_account, false));
+    assert_ok(env.send(&[release], &[buyer]));
+
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Success);
+    assert_eq!(env.token_balance(&nft.ata(&buyer.pubkey()).unwrap()), 1);
+    assert_eq!(env.token_balance(&creator_account), 50);
+    assert_eq!(env.token_balance(&asset.ata(&treasury).unwrap()), 10);
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 940);
+}
+
+#[test]
+fn seller_confirmation_rejects_mismatched_nft_token_program() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let Market { buyer, seller, asset } = &market;
+
+    let nft = env.create_nft(&spl_token_2022::ID, &seller.pubkey(), 0, Vec::new(), None);
+    env.fund_token_account(&nft, &buyer.pubkey(), 0);
+
+    let args = nft_order_args(&env, 1_000, &seller.pubkey(), &buyer.pubkey(), &nft);
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    // Token-2022 NFT 按 SPL Token 程序存入托管被拒绝
+    let mut deposit = seller_nft(&market, &nft);
+    deposit.token_program = spl_token::ID;
+    assert_error(
+        env.send(&[env.seller_confirmation_nft(&seller.pubkey(), &buyer.pubkey(), 1, &deposit)], &[seller]),
+        ErrorCode::InvalidNftTokenProgram,
+    );
+    assert_eq!(env.escrow(&pda::order(&buyer.pubkey(), 1)).status, TransactionStatus::Funded);
+}
+
+#[test]
+fn seller_confirmation_rejects_royalty_above_fee_headroom() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let Market { buyer, seller, asset } = &market;
+
+    // 95% 版税加上最高手续费超过放款金额，确认后订单将无法放款或裁决
+    let nft = env.create_nft(&spl_token::ID, &seller.pubkey(), 9_500, Vec::new(), None);
+    env.fund_token_account(&nft, &buyer.pubkey(), 0);
+
+    let args = nft_order_args(&env, 1_000, &seller.pubkey(), &buyer.pubkey(), &nft);
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_error(
+        env.send(&[env.seller_confirmation_nft(&seller.pubkey(), &buyer.pubkey(), 1, &seller_nft(&market, &nft))], &[seller]),
+        ErrorCode::RoyaltyTooHigh,
+    );
+    assert_eq!(env.escrow(&pda::order(&buyer.pubkey(), 1)).status, TransactionStatus::Funded);
+}
+
+#[test]
+fn collection_order_rejects_unverified_nft() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let Market { buyer, seller, asset } = &market;
+
+    // 元数据声明属于该集合但未经集合验证的NFT不能成交
+    let collection_mint = env.create_nft(&spl_token::ID, &env.admin.pubkey(), 0, Vec::new(), None).mint().unwrap();
+    let collection = Collection { verified: false, key: collection_mint };
+    let nft = env.create_nft(&spl_token::ID, &seller.pubkey(), 0, Vec::new(), Some(collection));
+    env.fund_token_account(&nft, &buyer.pubkey(), 0);
+
+    let args = collection_order_args(&env, 1_000, &collection_mint);
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_error(
+        env.send(&[env.seller_confirmation_nft(&seller.pubkey(), &buyer.pubkey(), 1, &seller_nft(&market, &nft))], &[seller]),
+        ErrorCode::UnverifiedCollection,
+    );
+
+    let escrow = pda::order(&buyer.pubkey(), 1);
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Funded);
+    assert_eq!(env.token_balance(&nft.ata(&seller.pubkey()).unwrap()), 1);
+}
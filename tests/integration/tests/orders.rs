@@ -0,0 +1,327 @@
+use anchor_spl::{token::spl_token, token_2022::spl_token_2022};
+use sol_escrow::{
+    error::ErrorCode,
+    instructions::MilestoneArgs,
+    state::TransactionStatus,
+};
+use sol_escrow_integration_tests::{assert_error, assert_ok, ix, pda, Asset, Market, TestEnv, BUYER_BALANCE};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, signer::Signer};
+
+
+// 创建并付款的指定卖家订单，卖家已确认
+fn confirmed_order(env: &mut TestEnv, market: &Market, order_id: u64, amount: u64) {
+    let Market { buyer, seller, asset } = market;
+    let args = ix::order_args(amount, env.now(), Some(seller.pubkey()));
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), order_id, asset, args)], &[buyer]));
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), order_id)], &[seller]));
+}
+
+
+#[test]
+fn spl_token_order_lifecycle() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let Market { buyer, seller, asset } = &market;
+    let treasury = env.treasury.pubkey();
+    let escrow = pda::order(&buyer.pubkey(), 1);
+
+    let args = ix::order_args(10_000, env.now(), Some(seller.pubkey()));
+    assert_ok(env.send(&[ix::create_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Created);
+
+    // 付款前卖家不能确认
+    assert_error(
+        env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]),
+        ErrorCode::SellerConfirmationNotAllowed,
+    );
+
+    assert_ok(env.send(&[ix::buyer_payment(&buyer.pubkey(), 1, asset)], &[buyer]));
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Funded);
+    assert_eq!(env.token_balance(&asset.ata(&escrow).unwrap()), 10_000);
+    assert_eq!(env.token_balance(&asset.ata(&buyer.pubkey()).unwrap()), BUYER_BALANCE - 10_000);
+
+    // 确认前不能放款
+    assert_error(
+        env.send(&[ix::escrow_release(&buyer.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury)], &[buyer]),
+        ErrorCode::FundsReleaseNotAllowed,
+    );
+
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::InTransit);
+
+    // 已结束前不能关闭订单
+    assert_error(env.send(&[ix::close_order(&buyer.pubkey(), 1, asset)], &[buyer]), ErrorCode::OrderNotSettled);
+
+    assert_ok(env.send(&[ix::escrow_release(&buyer.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury)], &[buyer]));
+
+    let escrow_data = env.escrow(&escrow);
+    assert_eq!(escrow_data.status, TransactionStatus::Success);
+    assert_eq!(escrow_data.released_amount, 10_000);
+    // 默认手续费 1%
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 9_900);
+    assert_eq!(env.token_balance(&asset.ata(&treasury).unwrap()), 100);
+
+    assert_ok(env.send(&[ix::close_order(&buyer.pubkey(), 1, asset)], &[buyer]));
+    assert!(!env.account_exists(&escrow));
+    assert!(!env.account_exists(&asset.ata(&escrow).unwrap()));
+}
+
+#[test]
+fn release_requires_authority() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let attacker = env.create_user();
+    let treasury = env.treasury.pubkey();
+    confirmed_order(&mut env, &market, 1, 1_000);
+    let Market { buyer, seller, asset } = &market;
+
+    assert_error(
+        env.send(&[ix::escrow_release(&attacker.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury)], &[&attacker]),
+        ErrorCode::Unauthorized,
+    );
+    // 未设置自动放款的订单卖家不能自行领取
+    assert_error(
+        env.send(&[ix::escrow_release(&seller.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury)], &[seller]),
+        ErrorCode::ReleaseNotReady,
+    );
+}
+
+#[test]
+fn seller_cannot_confirm_someone_elses_order() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let attacker = env.create_user();
+    let Market { buyer, seller, asset } = &market;
+
+    let args = ix::order_args(1_000, env.now(), Some(seller.pubkey()));
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    assert_error(
+        env.send(&[env.seller_confirmation(&attacker.pubkey(), &buyer.pubkey(), 1)], &[&attacker]),
+        ErrorCode::InvalidSeller,
+    );
+}
+
+#[test]
+fn mint_fee_overrides_default_fee() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let admin = env.admin.insecure_clone();
+    let treasury = env.treasury.pubkey();
+    assert_ok(env.send(&[ix::set_mint_fee(&admin.pubkey(), &asset.mint().unwrap(), 250)], &[&admin]));
+
+    let market = env.market(asset);
+    confirmed_order(&mut env, &market, 1, 10_000);
+    let Market { buyer, seller, asset } = &market;
+
+    assert_ok(env.send(&[ix::escrow_release(&buyer.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury)], &[buyer]));
+
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 9_750);
+    assert_eq!(env.token_balance(&asset.ata(&treasury).unwrap()), 250);
+}
+
+#[test]
+fn token_2022_order_lifecycle() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token_2022::ID, 6);
+    let market = env.market(asset);
+    let treasury = env.treasury.pubkey();
+    confirmed_order(&mut env, &market, 1, 10_000);
+    let Market { buyer, seller, asset } = &market;
+
+    assert_ok(env.send(&[ix::escrow_release(&buyer.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury)], &[buyer]));
+
+    assert_eq!(env.escrow(&pda::order(&buyer.pubkey(), 1)).status, TransactionStatus::Success);
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 9_900);
+    assert_eq!(env.token_balance(&asset.ata(&treasury).unwrap()), 100);
+}
+
+#[test]
+fn transfer_fee_mint_records_received_amount() {
+    let mut env = TestEnv::with_config();
+    // 1% 转账手续费
+    let asset = env.create_transfer_fee_mint(100, u64::MAX);
+    let market = env.market(asset);
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+
+    let args = ix::order_args(10_000, env.now(), Some(seller.pubkey()));
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    // 订单按托管账户实际到账金额记账
+    let escrow_data = env.escrow(&escrow);
+    assert_eq!(escrow_data.status, TransactionStatus::Funded);
+    assert_eq!(escrow_data.amount, 9_900);
+    assert_eq!(env.token_balance(&asset.ata(&escrow).unwrap()), 9_900);
+    assert_eq!(env.withheld_fee(&asset.ata(&escrow).unwrap()), 100);
+
+    // 带转账手续费的代币不支持里程碑
+    let mut args = ix::order_args(10_000, env.now(), Some(seller.pubkey()));
+    args.milestones = vec![
+        MilestoneArgs { amount: 4_000, deadline: None },
+        MilestoneArgs { amount: 6_000, deadline: None },
+    ];
+    assert_error(
+        env.send(&[ix::create_order(&buyer.pubkey(), 2, asset, args)], &[buyer]),
+        ErrorCode::InvalidMilestones,
+    );
+}
+
+#[test]
+fn transfer_fee_order_closes_after_release() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_transfer_fee_mint(100, u64::MAX);
+    let market = env.market(asset);
+    let Market { buyer, seller, asset } = &market;
+    let treasury = env.treasury.pubkey();
+    let escrow = pda::order(&buyer.pubkey(), 1);
+    let escrow_vault = asset.ata(&escrow).unwrap();
+
+    confirmed_order(&mut env, &market, 1, 10_000);
+    assert_ok(env.send(&[ix::escrow_release(&buyer.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury)], &[buyer]));
+    assert_eq!(env.token_balance(&escrow_vault), 0);
+    assert_eq!(env.withheld_fee(&escrow_vault), 100);
+
+    // 托管账户中预扣的手续费归集到mint后关闭
+    assert_ok(env.send(&[ix::close_order(&buyer.pubkey(), 1, asset)], &[buyer]));
+    assert!(!env.account_exists(&escrow));
+    assert!(!env.account_exists(&escrow_vault));
+    assert_eq!(env.harvested_fee(&asset.mint().unwrap()), 100);
+}
+
+#[test]
+fn native_sol_order_lifecycle() {
+    let mut env = TestEnv::with_config();
+    let market = env.market(Asset::Native);
+    let treasury = env.treasury.pubkey();
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+
+    let args = ix::order_args(LAMPORTS_PER_SOL, env.now(), Some(seller.pubkey()));
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+
+    let escrow_data = env.escrow(&escrow);
+    assert!(escrow_data.is_native);
+    assert_eq!(escrow_data.escrow_vault, escrow);
+    let escrow_lamports = env.lamports(&escrow);
+
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+
+    let seller_before = env.lamports(&seller.pubkey());
+    let treasury_before = env.lamports(&treasury);
+    assert_ok(env.send(&[ix::escrow_release(&buyer.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury)], &[buyer]));
+
+    assert_eq!(env.escrow(&escrow).status, TransactionStatus::Success);
+    assert_eq!(env.lamports(&seller.pubkey()) - seller_before, LAMPORTS_PER_SOL / 100 * 99);
+    assert_eq!(env.lamports(&treasury) - treasury_before, LAMPORTS_PER_SOL / 100);
+    // 托管PDA只保留租金
+    assert_eq!(env.lamports(&escrow), escrow_lamports - LAMPORTS_PER_SOL);
+
+    let buyer_before = env.lamports(&buyer.pubkey());
+    assert_ok(env.send(&[ix::close_order(&buyer.pubkey(), 1, asset)], &[buyer]));
+    assert!(!env.account_exists(&escrow));
+    assert!(env.lamports(&buyer.pubkey()) > buyer_before);
+}
+
+#[test]
+fn milestones_release_in_parts() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let treasury = env.treasury.pubkey();
+    let Market { buyer, seller, asset } = &market;
+    let escrow = pda::order(&buyer.pubkey(), 1);
+
+    let mut args = ix::order_args(1_000, env.now(), Some(seller.pubkey()));
+    args.milestones = vec![
+        MilestoneArgs { amount: 400, deadline: None },
+        MilestoneArgs { amount: 600, deadline: None },
+    ];
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+
+    let release = |index| ix::release_milestone(&buyer.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, index);
+
+    assert_ok(env.send(&[release(0)], &[buyer]));
+    let escrow_data = env.escrow(&escrow);
+    assert_eq!(escrow_data.status, TransactionStatus::InTransit);
+    assert_eq!(escrow_data.released_amount, 400);
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 396);
+
+    assert_error(env.send(&[release(0)], &[buyer]), ErrorCode::MilestoneAlreadyReleased);
+    assert_error(env.send(&[release(2)], &[buyer]), ErrorCode::InvalidMilestoneIndex);
+
+    assert_ok(env.send(&[release(1)], &[buyer]));
+    let escrow_data = env.escrow(&escrow);
+    assert_eq!(escrow_data.status, TransactionStatus::Success);
+    assert_eq!(escrow_data.released_amount, 1_000);
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 990);
+    assert_eq!(env.token_balance(&asset.ata(&treasury).unwrap()), 10);
+}
+
+#[test]
+fn seller_claims_milestone_after_deadline() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let treasury = env.treasury.pubkey();
+    let Market { buyer, seller, asset } = &market;
+    let now = env.now();
+
+    let mut args = ix::order_args(1_000, now, Some(seller.pubkey()));
+    args.milestones = vec![
+        MilestoneArgs { amount: 400, deadline: Some(now + 600) },
+        MilestoneArgs { amount: 600, deadline: None },
+    ];
+    assert_ok(env.send(&[ix::create_and_fund_order(&buyer.pubkey(), 1, asset, args)], &[buyer]));
+    assert_ok(env.send(&[env.seller_confirmation(&seller.pubkey(), &buyer.pubkey(), 1)], &[seller]));
+
+    let claim = |index| ix::release_milestone(&seller.pubkey(), &buyer.pubkey(), &seller.pubkey(), 1, asset, &treasury, index);
+
+    // 截止时间之前只有买家可以放款
+    assert_error(env.send(&[claim(0)], &[seller]), ErrorCode::ReleaseNotReady);
+
+    env.warp(600);
+    assert_ok(env.send(&[claim(0)], &[seller]));
+    let escrow_data = env.escrow(&pda::order(&buyer.pubkey(), 1));
+    assert!(escrow_data.milestones[0].released);
+    assert_eq!(escrow_data.released_amount, 400);
+    assert_eq!(env.token_balance(&asset.ata(&seller.pubkey()).unwrap()), 396);
+
+    // 没有截止时间的里程碑仍需买家放款
+    assert_error(env.send(&[claim(1)], &[seller]), ErrorCode::ReleaseNotReady);
+}
+
+#[test]
+fn create_order_validates_terms() {
+    let mut env = TestEnv::with_config();
+    let asset = env.create_mint(&spl_token::ID, 6);
+    let market = env.market(asset);
+    let Market { buyer, seller, asset } = &market;
+    let now = env.now();
+
+    let mut args = ix::order_args(1_000, now, Some(seller.pubkey()));
+    args.expiration = now + 10;
+    assert_error(env.send(&[ix::create_order(&buyer.pubkey(), 1, asset, args)], &[buyer]), ErrorCode::ExpirationTooSoon);
+
+    let args = ix::order_args(0, now, Some(seller.pubkey()));
+    assert_error(env.send(&[ix::create_order(&buyer.pubkey(), 1, asset, args)], &[buyer]), ErrorCode::AmountZero);
+
+    // 指定卖家与公开报价二选一
+    let mut args = ix::order_args(1_000, now, Some(seller.pubkey()));
+    args.open_offer = true;
+    assert_error(
+        env.send(&[ix::create_order(&buyer.pubkey(), 1, asset, args)], &[buyer]),
+        ErrorCode::InvalidSellerSelection,
+    );
+
+    let mut args = ix::order_args(1_000, now, Some(seller.pubkey()));
+    args.release_delay = Some(0);
+    assert_error(
+        env.send(&[ix::create_order(&buyer.pubkey(), 1, asset, args)], &[buyer]),
+        ErrorCode::InvalidReleaseDelay,
+    );
+}
@@ -1,14 +1,21 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { SolEscrow } from "../target/types/sol_escrow";
-import { Keypair, PublicKey } from "@solana/web3.js";
+import { Keypair, PublicKey, SystemProgram } from "@solana/web3.js";
 import {
   TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
+  ExtensionType,
+  getMintLen,
+  createInitializeMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  createInitializeNonTransferableMintInstruction,
   createMint,
   mintTo,
   getOrCreateAssociatedTokenAccount,
   Account,
-  transfer
+  transfer,
+  getAssociatedTokenAddressSync
 } from "@solana/spl-token";
 import { createUmi } from "@metaplex-foundation/umi-bundle-defaults";
 import { generateSigner, percentAmount, KeypairSigner, some, keypairIdentity } from "@metaplex-foundation/umi";
@@ -17,11 +24,14 @@ import assert from "assert";
 
 
 describe("sol-escrow", () => {
-  let Created = 0;
-  let Funded = 1;
-  let Cancelled = 2;
-  let Success = 3;
-  let Expired = 4;
+  // 订单状态以枚举形式存储，客户端解码为 { variant: {} }
+  const Created = { created: {} };
+  const Funded = { funded: {} };
+  const InTransit = { inTransit: {} };
+  const Success = { success: {} };
+  const Cancelled = { cancelled: {} };
+  const Disputed = { disputed: {} };
+  const Expired = { expired: {} };
   
   const provider = anchor.AnchorProvider.env()
   anchor.setProvider(provider);
@@ -49,6 +59,61 @@ describe("sol-escrow", () => {
   // 设置 Umi 的身份为 umiSigner
   umi.use(keypairIdentity(umiSigner));
 
+  // 订单编号，参与托管账户PDA派生
+  const orderId = new anchor.BN(1);
+
+  function getEscrowAddress(buyer: PublicKey, id: anchor.BN): PublicKey {
+    const [escrowAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("order"), buyer.toBuffer(), id.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    return escrowAddress;
+  }
+
+  // 卖家确认的订单条款：订单当前的金额与超时时间
+  async function getOrderTerms(buyer: PublicKey, id: anchor.BN): Promise<[anchor.BN, anchor.BN]> {
+    const escrow = await program.account.escrow.fetch(getEscrowAddress(buyer, id));
+    return [escrow.amount, escrow.expiration];
+  }
+
+  // 无权操作订单的第三方
+  const attacker = Keypair.generate();
+  // 争议订单的仲裁者
+  const arbitrator = Keypair.generate();
+  // 协议手续费收款方，默认手续费 1%
+  const treasury = Keypair.generate();
+  const feeBps = 100;
+  let treasuryToken: Account;
+
+  // 断言交易因程序错误失败
+  async function expectAnchorError(promise: Promise<unknown>, code: string) {
+    try {
+      await promise;
+    } catch (err) {
+      assert(err instanceof anchor.AnchorError, `应返回程序错误 ${code}`);
+      assert.strictEqual(err.error.errorCode.code, code);
+      return;
+    }
+    assert.fail(`交易应失败: ${code}`);
+  }
+
+  // 仅由攻击者签名发送指令，缺少买家签名的交易必须被拒绝
+  async function expectMissingSignature(ix: anchor.web3.TransactionInstruction) {
+    const tx = new anchor.web3.Transaction().add(ix);
+    tx.feePayer = attacker.publicKey;
+    tx.recentBlockhash = (await connection.getLatestBlockhash()).blockhash;
+    tx.partialSign(attacker);
+    await assert.rejects(
+      connection.sendRawTransaction(tx.serialize({ requireAllSignatures: false })),
+      "缺少买家签名的交易不应成功"
+    );
+  }
+
+  async function getTokenAmount(tokenAccount: PublicKey): Promise<number> {
+    const balanceInfo = await connection.getTokenAccountBalance(tokenAccount);
+    return Number(balanceInfo.value.amount);
+  }
+
   async function getTokenBalance(tokenAccount: PublicKey): Promise<number> {
     const balanceInfo = await connection.getTokenAccountBalance(tokenAccount);
     return balanceInfo.value.uiAmount!;
@@ -103,7 +168,8 @@ describe("sol-escrow", () => {
       mint: nft1,
       name: "test NFT 1",
       uri: "https://example.com/nft1.json",
-      sellerFeeBasisPoints: percentAmount(0),
+      // 5% 版税，创作者默认为铸造者（payer）
+      sellerFeeBasisPoints: percentAmount(5),
       collection: some({
         key: collectionMint.publicKey,
         verified: false
@@ -198,6 +264,27 @@ describe("sol-escrow", () => {
     );
 
     console.log("✅ NFT 已转移到卖家账户");
+
+    // 为攻击者准备手续费与代币账户
+    const sig = await connection.requestAirdrop(attacker.publicKey, anchor.web3.LAMPORTS_PER_SOL);
+    await connection.confirmTransaction(sig);
+    await getOrCreateAssociatedTokenAccount(connection, payer, mint, attacker.publicKey);
+    await getOrCreateAssociatedTokenAccount(connection, payer, mint, seller.publicKey);
+
+    // 初始化协议手续费配置，程序升级权限持有者为管理员
+    treasuryToken = await getOrCreateAssociatedTokenAccount(connection, payer, mint, treasury.publicKey);
+    const [programData] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    await program.methods.initializeConfig(treasury.publicKey, feeBps).accounts({
+      admin: payer.publicKey,
+      programData,
+    }).rpc();
+
+    // 卖家挂单需要支付账户租金
+    const sellerSig = await connection.requestAirdrop(seller.publicKey, anchor.web3.LAMPORTS_PER_SOL);
+    await connection.confirmTransaction(sellerSig);
   });
 
   it("Is create order", async () => {
@@ -212,67 +299,120 @@ describe("sol-escrow", () => {
 
     const now = Math.floor(Date.now() / 1000);
 
-    await program.methods.createOrder(
-      new anchor.BN(1000),
-      new anchor.BN(now + 3600),
-      new PublicKey(nft1.publicKey),
-      buyerNftAccount.address,
-      true
-    ).accounts({
+    await program.methods.createOrder(orderId, {
+      amount: new anchor.BN(1000),
+      expiration: new anchor.BN(now + 3600),
+      nftMint: new PublicKey(nft1.publicKey),
+      collectionMint: null,
+      buyerNftAccount: buyerNftAccount.address,
+      isNft: true,
+      arbitrator: null,
+      seller: null,
+      openOffer: true,
+      releaseDelay: null,
+      milestones: []
+    }).accounts({
       mint,
       tokenProgram: TOKEN_PROGRAM_ID
     }).rpc();
     
-    const [escrowAddress] = PublicKey.findProgramAddressSync(
-      [Buffer.from("order"), payer.publicKey.toBuffer()],
-      program.programId
-    );
+    const escrowAddress = getEscrowAddress(payer.publicKey, orderId);
 
     const escrowData = await program.account.escrow.fetch(escrowAddress);
     console.log("escrowData: ", escrowData);
 
     assert.strictEqual(escrowData.amount.toNumber(), 1000, "订单金额不正确");
     assert(escrowData.expiration.toNumber() > now, "订单过期时间不合理");
+    assert(escrowData.orderId.eq(orderId), "订单编号不正确");
+  });
+
+  it("Is create concurrent order", async () => {
+    // 同一买家使用不同订单编号可同时持有多个订单
+    const secondOrderId = new anchor.BN(2);
+    const now = Math.floor(Date.now() / 1000);
+
+    await program.methods.createOrder(secondOrderId, {
+      amount: new anchor.BN(500),
+      expiration: new anchor.BN(now + 3600),
+      nftMint: null,
+      collectionMint: null,
+      buyerNftAccount: null,
+      isNft: false,
+      arbitrator: null,
+      seller: null,
+      openOffer: true,
+      releaseDelay: null,
+      milestones: []
+    }).accounts({
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    const firstEscrow = await program.account.escrow.fetch(getEscrowAddress(payer.publicKey, orderId));
+    const secondEscrow = await program.account.escrow.fetch(getEscrowAddress(payer.publicKey, secondOrderId));
+
+    assert.strictEqual(firstEscrow.amount.toNumber(), 1000, "第一个订单被覆盖");
+    assert.strictEqual(secondEscrow.amount.toNumber(), 500, "第二个订单金额不正确");
+    assert(!secondEscrow.escrowVault.equals(firstEscrow.escrowVault), "订单托管账户不应共用");
+  });
+
+  it("create order with far expiration fails", async () => {
+    const now = Math.floor(Date.now() / 1000);
+
+    // 超时时间最多比当前时间晚 30 天
+    await expectAnchorError(
+      program.methods.createOrder(new anchor.BN(99), {
+        amount: new anchor.BN(500),
+        expiration: new anchor.BN(now + 31 * 24 * 60 * 60),
+        nftMint: null,
+        collectionMint: null,
+        buyerNftAccount: null,
+        isNft: false,
+        arbitrator: null,
+        seller: null,
+        openOffer: true,
+        releaseDelay: null,
+        milestones: []
+      }).accounts({
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID
+      }).rpc(),
+      "ExpirationTooFar"
+    );
   });
 
   it("Buyer Payment",async () => {
-    await program.methods.buyerPayment().accounts({
+    await program.methods.buyerPayment(orderId).accounts({
       buyer: payer.publicKey,
       mint,
       tokenProgram: TOKEN_PROGRAM_ID
     }).rpc();
 
-    const [escrowAddress] = PublicKey.findProgramAddressSync(
-      [Buffer.from("order"), payer.publicKey.toBuffer()],
-      program.programId
-    );
+    const escrowAddress = getEscrowAddress(payer.publicKey, orderId);
 
     const escrowData = await program.account.escrow.fetch(escrowAddress);
     console.log("Buyer Payment: ", escrowData);
 
-    assert.strictEqual(escrowData.status, Funded, "订单状态未更新为 Funded");
+    assert.deepStrictEqual(escrowData.status, Funded, "订单状态未更新为 Funded");
     // 校验托管账户余额
     const escrowVaultBalance = await getTokenBalance(escrowData.escrowVault);
     assert.strictEqual(escrowVaultBalance, 1000 / 100, "托管账户余额不正确");
   });
 
   // it("order cancellation", async () => {
-  //   await program.methods.orderCancellation().accounts({
+  //   await program.methods.orderCancellation(orderId).accounts({
   //     buyer: payer.publicKey,
   //     mint,
   //     tokenProgram: TOKEN_PROGRAM_ID
   //   }).rpc();
 
-  //   const [escrowAddress] = PublicKey.findProgramAddressSync(
-  //     [Buffer.from("order"), payer.publicKey.toBuffer()],
-  //     program.programId
-  //   );
+  //   const escrowAddress = getEscrowAddress(payer.publicKey, orderId);
 
   //   const escrowData = await program.account.escrow.fetch(escrowAddress);
   //   console.log("order cancellation: ", escrowData);
 
   //   // 添加校验：订单状态应为 Cancelled
-  //   assert.strictEqual(escrowData.status, Expired, "订单状态未更新为 Expired");
+  //   assert.deepStrictEqual(escrowData.status, Expired, "订单状态未更新为 Expired");
   //   // 买家退款到账，假设 buyerTokenAccount 为买家的 ATA
   //   const buyerBalance = await getTokenBalance(tokenAccount.address);
   //   assert.strictEqual(buyerBalance, 1000, "买家未收到退款");
@@ -291,15 +431,16 @@ describe("sol-escrow", () => {
     );
 
     // 获取订单信息
-    const [escrowAddress1] = PublicKey.findProgramAddressSync(
-      [Buffer.from("order"), payer.publicKey.toBuffer()],
-      program.programId
-    );
+    const escrowAddress1 = getEscrowAddress(payer.publicKey, orderId);
 
     const escrowData1 = await program.account.escrow.fetch(escrowAddress1);
+    // 卖家确认时 NFT 先存入托管账户
+    const nftVault = getAssociatedTokenAddressSync(new PublicKey(nft1.publicKey), escrowAddress1, true);
+    const [nftMetadata] = findMetadataPda(umi, { mint: nft1.publicKey });
+    const creatorBalanceBefore = await getTokenAmount(tokenAccount.address);
 
     // 卖家确认指令对象
-    const sellerConfirmationIx = await program.methods.sellerConfirmation()
+    const sellerConfirmationIx = await program.methods.sellerConfirmation(orderId, ...(await getOrderTerms(payer.publicKey, orderId)))
       .accounts({
         seller: seller.publicKey,
         buyer: payer.publicKey,
@@ -307,16 +448,29 @@ describe("sol-escrow", () => {
         sellerNftAccount: sellerNftAccount1.address,
         buyerNftAccount: escrowData1.buyerNftAccount,
         nftMint: nft1.publicKey,
+        nftMetadata,
+        nftVault,
       }).instruction();
 
-    // 合约转账指令对象
-    const escrowReleaseIx = await program.methods.escrowRelease()
+    // 合约转账指令对象，托管的 NFT 同时交付买家
+    const escrowReleaseIx = await program.methods.escrowRelease(orderId)
       .accounts({
+        authority: payer.publicKey,
         buyer: payer.publicKey,
         seller: seller.publicKey,
         mint,
-        tokenProgram: TOKEN_PROGRAM_ID
-      }).instruction()
+        tokenProgram: TOKEN_PROGRAM_ID,
+        treasuryTokenAccount: treasuryToken.address,
+        nftMint: nft1.publicKey,
+        nftVault,
+        buyerNftAccount: escrowData1.buyerNftAccount,
+        nftTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      // 已验证创作者的版税收款账户
+      .remainingAccounts([
+        { pubkey: tokenAccount.address, isWritable: true, isSigner: false },
+      ])
+      .instruction()
 
     // 创建新交易
     const tx = new anchor.web3.Transaction();
@@ -324,21 +478,991 @@ describe("sol-escrow", () => {
 
     await provider.sendAndConfirm(tx, [seller]);
 
-    const [escrowAddress] = PublicKey.findProgramAddressSync(
-      [Buffer.from("order"), payer.publicKey.toBuffer()],
-      program.programId
-    );
+    const escrowAddress = getEscrowAddress(payer.publicKey, orderId);
 
     const escrowData = await program.account.escrow.fetch(escrowAddress);
     console.log("seller confirmation: ", escrowData);
 
     // 添加校验：订单状态应为 Success
-    assert.strictEqual(escrowData.status, Success, "订单状态未更新为 Success");
-    // 校验卖家到账：卖家账户余额应为10
+    assert.deepStrictEqual(escrowData.status, Success, "订单状态未更新为 Success");
+    // 校验卖家到账：扣除 1% 手续费与 5% 版税后卖家账户余额应为9.4
     const sellerBalance = await getTokenBalance(sellerToken.address);
-    assert.strictEqual(sellerBalance, 10, "卖家未收到正确资金");
+    assert.strictEqual(sellerBalance, 9.4, "卖家未收到正确资金");
+    assert.strictEqual(await getTokenAmount(treasuryToken.address), 10, "手续费未转入收款账户");
+    assert.strictEqual(escrowData.royaltyBps, 500, "版税基点未记录");
+    assert.strictEqual(
+      await getTokenAmount(tokenAccount.address) - creatorBalanceBefore,
+      50,
+      "创作者未收到版税"
+    );
     // 托管账户余额应为0
     const escrowVaultBalance = await getTokenBalance(escrowData.escrowVault);
     assert.strictEqual(escrowVaultBalance, 0, "托管账户余额未归零");
+    // 买家收到 NFT，NFT 托管账户已关闭
+    assert.strictEqual(await getTokenBalance(escrowData1.buyerNftAccount), 1, "买家未收到 NFT");
+    assert.strictEqual(await connection.getAccountInfo(nftVault), null, "NFT 托管账户未关闭");
+  });
+
+  it("close order", async () => {
+    const escrowAddress = getEscrowAddress(payer.publicKey, orderId);
+    const escrowData = await program.account.escrow.fetch(escrowAddress);
+
+    await program.methods.closeOrder(orderId).accounts({
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    // 订单账户与托管代币账户均应被关闭
+    assert.strictEqual(await connection.getAccountInfo(escrowAddress), null, "订单账户未关闭");
+    assert.strictEqual(await connection.getAccountInfo(escrowData.escrowVault), null, "托管账户未关闭");
+  });
+
+  it("unauthorized buyer payment fails", async () => {
+    const ix = await program.methods.buyerPayment(new anchor.BN(2)).accounts({
+      buyer: payer.publicKey,
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).instruction();
+
+    await expectMissingSignature(ix);
+  });
+
+  it("Buyer Payment for concurrent order", async () => {
+    await program.methods.buyerPayment(new anchor.BN(2)).accounts({
+      buyer: payer.publicKey,
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    const escrowData = await program.account.escrow.fetch(getEscrowAddress(payer.publicKey, new anchor.BN(2)));
+    assert.deepStrictEqual(escrowData.status, Funded, "订单状态未更新为 Funded");
+  });
+
+  it("unauthorized cancellation fails", async () => {
+    const ix = await program.methods.orderCancellation(new anchor.BN(2)).accounts({
+      buyer: payer.publicKey,
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).instruction();
+
+    await expectMissingSignature(ix);
+  });
+
+  it("unauthorized release fails", async () => {
+    await program.methods.sellerConfirmation(new anchor.BN(2), ...(await getOrderTerms(payer.publicKey, new anchor.BN(2)))).accounts({
+      seller: seller.publicKey,
+      buyer: payer.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      sellerNftAccount: null,
+      buyerNftAccount: null,
+      nftMint: null,
+      nftMetadata: null,
+      nftVault: null,
+    }).signers([seller]).rpc();
+
+    // 第三方不能放款
+    await expectAnchorError(
+      program.methods.escrowRelease(new anchor.BN(2)).accounts({
+        authority: attacker.publicKey,
+        buyer: payer.publicKey,
+        seller: seller.publicKey,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        treasuryTokenAccount: treasuryToken.address,
+        nftMint: null,
+        nftVault: null,
+        buyerNftAccount: null,
+        nftTokenProgram: null
+      }).signers([attacker]).rpc(),
+      "Unauthorized"
+    );
+
+    // 买家也不能把资金放给订单之外的卖家
+    await expectAnchorError(
+      program.methods.escrowRelease(new anchor.BN(2)).accounts({
+        authority: payer.publicKey,
+        buyer: payer.publicKey,
+        seller: attacker.publicKey,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        treasuryTokenAccount: treasuryToken.address,
+        nftMint: null,
+        nftVault: null,
+        buyerNftAccount: null,
+        nftTokenProgram: null
+      }).rpc(),
+      "InvalidSeller"
+    );
+
+    // 未设置自动放款的订单，卖家不能自行领取
+    await expectAnchorError(
+      program.methods.escrowRelease(new anchor.BN(2)).accounts({
+        authority: seller.publicKey,
+        buyer: payer.publicKey,
+        seller: seller.publicKey,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        treasuryTokenAccount: treasuryToken.address,
+        nftMint: null,
+        nftVault: null,
+        buyerNftAccount: null,
+        nftTokenProgram: null
+      }).signers([seller]).rpc(),
+      "ReleaseNotReady"
+    );
+  });
+
+  it("close unsettled order fails", async () => {
+    // 订单2仍处于 InTransit 状态，不允许关闭
+    await expectAnchorError(
+      program.methods.closeOrder(new anchor.BN(2)).accounts({
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID
+      }).rpc(),
+      "OrderNotSettled"
+    );
+  });
+
+  it("dispute resolved by arbitrator", async () => {
+    const disputeOrderId = new anchor.BN(3);
+    const now = Math.floor(Date.now() / 1000);
+
+    await program.methods.createOrder(disputeOrderId, {
+      amount: new anchor.BN(1000),
+      expiration: new anchor.BN(now + 3600),
+      nftMint: null,
+      collectionMint: null,
+      buyerNftAccount: null,
+      isNft: false,
+      arbitrator: arbitrator.publicKey,
+      seller: seller.publicKey,
+      openOffer: false,
+      releaseDelay: null,
+      milestones: []
+    }).accounts({
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    await program.methods.buyerPayment(disputeOrderId).accounts({
+      buyer: payer.publicKey,
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    // 指定卖家的订单不能被其他人确认
+    await expectAnchorError(
+      program.methods.sellerConfirmation(disputeOrderId, ...(await getOrderTerms(payer.publicKey, disputeOrderId))).accounts({
+        seller: attacker.publicKey,
+        buyer: payer.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        sellerNftAccount: null,
+        buyerNftAccount: null,
+        nftMint: null,
+        nftMetadata: null,
+        nftVault: null,
+      }).signers([attacker]).rpc(),
+      "InvalidSeller"
+    );
+
+    await program.methods.sellerConfirmation(disputeOrderId, ...(await getOrderTerms(payer.publicKey, disputeOrderId))).accounts({
+      seller: seller.publicKey,
+      buyer: payer.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      sellerNftAccount: null,
+      buyerNftAccount: null,
+      nftMint: null,
+      nftMetadata: null,
+      nftVault: null,
+    }).signers([seller]).rpc();
+
+    // 买家发起争议
+    await program.methods.openDispute(disputeOrderId).accounts({
+      signer: payer.publicKey,
+      buyer: payer.publicKey,
+    }).rpc();
+
+    const escrowAddress = getEscrowAddress(payer.publicKey, disputeOrderId);
+    let escrowData = await program.account.escrow.fetch(escrowAddress);
+    assert.deepStrictEqual(escrowData.status, Disputed, "订单状态未更新为 Disputed");
+
+    // 非仲裁者不能裁决
+    await expectAnchorError(
+      program.methods.resolveDispute(disputeOrderId, 5000).accounts({
+        arbitrator: attacker.publicKey,
+        buyer: payer.publicKey,
+        seller: seller.publicKey,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        treasuryTokenAccount: treasuryToken.address,
+        nftMint: null,
+        nftVault: null,
+        nftDestination: null,
+        nftTokenProgram: null
+      }).signers([attacker]).rpc(),
+      "Unauthorized"
+    );
+
+    const sellerToken = await getOrCreateAssociatedTokenAccount(connection, payer, mint, seller.publicKey);
+    const sellerBefore = await getTokenAmount(sellerToken.address);
+    const treasuryBefore = await getTokenAmount(treasuryToken.address);
+    const buyerBefore = await getTokenBalance(tokenAccount.address);
+
+    // 仲裁者按 50% 拆分
+    await program.methods.resolveDispute(disputeOrderId, 5000).accounts({
+      arbitrator: arbitrator.publicKey,
+      buyer: payer.publicKey,
+      seller: seller.publicKey,
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      treasuryTokenAccount: treasuryToken.address,
+      nftMint: null,
+      nftVault: null,
+      nftDestination: null,
+      nftTokenProgram: null
+    }).signers([arbitrator]).rpc();
+
+    escrowData = await program.account.escrow.fetch(escrowAddress);
+    assert.deepStrictEqual(escrowData.status, Success, "订单状态未更新为 Success");
+    // 卖家所得 500 扣除 1% 协议手续费
+    assert.strictEqual(await getTokenAmount(sellerToken.address), sellerBefore + 495, "卖家未收到一半资金");
+    assert.strictEqual(await getTokenAmount(treasuryToken.address), treasuryBefore + 5, "手续费未转入收款账户");
+    assert.strictEqual(await getTokenBalance(tokenAccount.address), buyerBefore + 5, "买家未收到一半退款");
+    assert.strictEqual(await getTokenBalance(escrowData.escrowVault), 0, "托管账户余额未归零");
+  });
+
+  it("collection order accepts any verified NFT", async () => {
+    const collectionOrderId = new anchor.BN(4);
+    const now = Math.floor(Date.now() / 1000);
+
+    await program.methods.createOrder(collectionOrderId, {
+      amount: new anchor.BN(1000),
+      expiration: new anchor.BN(now + 3600),
+      nftMint: null,
+      collectionMint: new PublicKey(collectionMint.publicKey),
+      buyerNftAccount: null,
+      isNft: true,
+      arbitrator: null,
+      seller: null,
+      openOffer: true,
+      releaseDelay: null,
+      milestones: []
+    }).accounts({
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    await program.methods.buyerPayment(collectionOrderId).accounts({
+      buyer: payer.publicKey,
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    // 卖家交付集合内的 NFT 2
+    const buyerNftAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      payer,
+      new PublicKey(nft2.publicKey),
+      payer.publicKey
+    );
+    const [nftMetadata] = findMetadataPda(umi, { mint: nft2.publicKey });
+
+    await program.methods.sellerConfirmation(collectionOrderId, ...(await getOrderTerms(payer.publicKey, collectionOrderId))).accounts({
+      seller: seller.publicKey,
+      buyer: payer.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      sellerNftAccount: sellerNftAccount2.address,
+      buyerNftAccount: buyerNftAccount.address,
+      nftMint: nft2.publicKey,
+      nftMetadata,
+      nftVault: getAssociatedTokenAddressSync(
+        new PublicKey(nft2.publicKey),
+        getEscrowAddress(payer.publicKey, collectionOrderId),
+        true
+      ),
+    }).signers([seller]).rpc();
+
+    const escrowData = await program.account.escrow.fetch(getEscrowAddress(payer.publicKey, collectionOrderId));
+    assert.deepStrictEqual(escrowData.status, InTransit, "订单状态未更新为 InTransit");
+    assert(escrowData.nftMint.equals(new PublicKey(nft2.publicKey)), "未记录实际交付的 NFT");
+  });
+
+  it("seller listing filled by buyer", async () => {
+    // 卖家挂单出售的同质化资产
+    const assetMint = await createMint(connection, payer, payer.publicKey, null, 0);
+    const sellerAsset = await getOrCreateAssociatedTokenAccount(connection, payer, assetMint, seller.publicKey);
+    const buyerAsset = await getOrCreateAssociatedTokenAccount(connection, payer, assetMint, payer.publicKey);
+    await mintTo(connection, payer, assetMint, sellerAsset.address, payer, 100);
+
+    const listingId = new anchor.BN(1);
+    const [listingAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("listing"), seller.publicKey.toBuffer(), listingId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const now = Math.floor(Date.now() / 1000);
+
+    await program.methods.createListing(listingId, {
+      price: new anchor.BN(300),
+      assetAmount: new anchor.BN(50),
+      expiration: new anchor.BN(now + 3600),
+      isNft: false
+    }).accounts({
+      seller: seller.publicKey,
+      mint,
+      assetMint,
+      nftMetadata: null,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      assetTokenProgram: TOKEN_PROGRAM_ID
+    }).signers([seller]).rpc();
+
+    const listingData = await program.account.escrow.fetch(listingAddress);
+    assert(listingData.isListing, "未标记为挂单");
+    assert.strictEqual(await getTokenBalance(listingData.escrowVault), 50, "资产未存入托管账户");
+
+    const sellerToken = await getOrCreateAssociatedTokenAccount(connection, payer, mint, seller.publicKey);
+    const sellerBefore = await getTokenAmount(sellerToken.address);
+    const treasuryBefore = await getTokenAmount(treasuryToken.address);
+
+    await program.methods.fillListing(listingId).accounts({
+      buyer: payer.publicKey,
+      seller: seller.publicKey,
+      mint,
+      assetMint,
+      treasuryTokenAccount: treasuryToken.address,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      assetTokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    assert.strictEqual(await getTokenBalance(buyerAsset.address), 50, "买家未收到资产");
+    // 成交价 300 扣除 1% 协议手续费
+    assert.strictEqual(await getTokenAmount(sellerToken.address), sellerBefore + 297, "卖家未收到货款");
+    assert.strictEqual(await getTokenAmount(treasuryToken.address), treasuryBefore + 3, "手续费未转入收款账户");
+    assert.strictEqual(await connection.getAccountInfo(listingAddress), null, "挂单账户未关闭");
+  });
+
+  it("seller listing cancelled", async () => {
+    // 单个 0 位小数资产视为 NFT，需要元数据，这里挂单两份同质化资产
+    const assetMint = await createMint(connection, payer, payer.publicKey, null, 0);
+    const sellerAsset = await getOrCreateAssociatedTokenAccount(connection, payer, assetMint, seller.publicKey);
+    await mintTo(connection, payer, assetMint, sellerAsset.address, payer, 2);
+
+    const listingId = new anchor.BN(2);
+    const now = Math.floor(Date.now() / 1000);
+
+    await program.methods.createListing(listingId, {
+      price: new anchor.BN(300),
+      assetAmount: new anchor.BN(2),
+      expiration: new anchor.BN(now + 3600),
+      isNft: false
+    }).accounts({
+      seller: seller.publicKey,
+      mint,
+      assetMint,
+      nftMetadata: null,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      assetTokenProgram: TOKEN_PROGRAM_ID
+    }).signers([seller]).rpc();
+
+    assert.strictEqual(await getTokenBalance(sellerAsset.address), 0, "资产未存入托管账户");
+
+    await program.methods.cancelListing(listingId).accounts({
+      seller: seller.publicKey,
+      assetMint,
+      assetTokenProgram: TOKEN_PROGRAM_ID
+    }).signers([seller]).rpc();
+
+    assert.strictEqual(await getTokenBalance(sellerAsset.address), 2, "资产未退还卖家");
+  });
+
+  it("milestone releases pay the seller incrementally", async () => {
+    const milestoneOrderId = new anchor.BN(5);
+    const now = Math.floor(Date.now() / 1000);
+
+    await program.methods.createOrder(milestoneOrderId, {
+      amount: new anchor.BN(1000),
+      expiration: new anchor.BN(now + 3600),
+      nftMint: null,
+      collectionMint: null,
+      buyerNftAccount: null,
+      isNft: false,
+      arbitrator: null,
+      seller: seller.publicKey,
+      openOffer: false,
+      releaseDelay: null,
+      milestones: [
+        { amount: new anchor.BN(400), deadline: new anchor.BN(now + 1800) },
+        { amount: new anchor.BN(600), deadline: null }
+      ]
+    }).accounts({
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    await program.methods.buyerPayment(milestoneOrderId).accounts({
+      buyer: payer.publicKey,
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    await program.methods.sellerConfirmation(milestoneOrderId, ...(await getOrderTerms(payer.publicKey, milestoneOrderId))).accounts({
+      seller: seller.publicKey,
+      buyer: payer.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      sellerNftAccount: null,
+      buyerNftAccount: null,
+      nftMint: null,
+      nftMetadata: null,
+      nftVault: null,
+    }).signers([seller]).rpc();
+
+    const releaseAccounts = {
+      authority: payer.publicKey,
+      buyer: payer.publicKey,
+      seller: seller.publicKey,
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      treasuryTokenAccount: treasuryToken.address,
+      nftMint: null,
+      nftVault: null,
+      buyerNftAccount: null,
+      nftTokenProgram: null
+    };
+    const sellerToken = await getOrCreateAssociatedTokenAccount(connection, payer, mint, seller.publicKey);
+    const sellerBefore = await getTokenAmount(sellerToken.address);
+    const treasuryBefore = await getTokenAmount(treasuryToken.address);
+    const escrowAddress = getEscrowAddress(payer.publicKey, milestoneOrderId);
+
+    // 非管理员不能设置代币手续费
+    await expectAnchorError(
+      program.methods.setMintFee(250).accounts({ admin: attacker.publicKey, mint }).signers([attacker]).rpc(),
+      "Unauthorized"
+    );
+    // 该代币手续费覆盖为 2%
+    await program.methods.setMintFee(200).accounts({ admin: payer.publicKey, mint }).rpc();
+
+    // 第一个里程碑放款
+    await program.methods.releaseMilestone(milestoneOrderId, 0).accounts(releaseAccounts).rpc();
+
+    let escrowData = await program.account.escrow.fetch(escrowAddress);
+    assert.deepStrictEqual(escrowData.status, InTransit, "部分放款后订单应保持 InTransit");
+    assert.strictEqual(escrowData.releasedAmount.toNumber(), 400, "已放款金额不正确");
+    assert.strictEqual(await getTokenAmount(sellerToken.address), sellerBefore + 392, "卖家未收到第一期款项");
+
+    // 同一里程碑不能重复放款
+    await expectAnchorError(
+      program.methods.releaseMilestone(milestoneOrderId, 0).accounts(releaseAccounts).rpc(),
+      "MilestoneAlreadyReleased"
+    );
+
+    // 整体放款只释放剩余金额
+    await program.methods.escrowRelease(milestoneOrderId).accounts(releaseAccounts).rpc();
+
+    escrowData = await program.account.escrow.fetch(escrowAddress);
+    assert.deepStrictEqual(escrowData.status, Success, "订单状态未更新为 Success");
+    assert.strictEqual(await getTokenAmount(sellerToken.address), sellerBefore + 980, "卖家未收到剩余款项");
+    assert.strictEqual(await getTokenAmount(treasuryToken.address), treasuryBefore + 20, "手续费未按代币配置收取");
+    assert.strictEqual(await getTokenBalance(escrowData.escrowVault), 0, "托管账户余额未归零");
+  });
+
+  it("native SOL order moves lamports without wrapping", async () => {
+    const nativeOrderId = new anchor.BN(6);
+    const now = Math.floor(Date.now() / 1000);
+    const amount = anchor.web3.LAMPORTS_PER_SOL;
+
+    // 不传 mint 与托管代币账户，订单以原生 SOL 计价
+    await program.methods.createOrder(nativeOrderId, {
+      amount: new anchor.BN(amount),
+      expiration: new anchor.BN(now + 3600),
+      nftMint: null,
+      collectionMint: null,
+      buyerNftAccount: null,
+      isNft: false,
+      arbitrator: null,
+      seller: seller.publicKey,
+      openOffer: false,
+      releaseDelay: null,
+      milestones: []
+    }).accounts({
+      mint: null,
+      escrowVault: null,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    const escrowAddress = getEscrowAddress(payer.publicKey, nativeOrderId);
+    let escrowData = await program.account.escrow.fetch(escrowAddress);
+    assert(escrowData.isNative, "订单未标记为 SOL 计价");
+
+    const escrowRent = await connection.getBalance(escrowAddress);
+    await program.methods.buyerPayment(nativeOrderId).accounts({
+      buyer: payer.publicKey,
+      mint: null,
+      escrowVault: null,
+      buyerTokenAccount: null,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+    assert.strictEqual(await connection.getBalance(escrowAddress), escrowRent + amount, "SOL 未存入托管账户");
+
+    await program.methods.sellerConfirmation(nativeOrderId, ...(await getOrderTerms(payer.publicKey, nativeOrderId))).accounts({
+      seller: seller.publicKey,
+      buyer: payer.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      sellerNftAccount: null,
+      buyerNftAccount: null,
+      nftMint: null,
+      nftMetadata: null,
+      nftVault: null,
+    }).signers([seller]).rpc();
+
+    const sellerBefore = await connection.getBalance(seller.publicKey);
+    const treasuryBefore = await connection.getBalance(treasury.publicKey);
+    await program.methods.escrowRelease(nativeOrderId).accounts({
+      authority: payer.publicKey,
+      buyer: payer.publicKey,
+      seller: seller.publicKey,
+      mint: null,
+      escrowVault: null,
+      sellerTokenAccount: null,
+      treasuryTokenAccount: null,
+      treasury: treasury.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      nftMint: null,
+      nftVault: null,
+      buyerNftAccount: null,
+      nftTokenProgram: null
+    }).rpc();
+
+    // 扣除 1% 手续费后卖家收到 SOL，托管账户保留免租金额
+    const fee = amount * feeBps / 10000;
+    assert.strictEqual(await connection.getBalance(seller.publicKey), sellerBefore + amount - fee, "卖家未收到 SOL");
+    assert.strictEqual(await connection.getBalance(treasury.publicKey), treasuryBefore + fee, "手续费未转入收款方");
+    assert.strictEqual(await connection.getBalance(escrowAddress), escrowRent, "托管账户未保留免租金额");
+
+    escrowData = await program.account.escrow.fetch(escrowAddress);
+    assert.deepStrictEqual(escrowData.status, Success, "订单状态未更新为 Success");
+
+    await program.methods.closeOrder(nativeOrderId).accounts({
+      buyer: payer.publicKey,
+      mint: null,
+      escrowVault: null,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+    assert.strictEqual(await connection.getAccountInfo(escrowAddress), null, "订单账户未关闭");
+  });
+
+  // 创建带扩展的 Token-2022 代币
+  async function createToken2022Mint(
+    extensions: ExtensionType[],
+    initExtensions: (mint: PublicKey) => anchor.web3.TransactionInstruction[]
+  ): Promise<PublicKey> {
+    const mintKeypair = Keypair.generate();
+    const mintLen = getMintLen(extensions);
+    const lamports = await connection.getMinimumBalanceForRentExemption(mintLen);
+    const tx = new anchor.web3.Transaction().add(
+      SystemProgram.createAccount({
+        fromPubkey: payer.publicKey,
+        newAccountPubkey: mintKeypair.publicKey,
+        space: mintLen,
+        lamports,
+        programId: TOKEN_2022_PROGRAM_ID
+      }),
+      ...initExtensions(mintKeypair.publicKey),
+      createInitializeMintInstruction(mintKeypair.publicKey, 2, payer.publicKey, null, TOKEN_2022_PROGRAM_ID)
+    );
+    await provider.sendAndConfirm(tx, [mintKeypair]);
+    return mintKeypair.publicKey;
+  }
+
+  it("transfer fee mint records the net amount received", async () => {
+    // 1% 转账手续费
+    const feeMint = await createToken2022Mint([ExtensionType.TransferFeeConfig], (mint) => [
+      createInitializeTransferFeeConfigInstruction(mint, payer.publicKey, payer.publicKey, 100, BigInt(1_000_000), TOKEN_2022_PROGRAM_ID)
+    ]);
+    const buyerFeeToken = await getOrCreateAssociatedTokenAccount(
+      connection, payer, feeMint, payer.publicKey, false, undefined, undefined, TOKEN_2022_PROGRAM_ID
+    );
+    await mintTo(connection, payer, feeMint, buyerFeeToken.address, payer, 10_000, [], undefined, TOKEN_2022_PROGRAM_ID);
+
+    const feeOrderId = new anchor.BN(7);
+    const now = Math.floor(Date.now() / 1000);
+    await program.methods.createOrder(feeOrderId, {
+      amount: new anchor.BN(1000),
+      expiration: new anchor.BN(now + 3600),
+      nftMint: null,
+      collectionMint: null,
+      buyerNftAccount: null,
+      isNft: false,
+      arbitrator: null,
+      seller: seller.publicKey,
+      openOffer: false,
+      releaseDelay: null,
+      milestones: []
+    }).accounts({
+      mint: feeMint,
+      tokenProgram: TOKEN_2022_PROGRAM_ID
+    }).rpc();
+
+    await program.methods.buyerPayment(feeOrderId).accounts({
+      buyer: payer.publicKey,
+      mint: feeMint,
+      tokenProgram: TOKEN_2022_PROGRAM_ID
+    }).rpc();
+
+    // 托管账户实际收到 990，订单金额按实际到账记录
+    const escrowData = await program.account.escrow.fetch(getEscrowAddress(payer.publicKey, feeOrderId));
+    assert.strictEqual(escrowData.amount.toNumber(), 990, "订单金额未按实际到账记录");
+    assert.strictEqual(await getTokenAmount(escrowData.escrowVault), 990, "托管账户余额不正确");
+  });
+
+  it("non-transferable mint is rejected at order creation", async () => {
+    const soulboundMint = await createToken2022Mint([ExtensionType.NonTransferable], (mint) => [
+      createInitializeNonTransferableMintInstruction(mint, TOKEN_2022_PROGRAM_ID)
+    ]);
+    const now = Math.floor(Date.now() / 1000);
+
+    await expectAnchorError(
+      program.methods.createOrder(new anchor.BN(8), {
+        amount: new anchor.BN(1000),
+        expiration: new anchor.BN(now + 3600),
+        nftMint: null,
+        collectionMint: null,
+        buyerNftAccount: null,
+        isNft: false,
+        arbitrator: null,
+        seller: seller.publicKey,
+        openOffer: false,
+        releaseDelay: null,
+        milestones: []
+      }).accounts({
+        mint: soulboundMint,
+        tokenProgram: TOKEN_2022_PROGRAM_ID
+      }).rpc(),
+      "NonTransferableMint"
+    );
+  });
+
+  it("non-transferable asset is rejected at listing creation", async () => {
+    const soulboundMint = await createToken2022Mint([ExtensionType.NonTransferable], (mint) => [
+      createInitializeNonTransferableMintInstruction(mint, TOKEN_2022_PROGRAM_ID)
+    ]);
+    await getOrCreateAssociatedTokenAccount(
+      connection, payer, soulboundMint, seller.publicKey, false, undefined, undefined, TOKEN_2022_PROGRAM_ID
+    );
+    const now = Math.floor(Date.now() / 1000);
+
+    await expectAnchorError(
+      program.methods.createListing(new anchor.BN(3), {
+        price: new anchor.BN(300),
+        assetAmount: new anchor.BN(10),
+        expiration: new anchor.BN(now + 3600),
+        isNft: false
+      }).accounts({
+        seller: seller.publicKey,
+        mint,
+        assetMint: soulboundMint,
+        nftMetadata: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        assetTokenProgram: TOKEN_2022_PROGRAM_ID
+      }).signers([seller]).rpc(),
+      "NonTransferableMint"
+    );
+  });
+
+  it("buyer amends a funded order before seller confirmation", async () => {
+    const amendOrderId = new anchor.BN(9);
+    const now = Math.floor(Date.now() / 1000);
+    await program.methods.createOrder(amendOrderId, {
+      amount: new anchor.BN(1000),
+      expiration: new anchor.BN(now + 3600),
+      nftMint: null,
+      collectionMint: null,
+      buyerNftAccount: null,
+      isNft: false,
+      arbitrator: null,
+      seller: seller.publicKey,
+      openOffer: false,
+      releaseDelay: null,
+      milestones: []
+    }).accounts({
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    const paymentAccounts = {
+      buyer: payer.publicKey,
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    };
+    await program.methods.buyerPayment(amendOrderId).accounts(paymentAccounts).rpc();
+
+    const escrowAddress = getEscrowAddress(payer.publicKey, amendOrderId);
+    let escrowData = await program.account.escrow.fetch(escrowAddress);
+
+    // 提高报价：补足差额
+    await program.methods.amendOrder(amendOrderId, new anchor.BN(1500), new anchor.BN(now + 7200))
+      .accounts(paymentAccounts).rpc();
+    escrowData = await program.account.escrow.fetch(escrowAddress);
+    assert.strictEqual(escrowData.amount.toNumber(), 1500, "订单金额未更新");
+    assert.strictEqual(escrowData.expiration.toNumber(), now + 7200, "超时时间未更新");
+    assert.strictEqual(await getTokenAmount(escrowData.escrowVault), 1500, "差额未存入托管账户");
+
+    // 降低报价：退还多余资金
+    const buyerBefore = await getTokenAmount(tokenAccount.address);
+    await program.methods.amendOrder(amendOrderId, new anchor.BN(800), new anchor.BN(now + 7200))
+      .accounts(paymentAccounts).rpc();
+    assert.strictEqual(await getTokenAmount(escrowData.escrowVault), 800, "多余资金未转出");
+    assert.strictEqual(await getTokenAmount(tokenAccount.address), buyerBefore + 700, "多余资金未退还买家");
+
+    // 卖家确认后不能再修改
+    await program.methods.sellerConfirmation(amendOrderId, ...(await getOrderTerms(payer.publicKey, amendOrderId))).accounts({
+      seller: seller.publicKey,
+      buyer: payer.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      sellerNftAccount: null,
+      buyerNftAccount: null,
+      nftMint: null,
+      nftMetadata: null,
+      nftVault: null,
+    }).signers([seller]).rpc();
+    await expectAnchorError(
+      program.methods.amendOrder(amendOrderId, new anchor.BN(900), new anchor.BN(now + 7200))
+        .accounts(paymentAccounts).rpc(),
+      "AmendNotAllowed"
+    );
+  });
+
+  it("seller counter offer accepted by buyer", async () => {
+    const counterOrderId = new anchor.BN(10);
+    const now = Math.floor(Date.now() / 1000);
+    await program.methods.createOrder(counterOrderId, {
+      amount: new anchor.BN(1000),
+      expiration: new anchor.BN(now + 3600),
+      nftMint: null,
+      collectionMint: null,
+      buyerNftAccount: null,
+      isNft: false,
+      arbitrator: null,
+      seller: null,
+      openOffer: true,
+      releaseDelay: null,
+      milestones: []
+    }).accounts({
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    const paymentAccounts = {
+      buyer: payer.publicKey,
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    };
+    await program.methods.buyerPayment(counterOrderId).accounts(paymentAccounts).rpc();
+
+    const escrowAddress = getEscrowAddress(payer.publicKey, counterOrderId);
+    const [counterOffer] = PublicKey.findProgramAddressSync(
+      [Buffer.from("counter_offer"), escrowAddress.toBuffer(), seller.publicKey.toBuffer()],
+      program.programId
+    );
+
+    // 卖家还价 1100 后撤回，还价账户关闭
+    await program.methods.proposeCounter(counterOrderId, new anchor.BN(1100), new anchor.BN(now + 7200))
+      .accounts({ seller: seller.publicKey, buyer: payer.publicKey })
+      .signers([seller])
+      .rpc();
+    await program.methods.closeCounter(counterOrderId)
+      .accounts({ signer: seller.publicKey, buyer: payer.publicKey, seller: seller.publicKey })
+      .signers([seller])
+      .rpc();
+    assert.strictEqual(await connection.getAccountInfo(counterOffer), null, "还价账户未关闭");
+
+    // 卖家重新还价 1200
+    await program.methods.proposeCounter(counterOrderId, new anchor.BN(1200), new anchor.BN(now + 7200))
+      .accounts({ seller: seller.publicKey, buyer: payer.publicKey })
+      .signers([seller])
+      .rpc();
+    const offer = await program.account.counterOffer.fetch(counterOffer);
+    assert.strictEqual(offer.amount.toNumber(), 1200, "还价金额不正确");
+
+    // 与买家确认的条款不一致时不能接受
+    await expectAnchorError(
+      program.methods.acceptCounter(counterOrderId, new anchor.BN(1100), new anchor.BN(now + 7200)).accounts({
+        payment: paymentAccounts,
+        seller: seller.publicKey,
+        counterOffer
+      }).rpc(),
+      "CounterOfferChanged"
+    );
+
+    // 买家接受还价：补足差额并绑定卖家
+    await program.methods.acceptCounter(counterOrderId, new anchor.BN(1200), new anchor.BN(now + 7200)).accounts({
+      payment: paymentAccounts,
+      seller: seller.publicKey,
+      counterOffer
+    }).rpc();
+
+    const escrowData = await program.account.escrow.fetch(escrowAddress);
+    assert.strictEqual(escrowData.amount.toNumber(), 1200, "订单金额未更新为还价金额");
+    assert.strictEqual(escrowData.expiration.toNumber(), now + 7200, "超时时间未更新");
+    assert(escrowData.seller.equals(seller.publicKey), "订单未绑定还价卖家");
+    assert(!escrowData.openOffer, "订单仍为公开报价");
+    assert.strictEqual(await getTokenAmount(escrowData.escrowVault), 1200, "差额未存入托管账户");
+    assert.strictEqual(await connection.getAccountInfo(counterOffer), null, "还价账户未关闭");
+
+    // 其他卖家不能再还价
+    await expectAnchorError(
+      program.methods.proposeCounter(counterOrderId, new anchor.BN(900), new anchor.BN(now + 7200))
+        .accounts({ seller: attacker.publicKey, buyer: payer.publicKey })
+        .signers([attacker])
+        .rpc(),
+      "InvalidSeller"
+    );
+  });
+
+  it("create and fund order in one instruction", async () => {
+    const fundedOrderId = new anchor.BN(11);
+    const now = Math.floor(Date.now() / 1000);
+    const buyerBefore = await getTokenAmount(tokenAccount.address);
+
+    await program.methods.createAndFundOrder(fundedOrderId, {
+      amount: new anchor.BN(1000),
+      expiration: new anchor.BN(now + 3600),
+      nftMint: null,
+      collectionMint: null,
+      buyerNftAccount: null,
+      isNft: false,
+      arbitrator: null,
+      seller: seller.publicKey,
+      openOffer: false,
+      releaseDelay: null,
+      milestones: []
+    }).accounts({
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    // 订单直接进入 Funded，资金已存入托管账户
+    const escrowData = await program.account.escrow.fetch(getEscrowAddress(payer.publicKey, fundedOrderId));
+    assert.deepStrictEqual(escrowData.status, Funded, "订单状态未更新为 Funded");
+    assert.strictEqual(await getTokenAmount(escrowData.escrowVault), 1000, "资金未存入托管账户");
+    assert.strictEqual(await getTokenAmount(tokenAccount.address), buyerBefore - 1000, "买家账户未扣款");
+  });
+
+  it("crank rejects orders that have not expired", async () => {
+    // 设置执行者奖励
+    await program.methods.updateConfig(null, treasury.publicKey, feeBps, new anchor.BN(5000))
+      .accounts({ admin: payer.publicKey })
+      .rpc();
+    const [configAddress] = PublicKey.findProgramAddressSync([Buffer.from("config")], program.programId);
+    const config = await program.account.config.fetch(configAddress);
+    assert.strictEqual(config.keeperBounty.toNumber(), 5000, "执行者奖励未更新");
+
+    // 订单 11 已付款但尚未超时
+    const fundedOrderId = new anchor.BN(11);
+    await expectAnchorError(
+      program.methods.crankExpired(fundedOrderId).accounts({
+        keeper: attacker.publicKey,
+        timeout: {
+          buyer: payer.publicKey,
+          mint,
+          escrow: getEscrowAddress(payer.publicKey, fundedOrderId),
+          escrowVault: getAssociatedTokenAddressSync(mint, getEscrowAddress(payer.publicKey, fundedOrderId), true),
+          buyerTokenAccount: tokenAccount.address,
+          seller: null,
+          nftMint: null,
+          nftVault: null,
+          sellerNftAccount: null,
+          nftTokenProgram: null,
+          tokenProgram: TOKEN_PROGRAM_ID
+        }
+      }).signers([attacker]).rpc(),
+      "OrderNotExpired"
+    );
+  });
+
+  it("seller claims funds after the release delay", async () => {
+    const autoReleaseOrderId = new anchor.BN(12);
+    const now = Math.floor(Date.now() / 1000);
+    await program.methods.createAndFundOrder(autoReleaseOrderId, {
+      amount: new anchor.BN(1000),
+      expiration: new anchor.BN(now + 3600),
+      nftMint: null,
+      collectionMint: null,
+      buyerNftAccount: null,
+      isNft: false,
+      arbitrator: null,
+      seller: seller.publicKey,
+      openOffer: false,
+      releaseDelay: new anchor.BN(1),
+      milestones: []
+    }).accounts({
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID
+    }).rpc();
+
+    await program.methods.sellerConfirmation(autoReleaseOrderId, ...(await getOrderTerms(payer.publicKey, autoReleaseOrderId))).accounts({
+      seller: seller.publicKey,
+      buyer: payer.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      sellerNftAccount: null,
+      buyerNftAccount: null,
+      nftMint: null,
+      nftMetadata: null,
+      nftVault: null,
+    }).signers([seller]).rpc();
+
+    const escrowAddress = getEscrowAddress(payer.publicKey, autoReleaseOrderId);
+    let escrowData = await program.account.escrow.fetch(escrowAddress);
+    assert(escrowData.releaseAfter !== null, "卖家确认时未设置自动放款时间");
+
+    // 等待自动放款时间到达后卖家自行领取
+    await new Promise((resolve) => setTimeout(resolve, 3000));
+    const sellerToken = await getOrCreateAssociatedTokenAccount(connection, payer, mint, seller.publicKey);
+    const sellerBefore = await getTokenAmount(sellerToken.address);
+    await program.methods.escrowRelease(autoReleaseOrderId).accounts({
+      authority: seller.publicKey,
+      buyer: payer.publicKey,
+      seller: seller.publicKey,
+      mint,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      treasuryTokenAccount: treasuryToken.address,
+      nftMint: null,
+      nftVault: null,
+      buyerNftAccount: null,
+      nftTokenProgram: null
+    }).signers([seller]).rpc();
+
+    escrowData = await program.account.escrow.fetch(escrowAddress);
+    assert.deepStrictEqual(escrowData.status, Success, "订单状态未更新为 Success");
+    assert.strictEqual(await getTokenAmount(sellerToken.address), sellerBefore + 980, "卖家未领取到款项");
+  });
+
+  it("timeout rejects unexpired and settled orders", async () => {
+    const timeoutAccounts = (id: anchor.BN) => ({
+      buyer: payer.publicKey,
+      mint,
+      escrowVault: getAssociatedTokenAddressSync(mint, getEscrowAddress(payer.publicKey, id), true),
+      buyerTokenAccount: tokenAccount.address,
+      seller: null,
+      nftMint: null,
+      nftVault: null,
+      sellerNftAccount: null,
+      nftTokenProgram: null,
+      tokenProgram: TOKEN_PROGRAM_ID
+    });
+
+    // 订单 11 已付款但尚未超时，不能退款
+    const fundedOrderId = new anchor.BN(11);
+    await expectAnchorError(
+      program.methods.timeoutCheck(fundedOrderId).accounts(timeoutAccounts(fundedOrderId)).rpc(),
+      "OrderNotExpired"
+    );
+    const escrowData = await program.account.escrow.fetch(getEscrowAddress(payer.publicKey, fundedOrderId));
+    assert.deepStrictEqual(escrowData.status, Funded, "未超时订单的状态被修改");
+
+    // 订单 12 已放款，不能被覆盖为 Expired
+    const settledOrderId = new anchor.BN(12);
+    await expectAnchorError(
+      program.methods.timeoutCheck(settledOrderId).accounts(timeoutAccounts(settledOrderId)).rpc(),
+      "OrderAlreadySettled"
+    );
   });
 });